@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS idempotency_keys
+(
+    idempotency_key VARCHAR(255)            NOT NULL,
+    method          VARCHAR(10)             NOT NULL,
+    path            VARCHAR(255)            NOT NULL,
+    request_hash    VARCHAR(64)             NOT NULL,
+    status_code     SMALLINT,
+    content_type    VARCHAR(100),
+    response_body   BYTEA,
+    created_at      TIMESTAMP DEFAULT NOW() NOT NULL,
+    CONSTRAINT pk_idempotency_keys PRIMARY KEY (idempotency_key, method, path)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created_at ON idempotency_keys(created_at);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_zip_codes_city_id;
+DROP TABLE IF EXISTS zip_codes;
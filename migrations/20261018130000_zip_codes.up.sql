@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS zip_codes
+(
+    zip_code      VARCHAR(8)   NOT NULL PRIMARY KEY,
+    address       VARCHAR(100),
+    neighbourhood VARCHAR(60),
+    city_id       UUID         NOT NULL
+        CONSTRAINT fk_zip_codes_city_id
+            REFERENCES cities (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_zip_codes_city_id ON zip_codes(city_id);
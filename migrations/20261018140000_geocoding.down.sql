@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS streets;
+
+ALTER TABLE addresses DROP COLUMN IF EXISTS geocoding_accuracy;
+
+ALTER TABLE cities DROP COLUMN IF EXISTS longitude;
+ALTER TABLE cities DROP COLUMN IF EXISTS latitude;
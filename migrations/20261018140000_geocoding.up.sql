@@ -0,0 +1,24 @@
+-- Add up migration script here
+ALTER TABLE cities ADD COLUMN IF NOT EXISTS latitude NUMERIC(10, 7);
+ALTER TABLE cities ADD COLUMN IF NOT EXISTS longitude NUMERIC(10, 7);
+
+ALTER TABLE addresses ADD COLUMN IF NOT EXISTS geocoding_accuracy VARCHAR(20);
+
+-- Addresses saved without coordinates used to be stored at 0, 0.
+UPDATE addresses SET latitude = NULL, longitude = NULL WHERE latitude = 0 AND longitude = 0;
+
+UPDATE addresses SET geocoding_accuracy = 'provided'
+WHERE latitude IS NOT NULL AND longitude IS NOT NULL AND geocoding_accuracy IS NULL;
+
+CREATE TABLE IF NOT EXISTS streets
+(
+    id              UUID           NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    name            VARCHAR(100)   NOT NULL,
+    normalized_name VARCHAR(100)   NOT NULL,
+    latitude        NUMERIC(10, 7) NOT NULL,
+    longitude       NUMERIC(10, 7) NOT NULL,
+    city_id         UUID           NOT NULL
+        CONSTRAINT fk_streets_city_id
+            REFERENCES cities (id),
+    CONSTRAINT unq_streets_city_id_normalized_name UNIQUE (city_id, normalized_name)
+);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS idx_addresses_latitude_longitude ON addresses(latitude, longitude);
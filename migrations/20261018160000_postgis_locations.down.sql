@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_routes_final_lat_final_long;
+DROP INDEX IF EXISTS idx_routes_initial_lat_initial_long;
+
+-- The indexes go away with the columns. The extension is kept, since other
+-- objects may depend on it.
+ALTER TABLE routes DROP COLUMN IF EXISTS final_location;
+ALTER TABLE routes DROP COLUMN IF EXISTS initial_location;
+ALTER TABLE addresses DROP COLUMN IF EXISTS location;
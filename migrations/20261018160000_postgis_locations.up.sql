@@ -0,0 +1,34 @@
+-- Add up migration script here
+-- PostGIS is optional: when it can't be installed, the geography columns are
+-- left out and spatial filters fall back to the numeric coordinates.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = 'postgis') THEN
+        RAISE NOTICE 'PostGIS is not available, skipping the geography columns';
+        RETURN;
+    END IF;
+
+    BEGIN
+        CREATE EXTENSION IF NOT EXISTS postgis;
+    EXCEPTION
+        WHEN insufficient_privilege THEN
+            RAISE NOTICE 'Not allowed to create the PostGIS extension, skipping the geography columns';
+            RETURN;
+    END;
+
+    -- Generated from the numeric columns, so they never drift apart.
+    EXECUTE 'ALTER TABLE addresses ADD COLUMN IF NOT EXISTS location GEOGRAPHY(POINT, 4326)
+        GENERATED ALWAYS AS (ST_SetSRID(ST_MakePoint(longitude::FLOAT8, latitude::FLOAT8), 4326)::GEOGRAPHY) STORED';
+    EXECUTE 'ALTER TABLE routes ADD COLUMN IF NOT EXISTS initial_location GEOGRAPHY(POINT, 4326)
+        GENERATED ALWAYS AS (ST_SetSRID(ST_MakePoint(initial_long::FLOAT8, initial_lat::FLOAT8), 4326)::GEOGRAPHY) STORED';
+    EXECUTE 'ALTER TABLE routes ADD COLUMN IF NOT EXISTS final_location GEOGRAPHY(POINT, 4326)
+        GENERATED ALWAYS AS (ST_SetSRID(ST_MakePoint(final_long::FLOAT8, final_lat::FLOAT8), 4326)::GEOGRAPHY) STORED';
+
+    CREATE INDEX IF NOT EXISTS idx_addresses_location ON addresses USING GIST (location);
+    CREATE INDEX IF NOT EXISTS idx_routes_initial_location ON routes USING GIST (initial_location);
+    CREATE INDEX IF NOT EXISTS idx_routes_final_location ON routes USING GIST (final_location);
+END
+$$;
+
+CREATE INDEX IF NOT EXISTS idx_routes_initial_lat_initial_long ON routes (initial_lat, initial_long);
+CREATE INDEX IF NOT EXISTS idx_routes_final_lat_final_long ON routes (final_lat, final_long);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS geofence_events;
+DROP TABLE IF EXISTS geofences;
+DROP TABLE IF EXISTS route_positions;
@@ -0,0 +1,81 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS route_positions
+(
+    id          UUID                    NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    route_id    UUID                    NOT NULL
+        CONSTRAINT fk_route_positions_route_id
+            REFERENCES routes (id) ON DELETE CASCADE,
+    latitude    NUMERIC(10, 8)          NOT NULL,
+    longitude   NUMERIC(11, 8)          NOT NULL,
+    recorded_at TIMESTAMP               NOT NULL,
+    created_at  TIMESTAMP DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_route_positions_route_id_recorded_at ON route_positions (route_id, recorded_at);
+
+-- A geofence is either a circle (center and radius) or a polygon (vertices,
+-- stored as [{"lat": ..., "long": ...}]). The bounding box is derived from
+-- either shape to narrow the geofences checked against a position.
+CREATE TABLE IF NOT EXISTS geofences
+(
+    id            UUID                    NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    name          VARCHAR(100)            NOT NULL,
+    kind          VARCHAR(20)             NOT NULL
+        CONSTRAINT chk_geofences_kind
+            CHECK (kind IN ('depot', 'customer_site', 'restricted_zone')),
+    shape         VARCHAR(10)             NOT NULL
+        CONSTRAINT chk_geofences_shape
+            CHECK (shape IN ('circle', 'polygon')),
+    center_lat    NUMERIC(10, 8),
+    center_long   NUMERIC(11, 8),
+    radius_meters NUMERIC(10, 2),
+    vertices      JSONB,
+    min_lat       DOUBLE PRECISION        NOT NULL,
+    max_lat       DOUBLE PRECISION        NOT NULL,
+    min_long      DOUBLE PRECISION        NOT NULL,
+    max_long      DOUBLE PRECISION        NOT NULL,
+    address_id    UUID
+        CONSTRAINT fk_geofences_address_id
+            REFERENCES addresses (id) ON DELETE SET NULL,
+    created_at    TIMESTAMP DEFAULT NOW() NOT NULL,
+    updated_at    TIMESTAMP DEFAULT NOW() NOT NULL,
+    CONSTRAINT chk_geofences_shape_fields
+        CHECK ((shape = 'circle' AND center_lat IS NOT NULL AND center_long IS NOT NULL
+                    AND radius_meters IS NOT NULL AND vertices IS NULL)
+            OR (shape = 'polygon' AND center_lat IS NULL AND center_long IS NULL
+                    AND radius_meters IS NULL AND vertices IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_geofences_bounds ON geofences (min_lat, max_lat, min_long, max_long);
+
+CREATE OR REPLACE TRIGGER update_updated_on_table
+    BEFORE UPDATE
+    ON
+        geofences
+    FOR EACH ROW
+EXECUTE PROCEDURE update_updated_on_table();
+
+CREATE TABLE IF NOT EXISTS geofence_events
+(
+    id          UUID                    NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    geofence_id UUID                    NOT NULL
+        CONSTRAINT fk_geofence_events_geofence_id
+            REFERENCES geofences (id) ON DELETE CASCADE,
+    route_id    UUID                    NOT NULL
+        CONSTRAINT fk_geofence_events_route_id
+            REFERENCES routes (id) ON DELETE CASCADE,
+    vehicle_id  UUID                    NOT NULL
+        CONSTRAINT fk_geofence_events_vehicle_id
+            REFERENCES vehicles (id) ON DELETE CASCADE,
+    event_type  VARCHAR(5)              NOT NULL
+        CONSTRAINT chk_geofence_events_event_type
+            CHECK (event_type IN ('enter', 'exit')),
+    latitude    NUMERIC(10, 8)          NOT NULL,
+    longitude   NUMERIC(11, 8)          NOT NULL,
+    occurred_at TIMESTAMP               NOT NULL,
+    created_at  TIMESTAMP DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_geofence_events_route_id_occurred_at ON geofence_events (route_id, occurred_at);
+CREATE INDEX IF NOT EXISTS idx_geofence_events_vehicle_id_occurred_at ON geofence_events (vehicle_id, occurred_at);
+CREATE INDEX IF NOT EXISTS idx_geofence_events_geofence_id_occurred_at ON geofence_events (geofence_id, occurred_at);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_addresses_search_text;
+ALTER TABLE addresses DROP COLUMN IF EXISTS search_text;
+DROP FUNCTION IF EXISTS immutable_unaccent(TEXT);
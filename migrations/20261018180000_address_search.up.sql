@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS unaccent;
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- unaccent() is only STABLE, as its dictionary could be changed, so it can't
+-- be used by a generated column. The dictionary is named to pin it down.
+CREATE OR REPLACE FUNCTION immutable_unaccent(TEXT) RETURNS TEXT AS $$
+    SELECT public.unaccent('public.unaccent'::REGDICTIONARY, $1)
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+-- The text matched by the address search, folded like the searched terms.
+ALTER TABLE addresses ADD COLUMN IF NOT EXISTS search_text TEXT
+    GENERATED ALWAYS AS (immutable_unaccent(LOWER(address || ' ' || number || ' ' || neighbourhood || ' ' || zip_code))) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_addresses_search_text ON addresses USING GIN (search_text gin_trgm_ops);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_cities_search_name;
+DROP INDEX IF EXISTS idx_states_search_name;
+DROP INDEX IF EXISTS idx_countries_search_name;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Trigram indexes serve the prefix searches by name, matched without case
+-- and accents.
+CREATE INDEX IF NOT EXISTS idx_countries_search_name ON countries USING GIN (immutable_unaccent(LOWER(name)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_states_search_name ON states USING GIN (immutable_unaccent(LOWER(name)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_cities_search_name ON cities USING GIN (immutable_unaccent(LOWER(name)) gin_trgm_ops);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS record_route_position_event ON route_positions;
+DROP TRIGGER IF EXISTS record_route_status_event ON routes;
+DROP TABLE IF EXISTS route_events;
+DROP FUNCTION IF EXISTS notify_route_event;
+DROP FUNCTION IF EXISTS record_route_position_event;
+DROP FUNCTION IF EXISTS record_route_status_event;
@@ -0,0 +1,82 @@
+-- Add up migration script here
+-- Changes streamed to the dispatch board. The id orders the events and lets a
+-- dropped connection resume where it stopped.
+CREATE TABLE IF NOT EXISTS route_events
+(
+    id         BIGSERIAL    NOT NULL PRIMARY KEY,
+    route_id   UUID         NOT NULL
+        CONSTRAINT fk_route_events_route_id
+            REFERENCES routes(id) ON DELETE CASCADE,
+    vehicle_id UUID         NOT NULL,
+    event_type VARCHAR(20)  NOT NULL
+        CONSTRAINT chk_route_events_event_type
+            CHECK (event_type IN ('status', 'position')),
+    data       JSONB        NOT NULL,
+    created_at TIMESTAMP    NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_route_events_route_id ON route_events(route_id, id);
+CREATE INDEX IF NOT EXISTS idx_route_events_vehicle_id ON route_events(vehicle_id, id);
+CREATE INDEX IF NOT EXISTS idx_route_events_created_at ON route_events(created_at);
+
+CREATE OR REPLACE FUNCTION record_route_status_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    -- Updates set the status even when it doesn't change.
+    IF TG_OP = 'UPDATE' AND OLD.status_id IS NOT DISTINCT FROM NEW.status_id THEN
+        RETURN NEW;
+    END IF;
+
+    INSERT INTO route_events (route_id, vehicle_id, event_type, data)
+    SELECT NEW.id, NEW.vehicle_id, 'status', jsonb_build_object(
+        'statusId', NEW.status_id,
+        'statusCode', s.code,
+        'previousStatusId', CASE WHEN TG_OP = 'UPDATE' THEN OLD.status_id END
+    )
+    FROM route_status s WHERE s.id = NEW.status_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER record_route_status_event
+    AFTER INSERT OR UPDATE OF status_id ON routes
+    FOR EACH ROW
+EXECUTE PROCEDURE record_route_status_event();
+
+CREATE OR REPLACE FUNCTION record_route_position_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO route_events (route_id, vehicle_id, event_type, data)
+    SELECT NEW.route_id, r.vehicle_id, 'position', jsonb_build_object(
+        'positionId', NEW.id,
+        'latitude', NEW.latitude::TEXT,
+        'longitude', NEW.longitude::TEXT,
+        'recordedAt', NEW.recorded_at
+    )
+    FROM routes r WHERE r.id = NEW.route_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER record_route_position_event
+    AFTER INSERT ON route_positions
+    FOR EACH ROW
+EXECUTE PROCEDURE record_route_position_event();
+
+-- Every server instance listens on this channel. Notifications are only
+-- delivered once the transaction commits.
+CREATE OR REPLACE FUNCTION notify_route_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('route_events', row_to_json(NEW)::TEXT);
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER notify_route_event
+    AFTER INSERT ON route_events
+    FOR EACH ROW
+EXECUTE PROCEDURE notify_route_event();
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS enqueue_route_webhook_event ON routes;
+DROP FUNCTION IF EXISTS enqueue_route_webhook_event();
+DROP FUNCTION IF EXISTS enqueue_webhook_event(VARCHAR, JSONB, VARCHAR);
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP TABLE IF EXISTS webhook_events;
+DROP TABLE IF EXISTS webhook_subscriptions;
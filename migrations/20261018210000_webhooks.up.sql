@@ -0,0 +1,131 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS webhook_subscriptions
+(
+    id          UUID                    NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    url         VARCHAR(2048)           NOT NULL,
+    event_types VARCHAR(40)[]           NOT NULL,
+    secret      VARCHAR(128)            NOT NULL,
+    active      BOOLEAN DEFAULT TRUE    NOT NULL,
+    created_at  TIMESTAMP DEFAULT NOW() NOT NULL,
+    updated_at  TIMESTAMP DEFAULT NOW() NOT NULL
+);
+
+CREATE OR REPLACE TRIGGER update_updated_on_table
+    BEFORE UPDATE
+    ON
+        webhook_subscriptions
+    FOR EACH ROW
+EXECUTE PROCEDURE update_updated_on_table();
+
+-- The outbox. Events are written in the transaction of the change they
+-- describe, so they are kept exactly when the change is. The dedupe key stops
+-- events detected by a periodic job from being recorded twice.
+CREATE TABLE IF NOT EXISTS webhook_events
+(
+    id         UUID                    NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    event_type VARCHAR(40)             NOT NULL,
+    data       JSONB                   NOT NULL,
+    dedupe_key VARCHAR(200)
+        CONSTRAINT unq_webhook_events_dedupe_key UNIQUE,
+    created_at TIMESTAMP DEFAULT NOW() NOT NULL
+);
+
+-- One delivery per event and subscription, kept as the delivery log.
+CREATE TABLE IF NOT EXISTS webhook_deliveries
+(
+    id                   UUID                     NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    event_id             UUID                     NOT NULL
+        CONSTRAINT fk_webhook_deliveries_event_id
+            REFERENCES webhook_events (id) ON DELETE CASCADE,
+    subscription_id      UUID                     NOT NULL
+        CONSTRAINT fk_webhook_deliveries_subscription_id
+            REFERENCES webhook_subscriptions (id) ON DELETE CASCADE,
+    status               VARCHAR(10) DEFAULT 'pending' NOT NULL
+        CONSTRAINT chk_webhook_deliveries_status
+            CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts             INTEGER DEFAULT 0        NOT NULL,
+    next_attempt_at      TIMESTAMP DEFAULT NOW()  NOT NULL,
+    last_response_status INTEGER,
+    last_error           TEXT,
+    delivered_at         TIMESTAMP,
+    created_at           TIMESTAMP DEFAULT NOW()  NOT NULL,
+    updated_at           TIMESTAMP DEFAULT NOW()  NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_pending ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_subscription_id_created_at ON webhook_deliveries (subscription_id, created_at);
+
+CREATE OR REPLACE TRIGGER update_updated_on_table
+    BEFORE UPDATE
+    ON
+        webhook_deliveries
+    FOR EACH ROW
+EXECUTE PROCEDURE update_updated_on_table();
+
+-- Records an event and a delivery for every active subscription to its type.
+-- Returns NULL when an event with the same dedupe key was already recorded.
+CREATE OR REPLACE FUNCTION enqueue_webhook_event(p_event_type VARCHAR, p_data JSONB, p_dedupe_key VARCHAR DEFAULT NULL)
+    RETURNS UUID AS $$
+DECLARE
+    v_event_id UUID;
+BEGIN
+    INSERT INTO webhook_events (event_type, data, dedupe_key)
+    VALUES (p_event_type, p_data, p_dedupe_key)
+    ON CONFLICT (dedupe_key) DO NOTHING
+    RETURNING id INTO v_event_id;
+
+    IF v_event_id IS NOT NULL THEN
+        INSERT INTO webhook_deliveries (event_id, subscription_id)
+        SELECT v_event_id, s.id
+        FROM webhook_subscriptions s
+        WHERE s.active AND p_event_type = ANY (s.event_types);
+    END IF;
+
+    RETURN v_event_id;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION enqueue_route_webhook_event()
+    RETURNS TRIGGER AS $$
+DECLARE
+    v_status_code VARCHAR;
+    v_event_type  VARCHAR;
+BEGIN
+    -- Updates set the status even when it doesn't change.
+    IF TG_OP = 'UPDATE' AND OLD.status_id IS NOT DISTINCT FROM NEW.status_id THEN
+        RETURN NEW;
+    END IF;
+
+    SELECT code INTO v_status_code FROM route_status WHERE id = NEW.status_id;
+
+    v_event_type := CASE
+        WHEN TG_OP = 'INSERT' THEN 'route.created'
+        WHEN v_status_code = 'STARTED' THEN 'route.started'
+        WHEN v_status_code = 'FINISHED' THEN 'route.finished'
+    END;
+
+    IF v_event_type IS NOT NULL THEN
+        PERFORM enqueue_webhook_event(v_event_type, jsonb_build_object(
+            'routeId', NEW.id,
+            'vehicleId', NEW.vehicle_id,
+            'driverId', NEW.driver_id,
+            'statusId', NEW.status_id,
+            'statusCode', v_status_code,
+            'initialLat', NEW.initial_lat::TEXT,
+            'initialLong', NEW.initial_long::TEXT,
+            'finalLat', NEW.final_lat::TEXT,
+            'finalLong', NEW.final_long::TEXT,
+            'totalDistance', NEW.total_distance::TEXT,
+            'startedAt', NEW.started_at,
+            'endedAt', NEW.ended_at
+        ));
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER enqueue_route_webhook_event
+    AFTER INSERT OR UPDATE OF status_id ON routes
+    FOR EACH ROW
+EXECUTE PROCEDURE enqueue_route_webhook_event();
@@ -0,0 +1,19 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS record_audit_log ON countries;
+DROP TRIGGER IF EXISTS record_audit_log ON states;
+DROP TRIGGER IF EXISTS record_audit_log ON cities;
+DROP TRIGGER IF EXISTS record_audit_log ON addresses;
+DROP TRIGGER IF EXISTS record_audit_log ON collaborators;
+DROP TRIGGER IF EXISTS record_audit_log ON drivers;
+DROP TRIGGER IF EXISTS record_audit_log ON cnh_types;
+DROP TRIGGER IF EXISTS record_audit_log ON vehicles;
+DROP TRIGGER IF EXISTS record_audit_log ON vehicles_documents;
+DROP TRIGGER IF EXISTS record_audit_log ON routes;
+DROP TRIGGER IF EXISTS record_audit_log ON route_status;
+DROP TRIGGER IF EXISTS record_audit_log ON geofences;
+DROP TRIGGER IF EXISTS record_audit_log ON webhook_subscriptions;
+DROP FUNCTION IF EXISTS record_audit_log();
+DROP TRIGGER IF EXISTS prevent_audit_log_change ON audit_log;
+DROP TRIGGER IF EXISTS prevent_audit_log_truncate ON audit_log;
+DROP FUNCTION IF EXISTS prevent_audit_log_change();
+DROP TABLE IF EXISTS audit_log;
@@ -0,0 +1,166 @@
+-- Add up migration script here
+-- Every change made to the entities, written by triggers so no code path can
+-- skip it. The actor and request id are set on the connection by the server
+-- before each use, and are NULL for changes made by background jobs.
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id          BIGSERIAL               NOT NULL PRIMARY KEY,
+    entity_type VARCHAR(40)             NOT NULL,
+    entity_id   UUID                    NOT NULL,
+    action      VARCHAR(6)              NOT NULL
+        CONSTRAINT chk_audit_log_action
+            CHECK (action IN ('create', 'update', 'delete')),
+    actor       VARCHAR(200),
+    request_id  VARCHAR(200),
+    -- The columns that changed, with their values before and after. Creations
+    -- only have `after` and deletions only have `before`.
+    before      JSONB,
+    after       JSONB,
+    occurred_at TIMESTAMP DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log (entity_type, entity_id, id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_occurred_at ON audit_log (occurred_at);
+
+CREATE OR REPLACE FUNCTION prevent_audit_log_change()
+    RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER prevent_audit_log_change
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW
+EXECUTE PROCEDURE prevent_audit_log_change();
+
+CREATE OR REPLACE TRIGGER prevent_audit_log_truncate
+    BEFORE TRUNCATE ON audit_log
+    FOR EACH STATEMENT
+EXECUTE PROCEDURE prevent_audit_log_change();
+
+-- Takes the entity type, then the columns left out of the log: timestamps
+-- and columns derived from others. Secrets are masked, but their changes are
+-- still recorded.
+CREATE OR REPLACE FUNCTION record_audit_log()
+    RETURNS TRIGGER AS $$
+DECLARE
+    v_ignored TEXT[] := ARRAY['updated_at'] || TG_ARGV[1:];
+    v_old     JSONB;
+    v_new     JSONB;
+    v_before  JSONB;
+    v_after   JSONB;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        v_old := to_jsonb(OLD) - v_ignored;
+    END IF;
+
+    IF TG_OP <> 'DELETE' THEN
+        v_new := to_jsonb(NEW) - v_ignored;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        SELECT jsonb_object_agg(o.key, o.value) INTO v_before
+        FROM jsonb_each(v_old) o
+        WHERE v_new -> o.key IS DISTINCT FROM o.value;
+
+        SELECT jsonb_object_agg(n.key, n.value) INTO v_after
+        FROM jsonb_each(v_new) n
+        WHERE v_old -> n.key IS DISTINCT FROM n.value;
+
+        -- Updates that rewrite the same values change nothing.
+        IF v_before IS NULL AND v_after IS NULL THEN
+            RETURN NULL;
+        END IF;
+    ELSE
+        v_before := v_old;
+        v_after := v_new;
+    END IF;
+
+    IF v_before ? 'secret' THEN
+        v_before := jsonb_set(v_before, '{secret}', '"***"');
+    END IF;
+
+    IF v_after ? 'secret' THEN
+        v_after := jsonb_set(v_after, '{secret}', '"***"');
+    END IF;
+
+    INSERT INTO audit_log (entity_type, entity_id, action, actor, request_id, before, after)
+    VALUES (
+        TG_ARGV[0],
+        COALESCE(NEW.id, OLD.id),
+        CASE TG_OP WHEN 'INSERT' THEN 'create' WHEN 'UPDATE' THEN 'update' ELSE 'delete' END,
+        NULLIF(current_setting('audit.actor', TRUE), ''),
+        NULLIF(current_setting('audit.request_id', TRUE), ''),
+        v_before,
+        v_after
+    );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON countries
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('country');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON states
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('state');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON cities
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('city');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON addresses
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('address', 'search_text', 'location');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON collaborators
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('collaborator');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON drivers
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('driver');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON cnh_types
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('cnh_type');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON vehicles
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('vehicle');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON vehicles_documents
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('vehicle_document');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON routes
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('route', 'initial_location', 'final_location');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON route_status
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('route_status');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON geofences
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('geofence', 'min_lat', 'max_lat', 'min_long', 'max_long');
+
+CREATE OR REPLACE TRIGGER record_audit_log
+    AFTER INSERT OR UPDATE OR DELETE ON webhook_subscriptions
+    FOR EACH ROW
+EXECUTE PROCEDURE record_audit_log('webhook_subscription');
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- When the request holding a key was taken. A key left without a response,
+-- by a crash or a client that went away, can be taken again once the lease
+-- is over.
+ALTER TABLE idempotency_keys
+    ADD COLUMN IF NOT EXISTS locked_at TIMESTAMP DEFAULT NOW() NOT NULL;
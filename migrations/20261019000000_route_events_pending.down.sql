@@ -0,0 +1,45 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS notify_pending_route_event ON route_events_pending;
+DROP FUNCTION IF EXISTS notify_pending_route_event;
+
+CREATE OR REPLACE FUNCTION record_route_status_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    -- Updates set the status even when it doesn't change.
+    IF TG_OP = 'UPDATE' AND OLD.status_id IS NOT DISTINCT FROM NEW.status_id THEN
+        RETURN NEW;
+    END IF;
+
+    INSERT INTO route_events (route_id, vehicle_id, event_type, data)
+    SELECT NEW.id, NEW.vehicle_id, 'status', jsonb_build_object(
+        'statusId', NEW.status_id,
+        'statusCode', s.code,
+        'previousStatusId', CASE WHEN TG_OP = 'UPDATE' THEN OLD.status_id END
+    )
+    FROM route_status s WHERE s.id = NEW.status_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION record_route_position_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO route_events (route_id, vehicle_id, event_type, data)
+    SELECT NEW.route_id, r.vehicle_id, 'position', jsonb_build_object(
+        'positionId', NEW.id,
+        'latitude', NEW.latitude::TEXT,
+        'longitude', NEW.longitude::TEXT,
+        'recordedAt', NEW.recorded_at
+    )
+    FROM routes r WHERE r.id = NEW.route_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Events not moved yet are kept.
+INSERT INTO route_events (route_id, vehicle_id, event_type, data, created_at)
+SELECT route_id, vehicle_id, event_type, data, created_at FROM route_events_pending ORDER BY id;
+
+DROP TABLE IF EXISTS route_events_pending;
@@ -0,0 +1,68 @@
+-- Add up migration script here
+-- The id of a route event used to be taken on insert, so an event committed
+-- after another could get a lower id, and a stream resuming after the later
+-- one skipped it. The triggers now write the events here, and the servers
+-- move them to route_events once committed, one at a time, which numbers them
+-- in the order they were committed.
+CREATE TABLE IF NOT EXISTS route_events_pending
+(
+    id         BIGSERIAL    NOT NULL PRIMARY KEY,
+    route_id   UUID         NOT NULL
+        CONSTRAINT fk_route_events_pending_route_id
+            REFERENCES routes(id) ON DELETE CASCADE,
+    vehicle_id UUID         NOT NULL,
+    event_type VARCHAR(20)  NOT NULL,
+    data       JSONB        NOT NULL,
+    created_at TIMESTAMP    NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION record_route_status_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    -- Updates set the status even when it doesn't change.
+    IF TG_OP = 'UPDATE' AND OLD.status_id IS NOT DISTINCT FROM NEW.status_id THEN
+        RETURN NEW;
+    END IF;
+
+    INSERT INTO route_events_pending (route_id, vehicle_id, event_type, data)
+    SELECT NEW.id, NEW.vehicle_id, 'status', jsonb_build_object(
+        'statusId', NEW.status_id,
+        'statusCode', s.code,
+        'previousStatusId', CASE WHEN TG_OP = 'UPDATE' THEN OLD.status_id END
+    )
+    FROM route_status s WHERE s.id = NEW.status_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION record_route_position_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO route_events_pending (route_id, vehicle_id, event_type, data)
+    SELECT NEW.route_id, r.vehicle_id, 'position', jsonb_build_object(
+        'positionId', NEW.id,
+        'latitude', NEW.latitude::TEXT,
+        'longitude', NEW.longitude::TEXT,
+        'recordedAt', NEW.recorded_at
+    )
+    FROM routes r WHERE r.id = NEW.route_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Wakes the servers up to move the new events once the transaction commits.
+CREATE OR REPLACE FUNCTION notify_pending_route_event()
+    RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('route_events_pending', '');
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER notify_pending_route_event
+    AFTER INSERT ON route_events_pending
+    FOR EACH STATEMENT
+EXECUTE PROCEDURE notify_pending_route_event();
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DELETE FROM idempotency_keys WHERE LENGTH(path) > 255;
+ALTER TABLE idempotency_keys ALTER COLUMN path TYPE VARCHAR(255);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- The path is part of the key, so a longer one failed the insert and the
+-- request with it.
+ALTER TABLE idempotency_keys ALTER COLUMN path TYPE TEXT;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+-- The administrators of the API, created with `rusty_roads create-admin`.
+-- Emails are stored in lowercase, so the constraint ignores the case.
+CREATE TABLE IF NOT EXISTS admins
+(
+    id            UUID                    NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    name          VARCHAR(100)            NOT NULL,
+    email         VARCHAR(150)            NOT NULL
+        CONSTRAINT unq_admins_email UNIQUE,
+    password_hash VARCHAR(255)            NOT NULL,
+    created_at    TIMESTAMP DEFAULT NOW() NOT NULL,
+    updated_at    TIMESTAMP DEFAULT NOW() NOT NULL
+);
+
+CREATE OR REPLACE TRIGGER update_updated_on_table
+    BEFORE UPDATE
+    ON
+        admins
+    FOR EACH ROW
+EXECUTE PROCEDURE update_updated_on_table();
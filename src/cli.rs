@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::config::ConfigArgs;
+
+/// Command line interface of the `rusty_roads` binary. Without a subcommand
+/// the server is started, as with `serve`.
+#[derive(Debug, Parser)]
+#[command(version, about = "Rusty Roads route manager API")]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the HTTP server
+    Serve,
+    /// Manage database migrations
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Insert reference data that isn't created by the migrations
+    #[command(subcommand)]
+    Seed(SeedCommand),
+    /// Import data from files
+    #[command(subcommand)]
+    Import(ImportCommand),
+    /// Locate addresses with the offline geocoder
+    #[command(subcommand)]
+    Geocode(GeocodeCommand),
+    /// Create an admin, reading the password from stdin
+    CreateAdmin {
+        /// Full name of the admin
+        #[arg(long)]
+        name: String,
+        /// Email the admin signs in with, unique regardless of case
+        #[arg(long)]
+        email: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply every pending migration
+    Up,
+    /// Revert the last applied migration, or every migration after --to
+    Down {
+        /// Keep the migrations up to this version applied, 0 reverts all
+        #[arg(long)]
+        to: Option<i64>,
+    },
+    /// List the migrations and whether they are applied
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SeedCommand {
+    /// Upsert the countries from the bundled ISO 3166-1 list, or from
+    /// countryapi.io when COUNTRY_SOURCE is countryapi
+    Countries,
+    /// Insert the CNH categories
+    CnhTypes,
+    /// Insert the route statuses
+    RouteStatus,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ImportCommand {
+    /// Import cities from a CSV file with the name, code and stateCode columns
+    Cities {
+        file: PathBuf,
+        /// Validate the file without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Upsert the IBGE states and municipalities, by code, from the JSON of
+    /// the IBGE localities API or a CSV with the code, name, stateCode and
+    /// stateName columns
+    Geography {
+        file: PathBuf,
+        /// Validate the file without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Upsert the local CEP table from a CSV with the zipCode, address,
+    /// neighbourhood and cityCode columns
+    ZipCodes {
+        file: PathBuf,
+        /// Validate the file without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Upsert the street index of the geocoder from a CSV with the name,
+    /// latitude, longitude and cityCode columns
+    Streets {
+        file: PathBuf,
+        /// Validate the file without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Set the centroid of the cities from a CSV with the code, latitude and
+    /// longitude columns
+    CityCentroids {
+        file: PathBuf,
+        /// Validate the file without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GeocodeCommand {
+    /// Geocode the saved addresses that have no coordinates
+    Backfill {
+        /// Addresses read per query
+        #[arg(long, default_value_t = 500)]
+        batch_size: usize,
+        /// Report what would be geocoded without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
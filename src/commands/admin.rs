@@ -0,0 +1,57 @@
+use std::{
+    error::Error,
+    io::{BufRead, IsTerminal},
+};
+
+use tracing::info;
+use validator::Validate;
+
+use crate::{
+    db::{admin::AdminExt, client::DBClient},
+    dtos::admin::CreateAdminDTO,
+    utils::password::hash_password,
+};
+
+/// Creates an admin. The password is read from stdin, without echo when it
+/// is a terminal, so it doesn't end up in the shell history.
+pub async fn run(db_client: &DBClient, name: String, email: String) -> Result<(), Box<dyn Error>> {
+    let admin = CreateAdminDTO {
+        name,
+        email,
+        password: read_password()?,
+    };
+
+    admin
+        .validate()
+        .map_err(|e| format!("Invalid admin: {e}"))?;
+
+    let password_hash = hash_password(&admin.password).map_err(|e| e.to_string())?;
+
+    let saved = match db_client
+        .save_admin(admin.name, admin.email, password_hash)
+        .await
+    {
+        Ok(saved) => saved,
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+            return Err("An admin with this email already exists".into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    info!(admin_id = %saved.id, "Admin created.");
+
+    println!("{}", serde_json::to_string_pretty(&saved)?);
+
+    Ok(())
+}
+
+fn read_password() -> Result<String, std::io::Error> {
+    if std::io::stdin().is_terminal() {
+        return rpassword::prompt_password("Password: ");
+    }
+
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
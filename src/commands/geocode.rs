@@ -0,0 +1,88 @@
+use std::error::Error;
+
+use crate::{
+    cli::GeocodeCommand,
+    config::Config,
+    db::{address::AddressExt, client::DBClient},
+    dtos::geocoding::GeocodingBackfillReportDTO,
+    utils::geocoding::{geocoder, Geocoder, GeocodingAccuracy},
+};
+
+/// Prints the report as JSON. Addresses that can't be located are left
+/// without coordinates and counted as not found.
+pub async fn run(
+    db_client: &DBClient,
+    config: &Config,
+    command: GeocodeCommand,
+) -> Result<(), Box<dyn Error>> {
+    let report = match command {
+        GeocodeCommand::Backfill {
+            batch_size,
+            dry_run,
+        } => {
+            let geocoder =
+                geocoder(config).ok_or("Geocoding is turned off by features.geocoding")?;
+
+            backfill(db_client, geocoder.as_ref(), batch_size.max(1), dry_run).await?
+        }
+    };
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(())
+}
+
+async fn backfill(
+    db_client: &DBClient,
+    geocoder: &dyn Geocoder,
+    batch_size: usize,
+    dry_run: bool,
+) -> Result<GeocodingBackfillReportDTO, Box<dyn Error>> {
+    let mut report = GeocodingBackfillReportDTO {
+        dry_run,
+        ..Default::default()
+    };
+    let mut after = None;
+
+    loop {
+        let addresses = db_client
+            .list_addresses_without_coordinates(after, batch_size)
+            .await?;
+
+        let Some(last) = addresses.last() else {
+            break;
+        };
+
+        after = Some(last.id);
+        report.total += addresses.len();
+
+        for address in addresses {
+            let Some(result) = geocoder
+                .geocode(db_client, &address.address, address.city_id)
+                .await?
+            else {
+                report.not_found += 1;
+                continue;
+            };
+
+            match result.accuracy {
+                GeocodingAccuracy::Street => report.street += 1,
+                GeocodingAccuracy::City => report.city += 1,
+                GeocodingAccuracy::Provided => {}
+            }
+
+            if !dry_run {
+                db_client
+                    .update_address_coordinates(
+                        address.id,
+                        result.latitude,
+                        result.longitude,
+                        &result.accuracy.to_string(),
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    path::Path,
+};
+
+use bigdecimal::BigDecimal;
+
+use crate::{
+    cli::ImportCommand,
+    db::{
+        city::CityExt, client::DBClient, country::CountryExt, state::StateExt, street::StreetExt,
+        zip_code::ZipCodeExt,
+    },
+    dtos::{
+        city::{ImportCityCentroidRowDTO, ImportCityRowDTO, ImportIbgeCityRowDTO},
+        geocoding::{ImportStreetRowDTO, SaveStreetParamsDTO},
+        import::{GeographyImportReportDTO, ImportReportDTO, UpsertCountsDTO},
+        state::RegisterStateDTO,
+        zip_code::ImportZipCodeRowDTO,
+    },
+    error::ErrorMessage,
+    utils::{ibge::parse_ibge_json, import::parse_csv, string::normalize_street_name},
+};
+
+/// The country the IBGE states belong to.
+const BRAZIL_ALPHA_2: &str = "BR";
+
+/// Rows saved per statement by the large imports. The national CEP base has
+/// about a million rows, too many for a single `UNNEST`.
+const UPSERT_BATCH_SIZE: usize = 10_000;
+
+/// Imports run the same checks as the HTTP imports and print the report as
+/// JSON. Nothing is saved when a row is invalid.
+pub async fn run(db_client: &DBClient, command: ImportCommand) -> Result<(), Box<dyn Error>> {
+    let errors = match command {
+        ImportCommand::Cities { file, dry_run } => {
+            let report = import_cities(db_client, &file, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+
+            report.errors.len()
+        }
+        ImportCommand::Geography { file, dry_run } => {
+            let report = import_geography(db_client, &file, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+
+            report.report.errors.len()
+        }
+        ImportCommand::ZipCodes { file, dry_run } => {
+            let report = import_zip_codes(db_client, &file, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+
+            report.errors.len()
+        }
+        ImportCommand::Streets { file, dry_run } => {
+            let report = import_streets(db_client, &file, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+
+            report.errors.len()
+        }
+        ImportCommand::CityCentroids { file, dry_run } => {
+            let report = import_city_centroids(db_client, &file, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+
+            report.errors.len()
+        }
+    };
+
+    match errors {
+        0 => Ok(()),
+        errors => Err(format!("{errors} invalid row(s), nothing was imported").into()),
+    }
+}
+
+async fn import_cities(
+    db_client: &DBClient,
+    file: &Path,
+    dry_run: bool,
+) -> Result<ImportReportDTO, Box<dyn Error>> {
+    let body = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+    let rows = parse_csv::<ImportCityRowDTO>(&body)?;
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut cities = vec![];
+    let mut codes = HashSet::new();
+
+    for row in rows {
+        let row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let state = db_client
+            .get_state(None, None, Some(&row_dto.state_code))
+            .await?;
+
+        let Some(state) = state else {
+            report.add_error(row.line, Some("state_code"), "Unknown state code");
+            continue;
+        };
+
+        let city = row_dto.into_register_city_dto(state.id.to_string());
+
+        let mut valid = report.validate(row.line, &city);
+
+        if !codes.insert(city.code.clone()) {
+            report.add_error(row.line, Some("code"), "Duplicate code in the file");
+            valid = false;
+        } else if db_client.get_city(None, Some(&city.code)).await?.is_some() {
+            report.add_error(row.line, Some("code"), ErrorMessage::CityExist);
+            valid = false;
+        }
+
+        if valid {
+            cities.push(city);
+        }
+    }
+
+    report.valid_rows = cities.len();
+
+    if !dry_run && !report.has_errors() {
+        report.imported = db_client.save_cities(cities).await?.len();
+    }
+
+    Ok(report)
+}
+
+/// Upserts the states and cities of an IBGE file by code, so it can be run
+/// again whenever IBGE publishes changes. Files ending in `.json` are read as
+/// the response of the IBGE localities API, any other file as CSV.
+async fn import_geography(
+    db_client: &DBClient,
+    file: &Path,
+    dry_run: bool,
+) -> Result<GeographyImportReportDTO, Box<dyn Error>> {
+    let body = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+
+    let rows = match file.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("json") => parse_ibge_json(&body)?,
+        _ => parse_csv::<ImportIbgeCityRowDTO>(&body)?,
+    };
+
+    let country = db_client
+        .get_country(None, None, Some(BRAZIL_ALPHA_2), None, None)
+        .await?
+        .ok_or("Brazil is not in the countries table, run `seed countries` first")?;
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut states: BTreeMap<String, String> = BTreeMap::new();
+    let mut cities = vec![];
+    let mut codes = HashSet::new();
+
+    for row in rows {
+        let row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let mut valid = report.validate(row.line, &row_dto);
+
+        if !codes.insert(row_dto.code.clone()) {
+            report.add_error(row.line, Some("code"), "Duplicate code in the file");
+            valid = false;
+        }
+
+        match states.get(&row_dto.state_code) {
+            Some(state_name) if *state_name != row_dto.state_name => {
+                report.add_error(
+                    row.line,
+                    Some("state_name"),
+                    "State name differs from a previous row",
+                );
+                valid = false;
+            }
+            Some(_) => {}
+            None if valid => {
+                states.insert(row_dto.state_code.clone(), row_dto.state_name.clone());
+            }
+            None => {}
+        }
+
+        if valid {
+            cities.push(ImportCityRowDTO {
+                name: row_dto.name,
+                code: row_dto.code,
+                state_code: row_dto.state_code,
+            });
+        }
+    }
+
+    report.valid_rows = cities.len();
+
+    let mut geography_report = GeographyImportReportDTO {
+        report,
+        states: None,
+        cities: None,
+    };
+
+    if dry_run || geography_report.report.has_errors() {
+        return Ok(geography_report);
+    }
+
+    let states: Vec<RegisterStateDTO> = states
+        .into_iter()
+        .map(|(code, name)| RegisterStateDTO {
+            name,
+            code,
+            country_id: country.id.to_string(),
+        })
+        .collect();
+
+    let total_states = states.len();
+    let total_cities = cities.len();
+
+    let (changed_states, changed_cities) = db_client.upsert_geography(states, cities).await?;
+
+    geography_report.report.imported = changed_cities.len();
+    geography_report.states = Some(UpsertCountsDTO::from_flags(total_states, &changed_states));
+    geography_report.cities = Some(UpsertCountsDTO::from_flags(total_cities, &changed_cities));
+
+    Ok(geography_report)
+}
+
+/// Upserts the local CEP table by CEP. The file is validated as a whole
+/// before anything is saved, then saved in batches of `UPSERT_BATCH_SIZE`.
+async fn import_zip_codes(
+    db_client: &DBClient,
+    file: &Path,
+    dry_run: bool,
+) -> Result<ImportReportDTO, Box<dyn Error>> {
+    let body = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+    let rows = parse_csv::<ImportZipCodeRowDTO>(&body)?;
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut zip_codes = vec![];
+    let mut codes = HashSet::new();
+    let mut known_cities: BTreeMap<String, bool> = BTreeMap::new();
+
+    for row in rows {
+        let mut row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        row_dto.address = row_dto.address.filter(|value| !value.is_empty());
+        row_dto.neighbourhood = row_dto.neighbourhood.filter(|value| !value.is_empty());
+
+        let mut valid = report.validate(row.line, &row_dto);
+
+        if !codes.insert(row_dto.zip_code.clone()) {
+            report.add_error(row.line, Some("zip_code"), "Duplicate code in the file");
+            valid = false;
+        }
+
+        if !is_known_city(db_client, &mut known_cities, &row_dto.city_code).await? {
+            report.add_error(row.line, Some("city_code"), "Unknown city code");
+            valid = false;
+        }
+
+        if valid {
+            zip_codes.push(row_dto);
+        }
+    }
+
+    report.valid_rows = zip_codes.len();
+
+    if dry_run || report.has_errors() {
+        return Ok(report);
+    }
+
+    while !zip_codes.is_empty() {
+        let batch: Vec<_> = zip_codes
+            .drain(..zip_codes.len().min(UPSERT_BATCH_SIZE))
+            .collect();
+
+        report.imported += db_client.upsert_zip_codes(batch).await?.len();
+    }
+
+    Ok(report)
+}
+
+/// Upserts the street index of the offline geocoder by city and normalized
+/// name, in batches of `UPSERT_BATCH_SIZE`.
+async fn import_streets(
+    db_client: &DBClient,
+    file: &Path,
+    dry_run: bool,
+) -> Result<ImportReportDTO, Box<dyn Error>> {
+    let body = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+    let rows = parse_csv::<ImportStreetRowDTO>(&body)?;
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut streets = vec![];
+    let mut keys = HashSet::new();
+    let mut known_cities: BTreeMap<String, bool> = BTreeMap::new();
+
+    for row in rows {
+        let row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let mut valid = report.validate(row.line, &row_dto);
+        valid &= validate_coordinates(&mut report, row.line, &row_dto.latitude, &row_dto.longitude);
+
+        let normalized_name = normalize_street_name(&row_dto.name);
+
+        if !keys.insert((row_dto.city_code.clone(), normalized_name.clone())) {
+            report.add_error(row.line, Some("name"), "Duplicate street in the file");
+            valid = false;
+        }
+
+        if !is_known_city(db_client, &mut known_cities, &row_dto.city_code).await? {
+            report.add_error(row.line, Some("city_code"), "Unknown city code");
+            valid = false;
+        }
+
+        if valid {
+            streets.push(SaveStreetParamsDTO {
+                name: row_dto.name,
+                normalized_name,
+                latitude: row_dto.latitude,
+                longitude: row_dto.longitude,
+                city_code: row_dto.city_code,
+            });
+        }
+    }
+
+    report.valid_rows = streets.len();
+
+    if dry_run || report.has_errors() {
+        return Ok(report);
+    }
+
+    while !streets.is_empty() {
+        let batch: Vec<_> = streets
+            .drain(..streets.len().min(UPSERT_BATCH_SIZE))
+            .collect();
+
+        report.imported += db_client.upsert_streets(batch).await?.len();
+    }
+
+    Ok(report)
+}
+
+/// Sets the centroid of the cities listed in the file, found by IBGE code.
+async fn import_city_centroids(
+    db_client: &DBClient,
+    file: &Path,
+    dry_run: bool,
+) -> Result<ImportReportDTO, Box<dyn Error>> {
+    let body = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+    let rows = parse_csv::<ImportCityCentroidRowDTO>(&body)?;
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut centroids = vec![];
+    let mut codes = HashSet::new();
+    let mut known_cities: BTreeMap<String, bool> = BTreeMap::new();
+
+    for row in rows {
+        let row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let mut valid =
+            validate_coordinates(&mut report, row.line, &row_dto.latitude, &row_dto.longitude);
+
+        if !codes.insert(row_dto.code.clone()) {
+            report.add_error(row.line, Some("code"), "Duplicate code in the file");
+            valid = false;
+        } else if !is_known_city(db_client, &mut known_cities, &row_dto.code).await? {
+            report.add_error(row.line, Some("code"), "Unknown city code");
+            valid = false;
+        }
+
+        if valid {
+            centroids.push(row_dto);
+        }
+    }
+
+    report.valid_rows = centroids.len();
+
+    if !dry_run && !report.has_errors() {
+        report.imported = db_client.update_city_centroids(centroids).await? as usize;
+    }
+
+    Ok(report)
+}
+
+/// Looks `code` up once per import, since large files repeat the same cities.
+async fn is_known_city(
+    db_client: &DBClient,
+    known_cities: &mut BTreeMap<String, bool>,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    if let Some(known) = known_cities.get(code) {
+        return Ok(*known);
+    }
+
+    let known = db_client.get_city(None, Some(code)).await?.is_some();
+    known_cities.insert(code.to_string(), known);
+
+    Ok(known)
+}
+
+fn validate_coordinates(
+    report: &mut ImportReportDTO,
+    line: u64,
+    latitude: &BigDecimal,
+    longitude: &BigDecimal,
+) -> bool {
+    let mut valid = true;
+
+    if *latitude < BigDecimal::from(-90) || *latitude > BigDecimal::from(90) {
+        report.add_error(
+            line,
+            Some("latitude"),
+            "Latitude must be between -90 and 90",
+        );
+        valid = false;
+    }
+
+    if *longitude < BigDecimal::from(-180) || *longitude > BigDecimal::from(180) {
+        report.add_error(
+            line,
+            Some("longitude"),
+            "Longitude must be between -180 and 180",
+        );
+        valid = false;
+    }
+
+    valid
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+
+use tracing::info;
+
+use crate::{
+    cli::MigrateCommand,
+    db::{
+        client::{DBClient, MIGRATOR},
+        health::HealthExt,
+    },
+};
+
+pub async fn run(db_client: &DBClient, command: MigrateCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        MigrateCommand::Up => {
+            MIGRATOR.run(&db_client.pool).await?;
+            info!("Migrations executed successfully.");
+        }
+        MigrateCommand::Down { to } => {
+            let applied = db_client.list_applied_migrations().await?;
+
+            // Without --to, only the last applied migration is reverted.
+            let target = match to {
+                Some(to) => to,
+                None if applied.is_empty() => {
+                    info!("No migration to revert.");
+                    return Ok(());
+                }
+                None => applied.iter().rev().nth(1).copied().unwrap_or(0),
+            };
+
+            MIGRATOR.undo(&db_client.pool, target).await?;
+            info!("Migrations after version {target} reverted successfully.");
+        }
+        MigrateCommand::Status => {
+            let applied = db_client.list_applied_migrations().await?;
+
+            for migration in MIGRATOR
+                .iter()
+                .filter(|migration| !migration.migration_type.is_down_migration())
+            {
+                let status = match applied.contains(&migration.version) {
+                    true => "applied",
+                    false => "pending",
+                };
+
+                println!(
+                    "{}  {status:<7}  {}",
+                    migration.version, migration.description
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub mod admin;
+pub mod geocode;
+pub mod import;
+pub mod migrate;
+pub mod seed;
@@ -0,0 +1,83 @@
+use std::error::Error;
+
+use tracing::info;
+
+use crate::{
+    cli::SeedCommand,
+    config::Config,
+    db::{client::DBClient, driver::CnhTypeExt, route::RouteStatusExt},
+    utils::fetch::{country_source, fetch_countries},
+};
+
+/// The CNH categories defined by the Brazilian traffic code.
+const CNH_TYPES: &[(&str, &str)] = &[
+    ("ACC", "Autorização para conduzir ciclomotor"),
+    ("A", "Veículos de duas ou três rodas"),
+    ("B", "Veículos de até 3.500 kg"),
+    ("C", "Veículos de carga acima de 3.500 kg"),
+    ("D", "Veículos com mais de 8 passageiros"),
+    ("E", "Veículos com unidade acoplada"),
+    ("AB", "Categorias A e B"),
+    ("AC", "Categorias A e C"),
+    ("AD", "Categorias A e D"),
+    ("AE", "Categorias A e E"),
+];
+
+const ROUTE_STATUSES: &[(&str, &str)] = &[
+    ("CREATED", "Rota criada"),
+    ("STARTED", "Rota iniciada"),
+    ("FINISHED", "Rota finalizada"),
+    ("CANCELED", "Rota cancelada"),
+];
+
+/// Seeds are idempotent: rows whose code already exists are left untouched.
+pub async fn run(
+    db_client: &DBClient,
+    config: &Config,
+    command: SeedCommand,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        SeedCommand::Countries => {
+            let source = country_source(config)?;
+            let report = fetch_countries(db_client, source.as_ref()).await?;
+
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        SeedCommand::CnhTypes => {
+            let mut inserted = 0;
+
+            for (code, description) in CNH_TYPES {
+                if db_client
+                    .get_cnh_type(None, Some(code.to_string()))
+                    .await?
+                    .is_none()
+                {
+                    db_client.save_cnh_type(*code, *description).await?;
+                    inserted += 1;
+                }
+            }
+
+            info!("{inserted} CNH types inserted.");
+        }
+        SeedCommand::RouteStatus => {
+            let mut inserted = 0;
+
+            for (code, description) in ROUTE_STATUSES {
+                if db_client
+                    .get_route_status(None, Some(code.to_string()))
+                    .await?
+                    .is_none()
+                {
+                    db_client
+                        .save_route_status(Some(*code), *description)
+                        .await?;
+                    inserted += 1;
+                }
+            }
+
+            info!("{inserted} route statuses inserted.");
+        }
+    }
+
+    Ok(())
+}
@@ -1,5 +1,23 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use actix_web::http::Method;
+use clap::Args;
+
+use crate::utils::{i18n::Language, logging::redact_url};
+
+/// The file read when neither `--config` nor `CONFIG_FILE` is given. It is
+/// optional, unlike a file that is named explicitly.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
 /// Represents the configuration settings for the application.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Config {
     /// The URL of the database.
     pub database_url: String,
@@ -7,46 +25,796 @@ pub struct Config {
     pub jwt_secret: String,
     /// The maximum age (in seconds) for JWT tokens.
     pub jwt_maxage: i64,
+    /// The address the server binds to.
+    pub bind_address: IpAddr,
     /// The port on which the application listens for incoming connections.
     pub port: u16,
+    /// How long (in hours) responses stored for an `Idempotency-Key` are replayed.
+    pub idempotency_retention_hours: i32,
+    /// The language used for responses when `Accept-Language` names none we support.
+    pub default_language: Language,
+    /// Where countries are read from by `seed countries` and at startup.
+    pub country_source: CountrySourceKind,
+    /// The key for countryapi.io, required when `country_source` is `countryapi`.
+    pub country_api_key: Option<String>,
+    /// Where `GET /api/v1/addresses/cep/{cep}` looks CEPs up.
+    pub zip_code_provider: ZipCodeProviderKind,
+    /// How far (in meters) from a route's start or end a known address may be
+    /// to be linked to it when none is given. `0` turns the linking off.
+    pub route_address_radius_meters: u32,
+    pub cors: CorsConfig,
+    pub database: DatabaseConfig,
+    pub features: FeatureConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountrySourceKind {
+    /// The ISO 3166-1 dataset bundled with the binary.
+    Bundled,
+    /// The countryapi.io HTTP API.
+    CountryApi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipCodeProviderKind {
+    /// The `zip_codes` table, filled by `import zip-codes`.
+    Local,
+    /// The ViaCEP HTTP API.
+    ViaCep,
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser.
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests.
+    pub allowed_methods: Vec<Method>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    /// The maximum number of connections kept by the pool.
+    pub max_connections: u32,
+    /// How many times connecting to the database is attempted at startup.
+    pub max_retries: u32,
+    /// How long to wait between two connection attempts.
+    pub retry_interval: Duration,
+}
+
+/// Optional behaviours that can be switched off per deployment.
+#[derive(Debug, Clone)]
+pub struct FeatureConfig {
+    /// Sync the country list from `country_source` at startup. Off by
+    /// default, `seed countries` does the same on demand.
+    pub fetch_countries: bool,
+    /// Geocode addresses saved without coordinates.
+    pub geocoding: bool,
+    /// Record request metrics and expose them at `/metrics`.
+    pub metrics: bool,
+    /// Run pending migrations at startup.
+    pub run_migrations: bool,
+    /// Send the webhook deliveries. Events are still recorded while it is
+    /// off, and sent once it is back on.
+    pub webhooks: bool,
+}
+
+/// Settings that can be overridden from the command line. They take
+/// precedence over the environment, which takes precedence over the file.
+#[derive(Debug, Default, Args)]
+pub struct ConfigArgs {
+    /// Path to a TOML configuration file [default: config.toml]
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+    /// URL of the PostgreSQL database
+    #[arg(long, global = true)]
+    pub database_url: Option<String>,
+    /// Maximum age of JWT tokens, in seconds
+    #[arg(long, global = true)]
+    pub jwt_maxage: Option<String>,
+    /// Address the server binds to [default: 0.0.0.0]
+    #[arg(long, global = true)]
+    pub bind_address: Option<String>,
+    /// Port the server listens on [default: 8000]
+    #[arg(long, global = true)]
+    pub port: Option<String>,
+    /// Hours responses are replayed for an Idempotency-Key [default: 24]
+    #[arg(long, global = true)]
+    pub idempotency_retention_hours: Option<String>,
+    /// Language used when Accept-Language names none we support [default: en]
+    #[arg(long, global = true)]
+    pub default_language: Option<String>,
+    /// Source of the country list, bundled or countryapi [default: bundled]
+    #[arg(long, global = true)]
+    pub country_source: Option<String>,
+    /// Provider used to look up CEPs, local or viacep [default: local]
+    #[arg(long, global = true)]
+    pub zip_code_provider: Option<String>,
+    /// Meters within which routes are linked to known addresses, 0 to disable [default: 0]
+    #[arg(long, global = true)]
+    pub route_address_radius_meters: Option<String>,
+    /// Comma separated origins allowed by CORS [default: http://localhost:3000,http://localhost:8000]
+    #[arg(long, global = true)]
+    pub cors_allowed_origins: Option<String>,
+    /// Comma separated methods allowed by CORS [default: GET,POST,PUT,DELETE]
+    #[arg(long, global = true)]
+    pub cors_allowed_methods: Option<String>,
+    /// Maximum number of pooled database connections [default: 10]
+    #[arg(long, global = true)]
+    pub database_max_connections: Option<String>,
+    /// Attempts to connect to the database at startup [default: 10]
+    #[arg(long, global = true)]
+    pub database_max_retries: Option<String>,
+    /// Seconds between two connection attempts [default: 5]
+    #[arg(long, global = true)]
+    pub database_retry_interval_secs: Option<String>,
+    /// Sync countries from the country source at startup [default: false]
+    #[arg(long, global = true)]
+    pub features_fetch_countries: Option<String>,
+    /// Geocode addresses saved without coordinates [default: true]
+    #[arg(long, global = true)]
+    pub features_geocoding: Option<String>,
+    /// Record request metrics and expose /metrics [default: true]
+    #[arg(long, global = true)]
+    pub features_metrics: Option<String>,
+    /// Run pending migrations at startup [default: true]
+    #[arg(long, global = true)]
+    pub features_run_migrations: Option<String>,
+    /// Send webhook deliveries from this instance [default: true]
+    #[arg(long, global = true)]
+    pub features_webhooks: Option<String>,
+}
+
+impl ConfigArgs {
+    /// Returns the value given for `key`, named as in the configuration file.
+    fn get(&self, key: &str) -> Option<&String> {
+        match key {
+            "database_url" => self.database_url.as_ref(),
+            "jwt_maxage" => self.jwt_maxage.as_ref(),
+            "bind_address" => self.bind_address.as_ref(),
+            "port" => self.port.as_ref(),
+            "idempotency_retention_hours" => self.idempotency_retention_hours.as_ref(),
+            "default_language" => self.default_language.as_ref(),
+            "country_source" => self.country_source.as_ref(),
+            "zip_code_provider" => self.zip_code_provider.as_ref(),
+            "route_address_radius_meters" => self.route_address_radius_meters.as_ref(),
+            "cors.allowed_origins" => self.cors_allowed_origins.as_ref(),
+            "cors.allowed_methods" => self.cors_allowed_methods.as_ref(),
+            "database.max_connections" => self.database_max_connections.as_ref(),
+            "database.max_retries" => self.database_max_retries.as_ref(),
+            "database.retry_interval_secs" => self.database_retry_interval_secs.as_ref(),
+            "features.fetch_countries" => self.features_fetch_countries.as_ref(),
+            "features.geocoding" => self.features_geocoding.as_ref(),
+            "features.metrics" => self.features_metrics.as_ref(),
+            "features.run_migrations" => self.features_run_migrations.as_ref(),
+            "features.webhooks" => self.features_webhooks.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// A setting, with the environment variables it is read from. When several
+/// variables are listed, the last one that is set wins.
+struct Setting {
+    key: &'static str,
+    env: &'static [&'static str],
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "database_url",
+        env: &["DATABASE_URL", "DATABASE_URL_DOCKER"],
+    },
+    Setting {
+        key: "jwt_secret",
+        env: &["JWT_SECRET_KEY"],
+    },
+    Setting {
+        key: "jwt_maxage",
+        env: &["JWT_MAXAGE"],
+    },
+    Setting {
+        key: "bind_address",
+        env: &["BIND_ADDRESS"],
+    },
+    Setting {
+        key: "port",
+        env: &["PORT"],
+    },
+    Setting {
+        key: "idempotency_retention_hours",
+        env: &["IDEMPOTENCY_RETENTION_HOURS"],
+    },
+    Setting {
+        key: "default_language",
+        env: &["DEFAULT_LANGUAGE"],
+    },
+    Setting {
+        key: "country_source",
+        env: &["COUNTRY_SOURCE"],
+    },
+    Setting {
+        key: "country_api_key",
+        env: &["COUNTRYAPI_KEY"],
+    },
+    Setting {
+        key: "zip_code_provider",
+        env: &["ZIP_CODE_PROVIDER"],
+    },
+    Setting {
+        key: "route_address_radius_meters",
+        env: &["ROUTE_ADDRESS_RADIUS_METERS"],
+    },
+    Setting {
+        key: "cors.allowed_origins",
+        env: &["CORS_ALLOWED_ORIGINS"],
+    },
+    Setting {
+        key: "cors.allowed_methods",
+        env: &["CORS_ALLOWED_METHODS"],
+    },
+    Setting {
+        key: "database.max_connections",
+        env: &["DATABASE_MAX_CONNECTIONS"],
+    },
+    Setting {
+        key: "database.max_retries",
+        env: &["DATABASE_MAX_RETRIES"],
+    },
+    Setting {
+        key: "database.retry_interval_secs",
+        env: &["DATABASE_RETRY_INTERVAL_SECS"],
+    },
+    Setting {
+        key: "features.fetch_countries",
+        env: &["FEATURES_FETCH_COUNTRIES"],
+    },
+    Setting {
+        key: "features.geocoding",
+        env: &["FEATURES_GEOCODING"],
+    },
+    Setting {
+        key: "features.metrics",
+        env: &["FEATURES_METRICS"],
+    },
+    Setting {
+        key: "features.run_migrations",
+        env: &["FEATURES_RUN_MIGRATIONS"],
+    },
+    Setting {
+        key: "features.webhooks",
+        env: &["FEATURES_WEBHOOKS"],
+    },
+];
+
+/// Every problem found while loading the configuration, so they can all be
+/// fixed at once instead of one restart at a time.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+
+        for error in &self.errors {
+            write!(f, "\n  - {error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A raw value and where it came from, used in error messages.
+struct RawValue {
+    value: String,
+    origin: String,
+}
+
+/// Merges the file, the environment and the command line, and parses the
+/// result while collecting every error.
+struct Loader<'a> {
+    file: BTreeMap<String, String>,
+    args: &'a ConfigArgs,
+    env: &'a dyn Fn(&str) -> Option<String>,
+    errors: Vec<String>,
+}
+
+impl Loader<'_> {
+    fn raw(&self, key: &str) -> Option<RawValue> {
+        if let Some(value) = self.args.get(key) {
+            return Some(RawValue {
+                value: value.to_owned(),
+                origin: format!("flag --{}", key.replace(['.', '_'], "-")),
+            });
+        }
+
+        let setting = SETTINGS.iter().find(|setting| setting.key == key)?;
+
+        let env = setting.env.iter().rev().find_map(|name| {
+            (self.env)(name)
+                .filter(|value| !value.is_empty())
+                .map(|value| RawValue {
+                    value,
+                    origin: format!("environment variable {name}"),
+                })
+        });
+
+        env.or_else(|| {
+            self.file.get(key).map(|value| RawValue {
+                value: value.to_owned(),
+                origin: "config file".to_string(),
+            })
+        })
+    }
+
+    /// Parses `key` with `parse`, which returns a description of the expected
+    /// value on failure. Missing settings fall back to `default`.
+    fn parse<T>(
+        &mut self,
+        key: &str,
+        default: Option<T>,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        let Some(raw) = self.raw(key) else {
+            if default.is_none() {
+                let setting = SETTINGS.iter().find(|setting| setting.key == key);
+
+                self.errors.push(format!(
+                    "{key}: missing, set it in the config file or in {}",
+                    setting.map_or(String::new(), |setting| setting.env.join(" or "))
+                ));
+            }
+
+            return default;
+        };
+
+        match parse(raw.value.trim()) {
+            Ok(value) => Some(value),
+            Err(expected) => {
+                self.errors.push(format!(
+                    "{key}: invalid value from {}, expected {expected}",
+                    raw.origin
+                ));
+
+                None
+            }
+        }
+    }
+
+    fn parse_optional(&mut self, key: &str) -> Option<String> {
+        self.raw(key)
+            .map(|raw| raw.value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
 }
 
 impl Config {
-    /// Initializes the application configuration based on environment variables.
-    ///
-    /// # Panics
+    /// Loads the configuration from, in increasing order of precedence, the
+    /// TOML file, the environment and the command line flags in `args`.
     ///
-    /// Panics if any of the required environment variables (`DATABASE_URL`, `JWT_SECRET_KEY`, `JWT_MAXAGE`)
-    /// are not set or if there is an error while parsing the `JWT_MAXAGE` value.
+    /// # Errors
     ///
-    /// # Returns
-    ///
-    /// Returns a `Config` instance initialized with values from environment variables.
+    /// Returns a `ConfigError` listing every missing or invalid setting.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use your_module_name::Config;
+    /// use your_module_name::{Config, ConfigArgs};
     ///
-    /// // Initialize the configuration settings
-    /// let config = Config::init();
-    /// println!("{:?}", config);
+    /// match Config::load(&ConfigArgs::default()) {
+    ///     Ok(config) => println!("{:?}", config),
+    ///     Err(e) => eprintln!("{e}"),
+    /// }
     /// ```
-    pub fn init() -> Config {
-        let mut database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    pub fn load(args: &ConfigArgs) -> Result<Config, ConfigError> {
+        Config::load_with_env(args, &|name| std::env::var(name).ok())
+    }
 
-        if let Ok(docker_database_url) = std::env::var("DATABASE_URL_DOCKER") {
-            database_url = docker_database_url;
+    /// Loads the configuration as `load` does, reading the environment
+    /// variables through `env`.
+    fn load_with_env(
+        args: &ConfigArgs,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut errors = Vec::new();
+
+        let file = match read_config_file(args, env) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(e);
+                BTreeMap::new()
+            }
+        };
+
+        for key in file.keys() {
+            if !SETTINGS.iter().any(|setting| setting.key == key) {
+                errors.push(format!("{key}: unknown setting in the config file"));
+            }
         }
 
-        let jwt_secret = std::env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
-        let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
+        let mut loader = Loader {
+            file,
+            args,
+            env,
+            errors,
+        };
+
+        let database_url = loader.parse("database_url", None, |value| {
+            match value.starts_with("postgres://") || value.starts_with("postgresql://") {
+                true => Ok(value.to_string()),
+                false => Err("a postgres:// URL".to_string()),
+            }
+        });
+        let jwt_secret = loader.parse("jwt_secret", None, non_empty);
+        let jwt_maxage = loader.parse("jwt_maxage", None, positive::<i64>);
+        let bind_address = loader.parse("bind_address", Some([0, 0, 0, 0].into()), |value| {
+            IpAddr::from_str(value).map_err(|_| "an IP address".to_string())
+        });
+        let port = loader.parse("port", Some(8000), positive::<u16>);
+        let idempotency_retention_hours =
+            loader.parse("idempotency_retention_hours", Some(24), positive::<i32>);
+        let default_language = loader.parse("default_language", Some(Language::En), |value| {
+            Language::from_tag(value).ok_or_else(|| "one of en, pt-BR".to_string())
+        });
+        let country_source = loader.parse(
+            "country_source",
+            Some(CountrySourceKind::Bundled),
+            |value| match value.to_ascii_lowercase().as_str() {
+                "bundled" => Ok(CountrySourceKind::Bundled),
+                "countryapi" => Ok(CountrySourceKind::CountryApi),
+                _ => Err("one of bundled, countryapi".to_string()),
+            },
+        );
+        let country_api_key = loader.parse_optional("country_api_key");
+        let zip_code_provider = loader.parse(
+            "zip_code_provider",
+            Some(ZipCodeProviderKind::Local),
+            |value| match value.to_ascii_lowercase().as_str() {
+                "local" => Ok(ZipCodeProviderKind::Local),
+                "viacep" => Ok(ZipCodeProviderKind::ViaCep),
+                _ => Err("one of local, viacep".to_string()),
+            },
+        );
+        let route_address_radius_meters =
+            loader.parse("route_address_radius_meters", Some(0), non_negative::<u32>);
+
+        let allowed_origins = loader.parse(
+            "cors.allowed_origins",
+            Some(vec![
+                "http://localhost:3000".to_string(),
+                "http://localhost:8000".to_string(),
+            ]),
+            |value| {
+                list(value)
+                    .map(|origin| {
+                        match origin.starts_with("http://") || origin.starts_with("https://") {
+                            true => Ok(origin.trim_end_matches('/').to_string()),
+                            false => Err(format!(
+                                "origins starting with http:// or https://, got {origin:?}"
+                            )),
+                        }
+                    })
+                    .collect()
+            },
+        );
+        let allowed_methods = loader.parse(
+            "cors.allowed_methods",
+            Some(vec![Method::GET, Method::POST, Method::PUT, Method::DELETE]),
+            |value| {
+                list(value)
+                    .map(|method| match method.to_ascii_uppercase().as_str() {
+                        "GET" => Ok(Method::GET),
+                        "POST" => Ok(Method::POST),
+                        "PUT" => Ok(Method::PUT),
+                        "PATCH" => Ok(Method::PATCH),
+                        "DELETE" => Ok(Method::DELETE),
+                        "HEAD" => Ok(Method::HEAD),
+                        "OPTIONS" => Ok(Method::OPTIONS),
+                        _ => Err(format!("HTTP methods, got {method:?}")),
+                    })
+                    .collect()
+            },
+        );
+
+        let max_connections = loader.parse("database.max_connections", Some(10), positive::<u32>);
+        let max_retries = loader.parse("database.max_retries", Some(10), positive::<u32>);
+        let retry_interval_secs =
+            loader.parse("database.retry_interval_secs", Some(5), positive::<u64>);
+
+        let fetch_countries = loader.parse("features.fetch_countries", Some(false), boolean);
+        let geocoding = loader.parse("features.geocoding", Some(true), boolean);
+        let metrics = loader.parse("features.metrics", Some(true), boolean);
+        let run_migrations = loader.parse("features.run_migrations", Some(true), boolean);
+        let webhooks = loader.parse("features.webhooks", Some(true), boolean);
+
+        if country_source == Some(CountrySourceKind::CountryApi) && country_api_key.is_none() {
+            loader.errors.push(
+                "country_api_key: missing, set COUNTRYAPI_KEY or use the bundled country_source"
+                    .to_string(),
+            );
+        }
 
-        Config {
-            database_url,
-            jwt_secret,
-            jwt_maxage: jwt_maxage.parse::<i64>().unwrap(),
-            port: 8000, // Default port
+        match loader.errors.is_empty() {
+            true => Ok(Config {
+                database_url: database_url.unwrap(),
+                jwt_secret: jwt_secret.unwrap(),
+                jwt_maxage: jwt_maxage.unwrap(),
+                bind_address: bind_address.unwrap(),
+                port: port.unwrap(),
+                idempotency_retention_hours: idempotency_retention_hours.unwrap(),
+                default_language: default_language.unwrap(),
+                country_source: country_source.unwrap(),
+                country_api_key,
+                zip_code_provider: zip_code_provider.unwrap(),
+                route_address_radius_meters: route_address_radius_meters.unwrap(),
+                cors: CorsConfig {
+                    allowed_origins: allowed_origins.unwrap(),
+                    allowed_methods: allowed_methods.unwrap(),
+                },
+                database: DatabaseConfig {
+                    max_connections: max_connections.unwrap(),
+                    max_retries: max_retries.unwrap(),
+                    retry_interval: Duration::from_secs(retry_interval_secs.unwrap()),
+                },
+                features: FeatureConfig {
+                    fetch_countries: fetch_countries.unwrap(),
+                    geocoding: geocoding.unwrap(),
+                    metrics: metrics.unwrap(),
+                    run_migrations: run_migrations.unwrap(),
+                    webhooks: webhooks.unwrap(),
+                },
+            }),
+            false => Err(ConfigError {
+                errors: loader.errors,
+            }),
         }
     }
 }
+
+/// Secrets are redacted, so the configuration can be logged safely.
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("database_url", &redact_url(&self.database_url))
+            .field("jwt_secret", &"***")
+            .field("jwt_maxage", &self.jwt_maxage)
+            .field("bind_address", &self.bind_address)
+            .field("port", &self.port)
+            .field(
+                "idempotency_retention_hours",
+                &self.idempotency_retention_hours,
+            )
+            .field("default_language", &self.default_language)
+            .field("country_source", &self.country_source)
+            .field(
+                "country_api_key",
+                &self.country_api_key.as_ref().map(|_| "***"),
+            )
+            .field("zip_code_provider", &self.zip_code_provider)
+            .field(
+                "route_address_radius_meters",
+                &self.route_address_radius_meters,
+            )
+            .field("cors", &self.cors)
+            .field("database", &self.database)
+            .field("features", &self.features)
+            .finish()
+    }
+}
+
+/// Reads the TOML file named by `--config` or `CONFIG_FILE` and flattens it
+/// into dotted keys, so `[cors] allowed_origins` becomes `cors.allowed_origins`.
+fn read_config_file(
+    args: &ConfigArgs,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let path = args
+        .config
+        .clone()
+        .or_else(|| env("CONFIG_FILE").map(PathBuf::from));
+
+    let (path, required) = match path {
+        Some(path) => (path, true),
+        None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    };
+
+    if !required && !Path::new(&path).exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("config file {}: {e}", path.display()))?;
+
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e| format!("config file {}: {e}", path.display()))?;
+
+    let mut values = BTreeMap::new();
+    flatten(None, table, &mut values);
+
+    Ok(values)
+}
+
+fn flatten(prefix: Option<&str>, table: toml::Table, values: &mut BTreeMap<String, String>) {
+    for (key, value) in table {
+        let key = match prefix {
+            Some(prefix) => format!("{prefix}.{key}"),
+            None => key,
+        };
+
+        match value {
+            toml::Value::Table(table) => flatten(Some(&key), table, values),
+            toml::Value::String(value) => {
+                values.insert(key, value);
+            }
+            toml::Value::Array(items) => {
+                let items: Vec<String> = items
+                    .into_iter()
+                    .map(|item| match item {
+                        toml::Value::String(item) => item,
+                        item => item.to_string(),
+                    })
+                    .collect();
+
+                values.insert(key, items.join(","));
+            }
+            value => {
+                values.insert(key, value.to_string());
+            }
+        }
+    }
+}
+
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn non_empty(value: &str) -> Result<String, String> {
+    match value.is_empty() {
+        true => Err("a non-empty value".to_string()),
+        false => Ok(value.to_string()),
+    }
+}
+
+fn positive<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(number) if number > T::default() => Ok(number),
+        _ => Err(format!("a positive number, got {value:?}")),
+    }
+}
+
+fn non_negative<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(number) if number >= T::default() => Ok(number),
+        _ => Err(format!("a non-negative number, got {value:?}")),
+    }
+}
+
+fn boolean(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(format!("true or false, got {value:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Writes `content` to a config file of its own, removed on drop.
+    struct ConfigFile(PathBuf);
+
+    impl ConfigFile {
+        fn new(content: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("rusty_roads-{}.toml", uuid::Uuid::new_v4()));
+            std::fs::write(&path, content).unwrap();
+
+            ConfigFile(path)
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn load(args: &ConfigArgs, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Config::load_with_env(args, &|name| env.get(name).cloned())
+    }
+
+    const REQUIRED: &str = r#"
+        database_url = "postgres://rr:hunter2@db:5432/rusty_roads"
+        jwt_secret = "s3cret-signing-key"
+        jwt_maxage = 60
+    "#;
+
+    #[test]
+    fn flags_override_the_environment_which_overrides_the_file() {
+        let file = ConfigFile::new(&format!("{REQUIRED}\nport = 1000"));
+
+        let mut args = ConfigArgs {
+            config: Some(file.0.clone()),
+            ..Default::default()
+        };
+
+        assert_eq!(load(&args, &[]).unwrap().port, 1000);
+        assert_eq!(load(&args, &[("PORT", "2000")]).unwrap().port, 2000);
+
+        args.port = Some("3000".to_string());
+
+        assert_eq!(load(&args, &[("PORT", "2000")]).unwrap().port, 3000);
+    }
+
+    #[test]
+    fn reports_every_invalid_setting_at_once() {
+        let file = ConfigFile::new(&format!("{REQUIRED}\n[features]\nmetrics = \"maybe\""));
+
+        let args = ConfigArgs {
+            config: Some(file.0.clone()),
+            port: Some("http".to_string()),
+            ..Default::default()
+        };
+
+        let errors = load(&args, &[("JWT_MAXAGE", "-1")]).unwrap_err().errors;
+
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(
+            errors[0].starts_with("jwt_maxage: invalid value from environment variable JWT_MAXAGE")
+        );
+        assert!(errors[1].starts_with("port: invalid value from flag --port"));
+        assert!(errors[2].starts_with("features.metrics: invalid value from config file"));
+    }
+
+    #[test]
+    fn reports_missing_settings() {
+        let errors = load(&ConfigArgs::default(), &[]).unwrap_err().errors;
+
+        let keys: Vec<&str> = errors
+            .iter()
+            .map(|error| error.split(':').next().unwrap())
+            .collect();
+
+        assert_eq!(keys, ["database_url", "jwt_secret", "jwt_maxage"]);
+    }
+
+    #[test]
+    fn redacts_secrets_in_debug_output() {
+        let config = load(
+            &ConfigArgs::default(),
+            &[
+                ("DATABASE_URL", "postgres://rr:hunter2@db:5432/rusty_roads"),
+                ("JWT_SECRET_KEY", "s3cret-signing-key"),
+                ("JWT_MAXAGE", "60"),
+                ("COUNTRY_SOURCE", "countryapi"),
+                ("COUNTRYAPI_KEY", "api-key-value"),
+            ],
+        )
+        .unwrap();
+
+        let debug = format!("{config:?}");
+
+        assert!(!debug.contains("hunter2"), "{debug}");
+        assert!(!debug.contains("s3cret-signing-key"), "{debug}");
+        assert!(!debug.contains("api-key-value"), "{debug}");
+        assert!(
+            debug.contains("postgres://rr:***@db:5432/rusty_roads"),
+            "{debug}"
+        );
+    }
+}
@@ -1,10 +1,29 @@
 use async_trait::async_trait;
 use bigdecimal::BigDecimal;
+use sqlx::{Postgres, QueryBuilder};
+use tracing::instrument;
 use uuid::Uuid;
 
-use crate::{dtos::address::SaveAddressParamsDTO, models::address::Address};
+use crate::{
+    dtos::address::SaveAddressParamsDTO,
+    models::address::{Address, AddressSearchResult, NearbyAddress},
+    utils::spatial::SpatialFilter,
+};
 
-use super::client::DBClient;
+use super::{
+    client::DBClient,
+    spatial::{push_spatial_filter, radius_bounds, PointColumns, EARTH_RADIUS_METERS},
+};
+
+/// The columns of `Address`, listed instead of `*` so the geography column
+/// added with PostGIS is left out.
+const ADDRESS_COLUMNS: &str = "id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy";
+
+const ADDRESS_LOCATION: PointColumns = PointColumns {
+    location: "location",
+    latitude: "latitude",
+    longitude: "longitude",
+};
 
 #[async_trait]
 pub trait AddressExt {
@@ -15,15 +34,83 @@ pub trait AddressExt {
         longitude: Option<BigDecimal>,
     ) -> Result<Option<Address>, sqlx::Error>;
 
+    /// Finds the addresses matching any of the `(address, number, zip_code)`
+    /// tuples in `details`, in a single query.
+    async fn get_addresses_by_details(
+        &self,
+        details: &[(String, String, String)],
+    ) -> Result<Vec<Address>, sqlx::Error>;
+
+    /// Picks a random address among those with coordinates.
     async fn get_address_random(&self) -> Result<Option<Address>, sqlx::Error>;
 
-    async fn list_addresses(&self, page: u32, limit: usize) -> Result<Vec<Address>, sqlx::Error>;
+    /// Lists a page of addresses, restricted to those inside `area` when
+    /// one is given, and to those after the id `after` when given.
+    async fn list_addresses(
+        &self,
+        page: u32,
+        limit: usize,
+        area: Option<SpatialFilter>,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Address>, sqlx::Error>;
+
+    /// Lists a page of the addresses of a city, ordered by street and number,
+    /// restricted to those after the `(address, number, zip_code, id)` key
+    /// `after` when given.
+    async fn list_addresses_by_city(
+        &self,
+        city_id: Uuid,
+        page: u32,
+        limit: usize,
+        after: Option<(String, String, String, Uuid)>,
+    ) -> Result<Vec<Address>, sqlx::Error>;
 
     async fn save_address<T: Into<String> + Send, B: Into<BigDecimal> + Send>(
         &self,
         params: SaveAddressParamsDTO<T, B>,
     ) -> Result<Address, sqlx::Error>;
 
+    async fn save_addresses<T: Into<String> + Send, B: Into<BigDecimal> + Send>(
+        &self,
+        addresses: Vec<SaveAddressParamsDTO<T, B>>,
+    ) -> Result<Vec<Address>, sqlx::Error>;
+
+    /// Lists the addresses within `radius_meters` of a point, closest first.
+    async fn list_nearest_addresses(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        radius_meters: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyAddress>, sqlx::Error>;
+
+    /// Searches addresses by street, number, neighbourhood and CEP, ignoring
+    /// case and accents and tolerating typos, best matches first.
+    async fn search_addresses(
+        &self,
+        terms: &str,
+        city_id: Option<Uuid>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<AddressSearchResult>, sqlx::Error>;
+
+    /// Lists the addresses missing a coordinate, ordered by ID and starting
+    /// after `after`, so a backfill can page through them even when some
+    /// can't be geocoded.
+    async fn list_addresses_without_coordinates(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<Address>, sqlx::Error>;
+
+    async fn update_address_coordinates(
+        &self,
+        address_id: Uuid,
+        latitude: BigDecimal,
+        longitude: BigDecimal,
+        geocoding_accuracy: &str,
+    ) -> Result<(), sqlx::Error>;
+
     async fn delete_address(
         &self,
         address_id: Option<Uuid>,
@@ -32,6 +119,7 @@ pub trait AddressExt {
 
 #[async_trait]
 impl AddressExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_address(
         &self,
         address_id: Option<Uuid>,
@@ -43,7 +131,7 @@ impl AddressExt for DBClient {
         if let Some(address_id) = address_id {
             address = sqlx::query_as!(
                 Address,
-                r#"SELECT * FROM addresses WHERE id = $1"#,
+                r#"SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy FROM addresses WHERE id = $1"#,
                 address_id
             )
             .fetch_optional(&self.pool)
@@ -52,7 +140,7 @@ impl AddressExt for DBClient {
             if let Some(longitude) = longitude {
                 address = sqlx::query_as!(
                     Address,
-                    r#"SELECT * FROM addresses WHERE latitude = $1 AND longitude = $2"#,
+                    r#"SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy FROM addresses WHERE latitude = $1 AND longitude = $2"#,
                     latitude,
                     longitude
                 )
@@ -64,10 +152,45 @@ impl AddressExt for DBClient {
         Ok(address)
     }
 
+    #[instrument(skip_all)]
+    async fn get_addresses_by_details(
+        &self,
+        details: &[(String, String, String)],
+    ) -> Result<Vec<Address>, sqlx::Error> {
+        let mut addresses = Vec::with_capacity(details.len());
+        let mut numbers = Vec::with_capacity(details.len());
+        let mut zip_codes = Vec::with_capacity(details.len());
+
+        for (address, number, zip_code) in details {
+            addresses.push(address.clone());
+            numbers.push(number.clone());
+            zip_codes.push(zip_code.clone());
+        }
+
+        let addresses = sqlx::query_as!(
+            Address,
+            r#"
+            SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy
+            FROM addresses
+            WHERE (address, number, zip_code) IN (
+                SELECT * FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[])
+            )
+            "#,
+            &addresses,
+            &numbers,
+            &zip_codes
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(addresses)
+    }
+
+    #[instrument(skip_all)]
     async fn get_address_random(&self) -> Result<Option<Address>, sqlx::Error> {
         let address = sqlx::query_as!(
             Address,
-            r#"SELECT * FROM addresses ORDER BY RANDOM() LIMIT 1"#,
+            r#"SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy FROM addresses WHERE latitude IS NOT NULL AND longitude IS NOT NULL ORDER BY RANDOM() LIMIT 1"#,
         )
         .fetch_optional(&self.pool)
         .await?;
@@ -75,14 +198,85 @@ impl AddressExt for DBClient {
         Ok(address)
     }
 
-    async fn list_addresses(&self, page: u32, limit: usize) -> Result<Vec<Address>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn list_addresses(
+        &self,
+        page: u32,
+        limit: usize,
+        area: Option<SpatialFilter>,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Address>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
+        if let Some(area) = area {
+            let mut builder: QueryBuilder<Postgres> =
+                QueryBuilder::new(format!("SELECT {ADDRESS_COLUMNS} FROM addresses WHERE ("));
+
+            push_spatial_filter(&mut builder, &area, &ADDRESS_LOCATION, self.postgis);
+            builder.push(")");
+
+            if let Some(after) = after {
+                builder.push(" AND id > ").push_bind(after);
+            }
+
+            builder
+                .push(" ORDER BY id LIMIT ")
+                .push_bind(limit as i64)
+                .push(" OFFSET ")
+                .push_bind(offset as i64);
+
+            return builder
+                .build_query_as::<Address>()
+                .fetch_all(&self.pool)
+                .await;
+        }
+
         let addresses = sqlx::query_as!(
             Address,
-            r#"SELECT * FROM addresses LIMIT $1 OFFSET $2"#,
+            r#"SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy FROM addresses WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(addresses)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_addresses_by_city(
+        &self,
+        city_id: Uuid,
+        page: u32,
+        limit: usize,
+        after: Option<(String, String, String, Uuid)>,
+    ) -> Result<Vec<Address>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+        let (address, number, zip_code, id) = match after {
+            Some((address, number, zip_code, id)) => {
+                (Some(address), Some(number), Some(zip_code), Some(id))
+            }
+            None => (None, None, None, None),
+        };
+
+        let addresses = sqlx::query_as!(
+            Address,
+            r#"
+            SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy
+            FROM addresses
+            WHERE city_id = $1
+                AND ($4::TEXT IS NULL OR (address, number, zip_code, id) > ($4, $5, $6, $7))
+            ORDER BY address, number, zip_code, id
+            LIMIT $2 OFFSET $3
+            "#,
+            city_id,
+            limit as i64,
+            offset as i64,
+            address,
+            number,
+            zip_code,
+            id
         )
         .fetch_all(&self.pool)
         .await?;
@@ -90,6 +284,7 @@ impl AddressExt for DBClient {
         Ok(addresses)
     }
 
+    #[instrument(skip_all)]
     async fn save_address<T: Into<String> + Send, B: Into<BigDecimal> + Send>(
         &self,
         params: SaveAddressParamsDTO<T, B>,
@@ -104,46 +299,244 @@ impl AddressExt for DBClient {
             latitude,
             longitude,
             city_id,
+            geocoding_accuracy,
         } = params;
 
+        let mut tx = self.begin_audited().await?;
+
         let address = sqlx::query_as!(
             Address,
             r#"
-            INSERT INTO addresses (address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id) 
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) 
-            RETURNING *"#,
+            INSERT INTO addresses (address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) 
+            RETURNING id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy"#,
             &address.into(),
             &number.into(),
             &neighbourhood.into(),
             &reference.map(Into::into) as _,
             &complement.map(Into::into) as _,
             &zip_code.into(),
-            &latitude.map(Into::into).unwrap_or_default(),
-            &longitude.map(Into::into).unwrap_or_default(),
+            latitude.map(Into::into) as Option<BigDecimal>,
+            longitude.map(Into::into) as Option<BigDecimal>,
             Uuid::parse_str(&city_id.into()).unwrap(),
+            geocoding_accuracy.map(Into::into) as Option<String>,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(address)
     }
 
+    #[instrument(skip_all)]
+    async fn save_addresses<T: Into<String> + Send, B: Into<BigDecimal> + Send>(
+        &self,
+        addresses: Vec<SaveAddressParamsDTO<T, B>>,
+    ) -> Result<Vec<Address>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+        let mut saved = Vec::with_capacity(addresses.len());
+
+        for params in addresses {
+            let SaveAddressParamsDTO {
+                address,
+                number,
+                neighbourhood,
+                reference,
+                complement,
+                zip_code,
+                latitude,
+                longitude,
+                city_id,
+                geocoding_accuracy,
+            } = params;
+
+            let city_id = Uuid::parse_str(&city_id.into())
+                .map_err(|e| sqlx::Error::Protocol(format!("Failed to parse city_id: {e}")))?;
+
+            let address = sqlx::query_as!(
+                Address,
+                r#"
+            INSERT INTO addresses (address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) 
+            RETURNING id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy"#,
+                &address.into(),
+                &number.into(),
+                &neighbourhood.into(),
+                &reference.map(Into::into) as _,
+                &complement.map(Into::into) as _,
+                &zip_code.into(),
+                latitude.map(Into::into) as Option<BigDecimal>,
+                longitude.map(Into::into) as Option<BigDecimal>,
+                city_id,
+                geocoding_accuracy.map(Into::into) as Option<String>,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            saved.push(address);
+        }
+
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_nearest_addresses(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        radius_meters: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyAddress>, sqlx::Error> {
+        // A bounding box around the point lets the coordinates index discard
+        // most rows before the distances are computed.
+        let [min_latitude, max_latitude, min_longitude, max_longitude] =
+            radius_bounds(latitude, longitude, radius_meters);
+
+        let addresses = sqlx::query_as!(
+            NearbyAddress,
+            r#"
+            SELECT id, address, number, neighbourhood, reference, complement, zip_code,
+                latitude, longitude, city_id, geocoding_accuracy, distance AS "distance!"
+            FROM (
+                SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy, 2 * $7::FLOAT8 * ASIN(SQRT(
+                    POWER(SIN(RADIANS(latitude::FLOAT8 - $1) / 2), 2)
+                    + COS(RADIANS($1)) * COS(RADIANS(latitude::FLOAT8))
+                    * POWER(SIN(RADIANS(longitude::FLOAT8 - $2) / 2), 2)
+                )) AS distance
+                FROM addresses
+                WHERE latitude BETWEEN $3 AND $4 AND longitude BETWEEN $5 AND $6
+            ) AS nearby
+            WHERE distance <= $8
+            ORDER BY distance
+            LIMIT $9
+            "#,
+            latitude,
+            longitude,
+            min_latitude,
+            max_latitude,
+            min_longitude,
+            max_longitude,
+            EARTH_RADIUS_METERS,
+            radius_meters,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(addresses)
+    }
+
+    #[instrument(skip_all)]
+    async fn search_addresses(
+        &self,
+        terms: &str,
+        city_id: Option<Uuid>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<AddressSearchResult>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        // `%>` compares the terms with the closest words of `search_text`,
+        // so a prefix or a misspelled street still matches, and goes
+        // through its trigram index.
+        let addresses = sqlx::query_as!(
+            AddressSearchResult,
+            r#"
+            SELECT a.id, a.address, a.number, a.neighbourhood, a.reference, a.complement, a.zip_code,
+                a.latitude, a.longitude, a.city_id, a.geocoding_accuracy,
+                c.name AS city_name, s.name AS state_name, s.code AS state_code,
+                word_similarity(immutable_unaccent(LOWER($1)), a.search_text)::FLOAT8 AS "score!"
+            FROM addresses a
+            INNER JOIN cities c ON c.id = a.city_id
+            INNER JOIN states s ON s.id = c.state_id
+            WHERE a.search_text %> immutable_unaccent(LOWER($1))
+                AND ($2::UUID IS NULL OR a.city_id = $2)
+            ORDER BY "score!" DESC, a.address, a.number
+            LIMIT $3 OFFSET $4
+            "#,
+            terms,
+            city_id,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(addresses)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_addresses_without_coordinates(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<Address>, sqlx::Error> {
+        let addresses = sqlx::query_as!(
+            Address,
+            r#"
+            SELECT id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy FROM addresses
+            WHERE (latitude IS NULL OR longitude IS NULL) AND ($1::UUID IS NULL OR id > $1)
+            ORDER BY id
+            LIMIT $2
+            "#,
+            after,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(addresses)
+    }
+
+    #[instrument(skip_all)]
+    async fn update_address_coordinates(
+        &self,
+        address_id: Uuid,
+        latitude: BigDecimal,
+        longitude: BigDecimal,
+        geocoding_accuracy: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
+        sqlx::query!(
+            r#"UPDATE addresses SET latitude = $2, longitude = $3, geocoding_accuracy = $4 WHERE id = $1"#,
+            address_id,
+            latitude,
+            longitude,
+            geocoding_accuracy
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
     async fn delete_address(
         &self,
         address_id: Option<Uuid>,
     ) -> Result<Option<Address>, sqlx::Error> {
         let mut address = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(address_id) = address_id {
             address = sqlx::query_as!(
                 Address,
-                r#"DELETE FROM addresses WHERE id = $1 RETURNING *"#,
+                r#"DELETE FROM addresses WHERE id = $1 RETURNING id, address, number, neighbourhood, reference, complement, zip_code, latitude, longitude, city_id, geocoding_accuracy"#,
                 address_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(address)
     }
 }
@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use tracing::instrument;
+
+use super::client::DBClient;
+use crate::models::admin::Admin;
+
+#[async_trait]
+pub trait AdminExt {
+    /// Saves an admin with an already hashed password. The email is stored
+    /// in lowercase.
+    async fn save_admin<T: Into<String> + Send>(
+        &self,
+        name: T,
+        email: T,
+        password_hash: T,
+    ) -> Result<Admin, sqlx::Error>;
+}
+
+#[async_trait]
+impl AdminExt for DBClient {
+    #[instrument(skip_all)]
+    async fn save_admin<T: Into<String> + Send>(
+        &self,
+        name: T,
+        email: T,
+        password_hash: T,
+    ) -> Result<Admin, sqlx::Error> {
+        let admin = sqlx::query_as!(
+            Admin,
+            r#"
+            INSERT INTO admins (name, email, password_hash)
+            VALUES ($1, LOWER($2), $3)
+            RETURNING *
+            "#,
+            name.into(),
+            email.into(),
+            password_hash.into()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(admin)
+    }
+}
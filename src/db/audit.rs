@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::models::audit::AuditLogEntry;
+
+use super::client::DBClient;
+
+/// Reads the audit log. It is only written by the triggers of the audited
+/// tables, and can't be changed.
+#[async_trait]
+pub trait AuditExt {
+    /// Lists the changes made to an entity type, or to one entity, most
+    /// recent first.
+    async fn list_audit_log_entries(
+        &self,
+        entity_type: Option<&str>,
+        entity_id: Option<Uuid>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<AuditLogEntry>, sqlx::Error>;
+}
+
+#[async_trait]
+impl AuditExt for DBClient {
+    #[instrument(skip_all)]
+    async fn list_audit_log_entries(
+        &self,
+        entity_type: Option<&str>,
+        entity_id: Option<Uuid>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<AuditLogEntry>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        let entries = sqlx::query_as!(
+            AuditLogEntry,
+            r#"
+            SELECT id, entity_type, entity_id, action, actor, request_id, before, after, occurred_at
+            FROM audit_log
+            WHERE ($1::VARCHAR IS NULL OR entity_type = $1) AND ($2::UUID IS NULL OR entity_id = $2)
+            ORDER BY id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            entity_type,
+            entity_id,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+}
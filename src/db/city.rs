@@ -1,9 +1,18 @@
 use async_trait::async_trait;
+use sqlx::PgConnection;
+use tracing::instrument;
 use uuid::Uuid;
 
-use crate::models::city::City;
+use crate::{
+    dtos::{
+        city::{ImportCityCentroidRowDTO, ImportCityRowDTO, RegisterCityDTO},
+        state::RegisterStateDTO,
+    },
+    models::city::City,
+    utils::string::escape_like,
+};
 
-use super::client::DBClient;
+use super::{client::DBClient, state::upsert_states};
 
 #[async_trait]
 pub trait CityExt {
@@ -13,7 +22,21 @@ pub trait CityExt {
         code: Option<&str>,
     ) -> Result<Option<City>, sqlx::Error>;
 
-    async fn list_cities(&self, page: u32, limit: usize) -> Result<Vec<City>, sqlx::Error>;
+    /// Finds the cities with any of the `codes` in a single query.
+    async fn get_cities_by_codes(&self, codes: &[String]) -> Result<Vec<City>, sqlx::Error>;
+
+    /// Lists a page of cities ordered by name, restricted to the state
+    /// `state_id`, to the names starting with `name`, regardless of case
+    /// and accents, and to those after the `(name, id)` key `after`, when
+    /// given.
+    async fn list_cities(
+        &self,
+        page: u32,
+        limit: usize,
+        name: Option<&str>,
+        state_id: Option<Uuid>,
+        after: Option<(String, Uuid)>,
+    ) -> Result<Vec<City>, sqlx::Error>;
 
     async fn save_city<T: Into<String> + Send>(
         &self,
@@ -22,11 +45,30 @@ pub trait CityExt {
         state_id: T,
     ) -> Result<City, sqlx::Error>;
 
+    async fn save_cities(&self, cities: Vec<RegisterCityDTO>) -> Result<Vec<City>, sqlx::Error>;
+
+    /// Upserts the states, then the cities, in a single transaction, so a
+    /// failed import leaves the geography as it was. Returns the flags of
+    /// `upsert_states` and `upsert_cities`.
+    async fn upsert_geography(
+        &self,
+        states: Vec<RegisterStateDTO>,
+        cities: Vec<ImportCityRowDTO>,
+    ) -> Result<(Vec<bool>, Vec<bool>), sqlx::Error>;
+
+    /// Sets the centroid of the cities, found by their code, in a single
+    /// statement. Returns how many cities changed.
+    async fn update_city_centroids(
+        &self,
+        centroids: Vec<ImportCityCentroidRowDTO>,
+    ) -> Result<u64, sqlx::Error>;
+
     async fn delete_city(&self, city_id: Option<Uuid>) -> Result<Option<City>, sqlx::Error>;
 }
 
 #[async_trait]
 impl CityExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_city(
         &self,
         city_id: Option<Uuid>,
@@ -47,14 +89,43 @@ impl CityExt for DBClient {
         Ok(city)
     }
 
-    async fn list_cities(&self, page: u32, limit: usize) -> Result<Vec<City>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn get_cities_by_codes(&self, codes: &[String]) -> Result<Vec<City>, sqlx::Error> {
+        let cities = sqlx::query_as!(City, r#"SELECT * FROM cities WHERE code = ANY($1)"#, codes)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(cities)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_cities(
+        &self,
+        page: u32,
+        limit: usize,
+        name: Option<&str>,
+        state_id: Option<Uuid>,
+        after: Option<(String, Uuid)>,
+    ) -> Result<Vec<City>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
+        let (after_name, after_id) = after.unzip();
 
         let cities = sqlx::query_as!(
             City,
-            r#"SELECT * FROM cities LIMIT $1 OFFSET $2"#,
+            r#"
+            SELECT * FROM cities
+            WHERE ($3::TEXT IS NULL OR immutable_unaccent(LOWER(name)) LIKE immutable_unaccent(LOWER($3)) || '%')
+                AND ($4::UUID IS NULL OR state_id = $4)
+                AND ($5::TEXT IS NULL OR (name, id) > ($5, $6))
+            ORDER BY name, id
+            LIMIT $1 OFFSET $2
+            "#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            name.map(escape_like),
+            state_id,
+            after_name,
+            after_id
         )
         .fetch_all(&self.pool)
         .await?;
@@ -62,12 +133,15 @@ impl CityExt for DBClient {
         Ok(cities)
     }
 
+    #[instrument(skip_all)]
     async fn save_city<T: Into<String> + Send>(
         &self,
         name: T,
         code: T,
         state_id: T,
     ) -> Result<City, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
         let city = sqlx::query_as!(
             City,
             r#"INSERT INTO cities (name, code, state_id) VALUES ($1, $2, $3) RETURNING *"#,
@@ -75,25 +149,153 @@ impl CityExt for DBClient {
             &code.into(),
             Uuid::parse_str(&state_id.into()).unwrap(),
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(city)
     }
 
+    #[instrument(skip_all)]
+    async fn save_cities(&self, cities: Vec<RegisterCityDTO>) -> Result<Vec<City>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+        let mut saved = Vec::with_capacity(cities.len());
+
+        for city in cities {
+            let state_id = Uuid::parse_str(&city.state_id)
+                .map_err(|e| sqlx::Error::Protocol(format!("Failed to parse state_id: {e}")))?;
+
+            let city = sqlx::query_as!(
+                City,
+                r#"INSERT INTO cities (name, code, state_id) VALUES ($1, $2, $3) RETURNING *"#,
+                &city.name,
+                &city.code,
+                state_id,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            saved.push(city);
+        }
+
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    #[instrument(skip_all)]
+    async fn upsert_geography(
+        &self,
+        states: Vec<RegisterStateDTO>,
+        cities: Vec<ImportCityRowDTO>,
+    ) -> Result<(Vec<bool>, Vec<bool>), sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
+        let changed_states = upsert_states(&mut tx, states).await?;
+        let changed_cities = upsert_cities(&mut tx, cities).await?;
+
+        tx.commit().await?;
+
+        Ok((changed_states, changed_cities))
+    }
+
+    #[instrument(skip_all)]
+    async fn update_city_centroids(
+        &self,
+        centroids: Vec<ImportCityCentroidRowDTO>,
+    ) -> Result<u64, sqlx::Error> {
+        let mut codes = Vec::with_capacity(centroids.len());
+        let mut latitudes = Vec::with_capacity(centroids.len());
+        let mut longitudes = Vec::with_capacity(centroids.len());
+
+        for centroid in centroids {
+            codes.push(centroid.code);
+            latitudes.push(centroid.latitude);
+            longitudes.push(centroid.longitude);
+        }
+
+        let mut tx = self.begin_audited().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE cities SET latitude = row.latitude, longitude = row.longitude
+            FROM UNNEST($1::VARCHAR[], $2::NUMERIC[], $3::NUMERIC[]) AS row (code, latitude, longitude)
+            WHERE cities.code = row.code
+                AND (cities.latitude, cities.longitude) IS DISTINCT FROM (row.latitude, row.longitude)
+            "#,
+            &codes,
+            &latitudes,
+            &longitudes,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip_all)]
     async fn delete_city(&self, city_id: Option<Uuid>) -> Result<Option<City>, sqlx::Error> {
         let mut city = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(city_id) = city_id {
             city = sqlx::query_as!(
                 City,
                 r#"DELETE FROM cities WHERE id = $1 RETURNING *"#,
                 city_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(city)
     }
 }
+
+/// Inserts or updates the cities by their code in a single statement,
+/// resolving each state by its code. Cities of unknown states are skipped.
+/// Returns one flag per inserted or changed row, `true` when the row was
+/// inserted; unchanged rows are not returned.
+pub async fn upsert_cities(
+    conn: &mut PgConnection,
+    cities: Vec<ImportCityRowDTO>,
+) -> Result<Vec<bool>, sqlx::Error> {
+    let mut names = Vec::with_capacity(cities.len());
+    let mut codes = Vec::with_capacity(cities.len());
+    let mut state_codes = Vec::with_capacity(cities.len());
+
+    for city in cities {
+        names.push(city.name);
+        codes.push(city.code);
+        state_codes.push(city.state_code);
+    }
+
+    // `xmax` is 0 for rows created by this statement, which tells
+    // inserts apart from updates.
+    let inserted = sqlx::query_scalar!(
+        r#"
+        INSERT INTO cities (name, code, state_id)
+        SELECT city.name, city.code, states.id
+        FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[]) AS city (name, code, state_code)
+        JOIN states ON states.code = city.state_code
+        ON CONFLICT (code) DO UPDATE
+            SET name = EXCLUDED.name, state_id = EXCLUDED.state_id
+            WHERE (cities.name, cities.state_id)
+                IS DISTINCT FROM (EXCLUDED.name, EXCLUDED.state_id)
+        RETURNING (xmax = 0) AS "inserted!"
+        "#,
+        &names,
+        &codes,
+        &state_codes,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(inserted)
+}
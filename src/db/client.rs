@@ -1,10 +1,17 @@
-use sqlx::{Pool, Postgres};
+use sqlx::{migrate::Migrator, Pool, Postgres, Transaction};
+
+use crate::utils::audit::set_audit_context;
+
+/// The migrations embedded in the binary, run at startup.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
 /// Represents a client for interacting with a PostgreSQL database.
 #[derive(Debug, Clone)]
 pub struct DBClient {
     /// The database connection pool.
     pub pool: Pool<Postgres>,
+    /// Whether the PostGIS geography columns exist, see `SpatialExt`.
+    pub postgis: bool,
 }
 
 impl DBClient {
@@ -28,6 +35,19 @@ impl DBClient {
     /// let db_client = DBClient::new(pool);
     /// ```
     pub fn new(pool: Pool<Postgres>) -> Self {
-        DBClient { pool }
+        DBClient {
+            pool,
+            postgis: false,
+        }
+    }
+
+    /// Starts a transaction that tells the audit triggers who is making its
+    /// changes. Every change to an audited entity runs in one.
+    pub async fn begin_audited(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        set_audit_context(&mut tx).await?;
+
+        Ok(tx)
     }
 }
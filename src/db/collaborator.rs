@@ -1,9 +1,10 @@
 use async_trait::async_trait;
-use log::info;
+use chrono::NaiveDateTime;
+use tracing::{info, instrument};
 use uuid::Uuid;
 
 use super::client::DBClient;
-use crate::models::collaborator::Collaborator;
+use crate::{dtos::collaborator::RegisterCollaboratorDTO, models::collaborator::Collaborator};
 
 #[async_trait]
 pub trait CollaboratorExt {
@@ -14,10 +15,21 @@ pub trait CollaboratorExt {
         email: Option<&str>,
     ) -> Result<Option<Collaborator>, sqlx::Error>;
 
+    /// Finds the collaborators with any of the `cpfs` or `emails` in a single
+    /// query.
+    async fn get_collaborators_by_cpfs_or_emails(
+        &self,
+        cpfs: &[String],
+        emails: &[String],
+    ) -> Result<Vec<Collaborator>, sqlx::Error>;
+
+    /// Lists a page of collaborators, restricted to those after the id `after`
+    /// when given.
     async fn list_collaborators(
         &self,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<Collaborator>, sqlx::Error>;
 
     async fn save_collaborator<T: Into<String> + Send>(
@@ -28,6 +40,13 @@ pub trait CollaboratorExt {
         email: T,
     ) -> Result<Collaborator, sqlx::Error>;
 
+    async fn save_collaborators(
+        &self,
+        collaborators: Vec<RegisterCollaboratorDTO>,
+    ) -> Result<Vec<Collaborator>, sqlx::Error>;
+
+    /// Updates the collaborator, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn update_collaborator<T: Into<String> + Send>(
         &self,
         collaborator_id: Option<Uuid>,
@@ -35,16 +54,21 @@ pub trait CollaboratorExt {
         cpf: T,
         rg: T,
         email: T,
-    ) -> Result<Collaborator, sqlx::Error>;
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Collaborator>, sqlx::Error>;
 
+    /// Deletes the collaborator, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn delete_collaborator(
         &self,
         collaborator_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
     ) -> Result<Option<Collaborator>, sqlx::Error>;
 }
 
 #[async_trait]
 impl CollaboratorExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_collaborator(
         &self,
         collaborator_id: Option<Uuid>,
@@ -82,18 +106,39 @@ impl CollaboratorExt for DBClient {
         Ok(collaborator)
     }
 
+    #[instrument(skip_all)]
+    async fn get_collaborators_by_cpfs_or_emails(
+        &self,
+        cpfs: &[String],
+        emails: &[String],
+    ) -> Result<Vec<Collaborator>, sqlx::Error> {
+        let collaborators = sqlx::query_as!(
+            Collaborator,
+            r#"SELECT * FROM collaborators WHERE cpf = ANY($1) OR email = ANY($2)"#,
+            cpfs,
+            emails
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(collaborators)
+    }
+
+    #[instrument(skip_all)]
     async fn list_collaborators(
         &self,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<Collaborator>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         let collaborators = sqlx::query_as!(
             Collaborator,
-            r#"SELECT * FROM collaborators LIMIT $1 OFFSET $2"#,
+            r#"SELECT * FROM collaborators WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
@@ -101,6 +146,7 @@ impl CollaboratorExt for DBClient {
         Ok(collaborators)
     }
 
+    #[instrument(skip_all)]
     async fn save_collaborator<T: Into<String> + Send>(
         &self,
         name: T,
@@ -108,6 +154,8 @@ impl CollaboratorExt for DBClient {
         rg: T,
         email: T,
     ) -> Result<Collaborator, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
         let collaborator = sqlx::query_as!(
             Collaborator,
             r#"INSERT INTO collaborators (name, cpf, rg, email) VALUES ($1, $2, $3, $4) RETURNING *"#,
@@ -116,12 +164,43 @@ impl CollaboratorExt for DBClient {
             &rg.into(),
             &email.into(),
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(collaborator)
     }
 
+    #[instrument(skip_all)]
+    async fn save_collaborators(
+        &self,
+        collaborators: Vec<RegisterCollaboratorDTO>,
+    ) -> Result<Vec<Collaborator>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+        let mut saved = Vec::with_capacity(collaborators.len());
+
+        for collaborator in collaborators {
+            let collaborator = sqlx::query_as!(
+                Collaborator,
+                r#"INSERT INTO collaborators (name, cpf, rg, email) VALUES ($1, $2, $3, $4) RETURNING *"#,
+                &collaborator.name,
+                &collaborator.cpf,
+                &collaborator.rg,
+                &collaborator.email,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            saved.push(collaborator);
+        }
+
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    #[instrument(skip_all)]
     async fn update_collaborator<T: Into<String> + Send>(
         &self,
         collaborator_id: Option<Uuid>,
@@ -129,42 +208,55 @@ impl CollaboratorExt for DBClient {
         cpf: T,
         rg: T,
         email: T,
-    ) -> Result<Collaborator, sqlx::Error> {
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Collaborator>, sqlx::Error> {
         let name = name.into();
 
         info!("Updating the collaborator: {}", &name);
 
+        let mut tx = self.begin_audited().await?;
+
         let collaborator = sqlx::query_as!(
             Collaborator,
-            r#"UPDATE collaborators SET name = $2, cpf = $3, rg = $4, email = $5 WHERE id = $1 RETURNING *;"#,
+            r#"UPDATE collaborators SET name = $2, cpf = $3, rg = $4, email = $5 WHERE id = $1 AND ($6::TIMESTAMP IS NULL OR updated_at = $6) RETURNING *;"#,
             &collaborator_id.unwrap(),
             &name,
             &cpf.into(),
             &rg.into(),
-            &email.into()
+            &email.into(),
+            expected_updated_at
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(collaborator)
     }
 
+    #[instrument(skip_all)]
     async fn delete_collaborator(
         &self,
         collaborator_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
     ) -> Result<Option<Collaborator>, sqlx::Error> {
         let mut collaborator = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(collaborator_id) = collaborator_id {
             collaborator = sqlx::query_as!(
                 Collaborator,
-                r#"DELETE FROM collaborators WHERE id = $1 RETURNING *"#,
-                collaborator_id
+                r#"DELETE FROM collaborators WHERE id = $1 AND ($2::TIMESTAMP IS NULL OR updated_at = $2) RETURNING *"#,
+                collaborator_id,
+                expected_updated_at
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(collaborator)
     }
 }
@@ -1,9 +1,11 @@
 use async_trait::async_trait;
-use log::info;
+use tracing::{info, instrument};
 use uuid::Uuid;
 
 use super::client::DBClient;
-use crate::models::country::Country;
+use crate::{
+    dtos::country::RegisterCountryDTO, models::country::Country, utils::string::escape_like,
+};
 
 #[async_trait]
 pub trait CountryExt {
@@ -16,7 +18,16 @@ pub trait CountryExt {
         numeric_3: Option<&str>,
     ) -> Result<Option<Country>, sqlx::Error>;
 
-    async fn list_countries(&self, page: u32, limit: usize) -> Result<Vec<Country>, sqlx::Error>;
+    /// Lists a page of countries ordered by name, restricted to the names
+    /// starting with `name`, regardless of case and accents, and to those
+    /// after the `(name, id)` key `after`, when given.
+    async fn list_countries(
+        &self,
+        page: u32,
+        limit: usize,
+        name: Option<&str>,
+        after: Option<(String, Uuid)>,
+    ) -> Result<Vec<Country>, sqlx::Error>;
 
     async fn save_country<T: Into<String> + Send>(
         &self,
@@ -35,6 +46,14 @@ pub trait CountryExt {
         numeric_3: T,
     ) -> Result<Country, sqlx::Error>;
 
+    /// Inserts or updates the countries by their alpha-2 code in a single
+    /// statement. Returns one flag per inserted or changed row, `true` when
+    /// the row was inserted; unchanged rows are not returned.
+    async fn upsert_countries(
+        &self,
+        countries: Vec<RegisterCountryDTO>,
+    ) -> Result<Vec<bool>, sqlx::Error>;
+
     async fn delete_country(
         &self,
         country_id: Option<Uuid>,
@@ -43,6 +62,7 @@ pub trait CountryExt {
 
 #[async_trait]
 impl CountryExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_country(
         &self,
         country_id: Option<Uuid>,
@@ -94,14 +114,31 @@ impl CountryExt for DBClient {
         Ok(country)
     }
 
-    async fn list_countries(&self, page: u32, limit: usize) -> Result<Vec<Country>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn list_countries(
+        &self,
+        page: u32,
+        limit: usize,
+        name: Option<&str>,
+        after: Option<(String, Uuid)>,
+    ) -> Result<Vec<Country>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
+        let (after_name, after_id) = after.unzip();
 
         let countries = sqlx::query_as!(
             Country,
-            r#"SELECT * FROM countries LIMIT $1 OFFSET $2"#,
+            r#"
+            SELECT * FROM countries
+            WHERE ($3::TEXT IS NULL OR immutable_unaccent(LOWER(name)) LIKE immutable_unaccent(LOWER($3)) || '%')
+                AND ($4::TEXT IS NULL OR (name, id) > ($4, $5))
+            ORDER BY name, id
+            LIMIT $1 OFFSET $2
+            "#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            name.map(escape_like),
+            after_name,
+            after_id
         )
         .fetch_all(&self.pool)
         .await?;
@@ -109,6 +146,7 @@ impl CountryExt for DBClient {
         Ok(countries)
     }
 
+    #[instrument(skip_all)]
     async fn save_country<T: Into<String> + Send>(
         &self,
         name: T,
@@ -120,6 +158,8 @@ impl CountryExt for DBClient {
 
         info!("Creating the country: {}", &name);
 
+        let mut tx = self.begin_audited().await?;
+
         let country = sqlx::query_as!(
             Country,
             r#"INSERT INTO countries (name, alpha_2, alpha_3, numeric_3) VALUES ($1, $2, $3, $4) RETURNING *"#,
@@ -128,12 +168,15 @@ impl CountryExt for DBClient {
             &alpha_3.into(),
             &numeric_3.into(),
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(country)
     }
 
+    #[instrument(skip_all)]
     async fn update_country<T: Into<String> + Send>(
         &self,
         country_id: Uuid,
@@ -146,6 +189,8 @@ impl CountryExt for DBClient {
 
         info!("Updating the country: {}", &name);
 
+        let mut tx = self.begin_audited().await?;
+
         let country = sqlx::query_as!(
             Country,
             r#"UPDATE countries
@@ -158,25 +203,76 @@ impl CountryExt for DBClient {
             &alpha_3.into(),
             &numeric_3.into()
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(country)
     }
 
+    #[instrument(skip_all)]
+    async fn upsert_countries(
+        &self,
+        countries: Vec<RegisterCountryDTO>,
+    ) -> Result<Vec<bool>, sqlx::Error> {
+        let mut names = Vec::with_capacity(countries.len());
+        let mut alpha_2_codes = Vec::with_capacity(countries.len());
+        let mut alpha_3_codes = Vec::with_capacity(countries.len());
+        let mut numeric_3_codes = Vec::with_capacity(countries.len());
+
+        for country in countries {
+            names.push(country.name);
+            alpha_2_codes.push(country.alpha_2);
+            alpha_3_codes.push(country.alpha_3);
+            numeric_3_codes.push(country.numeric_3);
+        }
+
+        let mut tx = self.begin_audited().await?;
+
+        // `xmax` is 0 for rows created by this statement, which tells
+        // inserts apart from updates.
+        let inserted = sqlx::query_scalar!(
+            r#"
+            INSERT INTO countries (name, alpha_2, alpha_3, numeric_3)
+            SELECT * FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[], $4::VARCHAR[])
+            ON CONFLICT (alpha_2) DO UPDATE
+                SET name = EXCLUDED.name, alpha_3 = EXCLUDED.alpha_3, numeric_3 = EXCLUDED.numeric_3
+                WHERE (countries.name, countries.alpha_3, countries.numeric_3)
+                    IS DISTINCT FROM (EXCLUDED.name, EXCLUDED.alpha_3, EXCLUDED.numeric_3)
+            RETURNING (xmax = 0) AS "inserted!"
+            "#,
+            &names,
+            &alpha_2_codes,
+            &alpha_3_codes,
+            &numeric_3_codes,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(inserted)
+    }
+
+    #[instrument(skip_all)]
     async fn delete_country(
         &self,
         country_id: Option<Uuid>,
     ) -> Result<Option<Country>, sqlx::Error> {
         if let Some(country_id) = country_id {
+            let mut tx = self.begin_audited().await?;
+
             let country = sqlx::query_as!(
                 Country,
                 r#"DELETE FROM countries WHERE id = $1 RETURNING *"#,
                 country_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
 
+            tx.commit().await?;
+
             if let Some(ref country) = country {
                 info!("Deleted the country: {}", country.name);
             }
@@ -1,10 +1,14 @@
 use async_trait::async_trait;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use sqlx::Error;
+use tracing::instrument;
 use uuid::Uuid;
 
 use super::client::DBClient;
-use crate::models::driver::{CnhType, Driver};
+use crate::{
+    dtos::driver::RegisterDriverDTO,
+    models::driver::{CnhType, Driver},
+};
 
 #[async_trait]
 pub trait DriverExt {
@@ -15,7 +19,22 @@ pub trait DriverExt {
         collaborator_id: Option<Uuid>,
     ) -> Result<Option<Driver>, sqlx::Error>;
 
-    async fn list_drivers(&self, page: u32, limit: usize) -> Result<Vec<Driver>, sqlx::Error>;
+    /// Finds the drivers with any of the `cnh_numbers` or `collaborator_ids`
+    /// in a single query.
+    async fn get_drivers_by_cnh_numbers_or_collaborators(
+        &self,
+        cnh_numbers: &[String],
+        collaborator_ids: &[Uuid],
+    ) -> Result<Vec<Driver>, sqlx::Error>;
+
+    /// Lists a page of drivers, restricted to those after the id `after`
+    /// when given.
+    async fn list_drivers(
+        &self,
+        page: u32,
+        limit: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Driver>, sqlx::Error>;
 
     async fn save_driver<T: Into<String> + Send>(
         &self,
@@ -25,6 +44,13 @@ pub trait DriverExt {
         collaborator_id: T,
     ) -> Result<Driver, sqlx::Error>;
 
+    async fn save_drivers(
+        &self,
+        drivers: Vec<RegisterDriverDTO>,
+    ) -> Result<Vec<Driver>, sqlx::Error>;
+
+    /// Updates the driver, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn update_driver<T: Into<String> + Send>(
         &self,
         driver_id: Option<Uuid>,
@@ -32,17 +58,25 @@ pub trait DriverExt {
         cnh_number: T,
         cnh_expiration_date: NaiveDate,
         cnh_type_id: T,
-    ) -> Result<Driver, sqlx::Error>;
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Driver>, sqlx::Error>;
 
+    /// Deletes the driver, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn delete_driver(
         &self,
         driver_id: Option<Uuid>,
         collaborator_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
     ) -> Result<Option<Driver>, sqlx::Error>;
+
+    /// Counts drivers whose CNH expired before today.
+    async fn count_drivers_with_expired_cnh(&self) -> Result<i64, sqlx::Error>;
 }
 
 #[async_trait]
 impl DriverExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_driver(
         &self,
         driver_id: Option<Uuid>,
@@ -76,14 +110,39 @@ impl DriverExt for DBClient {
         Ok(driver)
     }
 
-    async fn list_drivers(&self, page: u32, limit: usize) -> Result<Vec<Driver>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn get_drivers_by_cnh_numbers_or_collaborators(
+        &self,
+        cnh_numbers: &[String],
+        collaborator_ids: &[Uuid],
+    ) -> Result<Vec<Driver>, sqlx::Error> {
+        let drivers = sqlx::query_as!(
+            Driver,
+            r#"SELECT * FROM drivers WHERE cnh_number = ANY($1) OR collaborator_id = ANY($2)"#,
+            cnh_numbers,
+            collaborator_ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(drivers)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_drivers(
+        &self,
+        page: u32,
+        limit: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Driver>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         let drivers = sqlx::query_as!(
             Driver,
-            r#"SELECT * FROM drivers LIMIT $1 OFFSET $2"#,
+            r#"SELECT * FROM drivers WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
@@ -91,6 +150,7 @@ impl DriverExt for DBClient {
         Ok(drivers)
     }
 
+    #[instrument(skip_all)]
     async fn save_driver<T: Into<String> + Send>(
         &self,
         cnh_number: T,
@@ -104,6 +164,8 @@ impl DriverExt for DBClient {
         let collaborator_id = Uuid::parse_str(&collaborator_id.into())
             .map_err(|e| Error::Protocol(format!("Failed to parse collaborator_id: {e}")))?;
 
+        let mut tx = self.begin_audited().await?;
+
         let driver = sqlx::query_as!(
             Driver,
             r#"INSERT INTO drivers (cnh_number, cnh_expiration_date, cnh_type_id, collaborator_id) VALUES ($1, $2, $3, $4) RETURNING *"#,
@@ -112,12 +174,49 @@ impl DriverExt for DBClient {
             &cnh_type_id,
             &collaborator_id,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(driver)
     }
 
+    #[instrument(skip_all)]
+    async fn save_drivers(
+        &self,
+        drivers: Vec<RegisterDriverDTO>,
+    ) -> Result<Vec<Driver>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+        let mut saved = Vec::with_capacity(drivers.len());
+
+        for driver in drivers {
+            let cnh_type_id = Uuid::parse_str(&driver.id_cnh_type)
+                .map_err(|e| Error::Protocol(format!("Failed to parse cnh_type_id: {e}")))?;
+
+            let collaborator_id = Uuid::parse_str(&driver.collaborator_id)
+                .map_err(|e| Error::Protocol(format!("Failed to parse collaborator_id: {e}")))?;
+
+            let driver = sqlx::query_as!(
+                Driver,
+                r#"INSERT INTO drivers (cnh_number, cnh_expiration_date, cnh_type_id, collaborator_id) VALUES ($1, $2, $3, $4) RETURNING *"#,
+                driver.cnh_number,
+                driver.cnh_expiration_date,
+                &cnh_type_id,
+                &collaborator_id,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            saved.push(driver);
+        }
+
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    #[instrument(skip_all)]
     async fn update_driver<T: Into<String> + Send>(
         &self,
         driver_id: Option<Uuid>,
@@ -125,30 +224,35 @@ impl DriverExt for DBClient {
         cnh_number: T,
         cnh_expiration_date: NaiveDate,
         cnh_type_id: T,
-    ) -> Result<Driver, sqlx::Error> {
-        let driver: Driver = match (driver_id, collaborator_id) {
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Driver>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
+        let driver = match (driver_id, collaborator_id) {
             (Some(driver_id), _) => {
                 sqlx::query_as!(
                     Driver,
-                    r#"UPDATE drivers SET cnh_number = $2, cnh_expiration_date = $3, cnh_type_id = $4 WHERE id = $1 RETURNING *;"#,
+                    r#"UPDATE drivers SET cnh_number = $2, cnh_expiration_date = $3, cnh_type_id = $4 WHERE id = $1 AND ($5::TIMESTAMP IS NULL OR updated_at = $5) RETURNING *;"#,
                     &driver_id,
                     &cnh_number.into(),
                     &cnh_expiration_date,
-                    Uuid::parse_str(&cnh_type_id.into()).unwrap()
+                    Uuid::parse_str(&cnh_type_id.into()).unwrap(),
+                    expected_updated_at
                 )
-                .fetch_one(&self.pool)
+                .fetch_optional(&mut *tx)
                 .await?
             }
             (None, Some(collaborator_id)) => {
                 sqlx::query_as!(
                     Driver,
-                    r#"UPDATE drivers SET cnh_number = $2, cnh_expiration_date = $3, cnh_type_id = $4 WHERE collaborator_id = $1 RETURNING *;"#,
+                    r#"UPDATE drivers SET cnh_number = $2, cnh_expiration_date = $3, cnh_type_id = $4 WHERE collaborator_id = $1 AND ($5::TIMESTAMP IS NULL OR updated_at = $5) RETURNING *;"#,
                     &collaborator_id,
                     &cnh_number.into(),
                     &cnh_expiration_date,
-                    Uuid::parse_str(&cnh_type_id.into()).unwrap()
+                    Uuid::parse_str(&cnh_type_id.into()).unwrap(),
+                    expected_updated_at
                 )
-                .fetch_one(&self.pool)
+                .fetch_optional(&mut *tx)
                 .await?
             }
             _ => {
@@ -156,36 +260,57 @@ impl DriverExt for DBClient {
             }
         };
 
+        tx.commit().await?;
+
         Ok(driver)
     }
 
+    #[instrument(skip_all)]
     async fn delete_driver(
         &self,
         driver_id: Option<Uuid>,
         collaborator_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
     ) -> Result<Option<Driver>, sqlx::Error> {
         let mut driver = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(driver_id) = driver_id {
             driver = sqlx::query_as!(
                 Driver,
-                r#"DELETE FROM drivers WHERE id = $1 RETURNING *"#,
-                driver_id
+                r#"DELETE FROM drivers WHERE id = $1 AND ($2::TIMESTAMP IS NULL OR updated_at = $2) RETURNING *"#,
+                driver_id,
+                expected_updated_at
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         } else if let Some(collaborator_id) = collaborator_id {
             driver = sqlx::query_as!(
                 Driver,
-                r#"DELETE FROM drivers WHERE collaborator_id = $1 RETURNING *"#,
-                collaborator_id
+                r#"DELETE FROM drivers WHERE collaborator_id = $1 AND ($2::TIMESTAMP IS NULL OR updated_at = $2) RETURNING *"#,
+                collaborator_id,
+                expected_updated_at
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(driver)
     }
+
+    #[instrument(skip_all)]
+    async fn count_drivers_with_expired_cnh(&self) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM drivers WHERE cnh_expiration_date < CURRENT_DATE"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
 }
 
 #[async_trait]
@@ -196,11 +321,28 @@ pub trait CnhTypeExt {
         code: Option<String>,
     ) -> Result<Option<CnhType>, sqlx::Error>;
 
-    async fn list_cnh_type(&self, page: u32, limit: usize) -> Result<Vec<CnhType>, sqlx::Error>;
+    /// Finds the CNH types with any of the `codes` in a single query.
+    async fn get_cnh_types_by_codes(&self, codes: &[String]) -> Result<Vec<CnhType>, sqlx::Error>;
+
+    /// Lists a page of CNH types, restricted to those after the id `after`
+    /// when given.
+    async fn list_cnh_type(
+        &self,
+        page: u32,
+        limit: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<CnhType>, sqlx::Error>;
+
+    async fn save_cnh_type<T: Into<String> + Send>(
+        &self,
+        code: T,
+        description: T,
+    ) -> Result<CnhType, sqlx::Error>;
 }
 
 #[async_trait]
 impl CnhTypeExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_cnh_type(
         &self,
         cnh_type_id: Option<Uuid>,
@@ -225,18 +367,60 @@ impl CnhTypeExt for DBClient {
         Ok(cnh_type)
     }
 
-    async fn list_cnh_type(&self, page: u32, limit: usize) -> Result<Vec<CnhType>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn get_cnh_types_by_codes(&self, codes: &[String]) -> Result<Vec<CnhType>, sqlx::Error> {
+        let cnh_types = sqlx::query_as!(
+            CnhType,
+            r#"SELECT * FROM cnh_types WHERE code = ANY($1)"#,
+            codes
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(cnh_types)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_cnh_type(
+        &self,
+        page: u32,
+        limit: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<CnhType>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         let cnh_types = sqlx::query_as!(
             CnhType,
-            r#"SELECT * FROM cnh_types LIMIT $1 OFFSET $2"#,
+            r#"SELECT * FROM cnh_types WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
 
         Ok(cnh_types)
     }
+
+    #[instrument(skip_all)]
+    async fn save_cnh_type<T: Into<String> + Send>(
+        &self,
+        code: T,
+        description: T,
+    ) -> Result<CnhType, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
+        let cnh_type = sqlx::query_as!(
+            CnhType,
+            r#"INSERT INTO cnh_types (code, description) VALUES ($1, $2) RETURNING *"#,
+            &code.into(),
+            &description.into(),
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(cnh_type)
+    }
 }
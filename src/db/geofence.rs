@@ -0,0 +1,383 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDateTime;
+use sqlx::{types::Json, PgConnection};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    dtos::geofence::{SaveGeofenceEventParamsDTO, SaveGeofenceParamsDTO},
+    models::{
+        geofence::{Geofence, GeofenceEvent, GeofenceVertex},
+        route::{Route, RoutePosition},
+    },
+    utils::geofence::detect_transitions,
+};
+
+use super::client::DBClient;
+
+#[async_trait]
+pub trait GeofenceExt {
+    async fn get_geofence(
+        &self,
+        geofence_id: Option<Uuid>,
+    ) -> Result<Option<Geofence>, sqlx::Error>;
+
+    async fn list_geofences(&self, page: u32, limit: usize) -> Result<Vec<Geofence>, sqlx::Error>;
+
+    async fn save_geofence(&self, params: SaveGeofenceParamsDTO) -> Result<Geofence, sqlx::Error>;
+
+    /// Updates the geofence, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
+    async fn update_geofence(
+        &self,
+        geofence_id: Uuid,
+        params: SaveGeofenceParamsDTO,
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Geofence>, sqlx::Error>;
+
+    /// Deletes the geofence, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
+    async fn delete_geofence(
+        &self,
+        geofence_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Geofence>, sqlx::Error>;
+
+    /// Lists geofence events, most recent first, restricted to the given
+    /// route, vehicle and geofence.
+    async fn list_geofence_events(
+        &self,
+        route_id: Option<Uuid>,
+        vehicle_id: Option<Uuid>,
+        geofence_id: Option<Uuid>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<GeofenceEvent>, sqlx::Error>;
+}
+
+#[async_trait]
+impl GeofenceExt for DBClient {
+    #[instrument(skip_all)]
+    async fn get_geofence(
+        &self,
+        geofence_id: Option<Uuid>,
+    ) -> Result<Option<Geofence>, sqlx::Error> {
+        if let Some(geofence_id) = geofence_id {
+            let geofence = sqlx::query_as!(
+                Geofence,
+                r#"
+                SELECT id, name, kind, shape, center_lat, center_long, radius_meters,
+                    vertices AS "vertices: Json<Vec<GeofenceVertex>>", address_id, created_at, updated_at
+                FROM geofences WHERE id = $1
+                "#,
+                geofence_id
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            return Ok(geofence);
+        }
+
+        Ok(None)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_geofences(&self, page: u32, limit: usize) -> Result<Vec<Geofence>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        let geofences = sqlx::query_as!(
+            Geofence,
+            r#"
+            SELECT id, name, kind, shape, center_lat, center_long, radius_meters,
+                vertices AS "vertices: Json<Vec<GeofenceVertex>>", address_id, created_at, updated_at
+            FROM geofences ORDER BY id LIMIT $1 OFFSET $2
+            "#,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(geofences)
+    }
+
+    #[instrument(skip_all)]
+    async fn save_geofence(&self, params: SaveGeofenceParamsDTO) -> Result<Geofence, sqlx::Error> {
+        let [min_lat, max_lat, min_long, max_long] = params.bounds;
+
+        let mut tx = self.begin_audited().await?;
+
+        let geofence = sqlx::query_as!(
+            Geofence,
+            r#"
+            INSERT INTO geofences (name, kind, shape, center_lat, center_long, radius_meters, vertices, min_lat, max_lat, min_long, max_long, address_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING id, name, kind, shape, center_lat, center_long, radius_meters,
+                vertices AS "vertices: Json<Vec<GeofenceVertex>>", address_id, created_at, updated_at
+            "#,
+            params.name,
+            params.kind,
+            params.shape,
+            params.center_lat,
+            params.center_long,
+            params.radius_meters,
+            params.vertices.map(Json) as _,
+            min_lat,
+            max_lat,
+            min_long,
+            max_long,
+            params.address_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(geofence)
+    }
+
+    #[instrument(skip_all)]
+    async fn update_geofence(
+        &self,
+        geofence_id: Uuid,
+        params: SaveGeofenceParamsDTO,
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Geofence>, sqlx::Error> {
+        let [min_lat, max_lat, min_long, max_long] = params.bounds;
+
+        let mut tx = self.begin_audited().await?;
+
+        let geofence = sqlx::query_as!(
+            Geofence,
+            r#"
+            UPDATE geofences SET name = $2, kind = $3, shape = $4, center_lat = $5, center_long = $6, radius_meters = $7,
+                vertices = $8, min_lat = $9, max_lat = $10, min_long = $11, max_long = $12, address_id = $13
+            WHERE id = $1 AND ($14::TIMESTAMP IS NULL OR updated_at = $14)
+            RETURNING id, name, kind, shape, center_lat, center_long, radius_meters,
+                vertices AS "vertices: Json<Vec<GeofenceVertex>>", address_id, created_at, updated_at
+            "#,
+            geofence_id,
+            params.name,
+            params.kind,
+            params.shape,
+            params.center_lat,
+            params.center_long,
+            params.radius_meters,
+            params.vertices.map(Json) as _,
+            min_lat,
+            max_lat,
+            min_long,
+            max_long,
+            params.address_id,
+            expected_updated_at
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(geofence)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_geofence(
+        &self,
+        geofence_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Geofence>, sqlx::Error> {
+        let mut geofence = None;
+
+        let mut tx = self.begin_audited().await?;
+
+        if let Some(geofence_id) = geofence_id {
+            geofence = sqlx::query_as!(
+                Geofence,
+                r#"
+                DELETE FROM geofences WHERE id = $1 AND ($2::TIMESTAMP IS NULL OR updated_at = $2)
+                RETURNING id, name, kind, shape, center_lat, center_long, radius_meters,
+                    vertices AS "vertices: Json<Vec<GeofenceVertex>>", address_id, created_at, updated_at
+                "#,
+                geofence_id,
+                expected_updated_at
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(geofence)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_geofence_events(
+        &self,
+        route_id: Option<Uuid>,
+        vehicle_id: Option<Uuid>,
+        geofence_id: Option<Uuid>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<GeofenceEvent>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        let events = sqlx::query_as!(
+            GeofenceEvent,
+            r#"
+            SELECT * FROM geofence_events
+            WHERE ($1::UUID IS NULL OR route_id = $1)
+                AND ($2::UUID IS NULL OR vehicle_id = $2)
+                AND ($3::UUID IS NULL OR geofence_id = $3)
+            ORDER BY occurred_at DESC, created_at DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            route_id,
+            vehicle_id,
+            geofence_id,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+/// Records the geofences `route` entered or left at `positions`, which must
+/// be ordered by `recorded_at` and not older than the positions evaluated
+/// before, as checked by `save_route_positions`.
+///
+/// Runs on the connection of the transaction saving the positions, which
+/// holds a lock on the route so concurrent batches are evaluated in turn.
+pub async fn save_geofence_events(
+    conn: &mut PgConnection,
+    route: &Route,
+    positions: &[RoutePosition],
+) -> Result<Vec<GeofenceEvent>, sqlx::Error> {
+    let points: Vec<(f64, f64)> = positions
+        .iter()
+        .map(|position| {
+            (
+                position.latitude.to_f64().unwrap_or_default(),
+                position.longitude.to_f64().unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    let Some([min_lat, max_lat, min_long, max_long]) = bounds(&points) else {
+        return Ok(vec![]);
+    };
+
+    // Only the geofences whose box overlaps the batch can be entered.
+    let geofences = sqlx::query_as!(
+        Geofence,
+        r#"
+        SELECT id, name, kind, shape, center_lat, center_long, radius_meters,
+            vertices AS "vertices: Json<Vec<GeofenceVertex>>", address_id, created_at, updated_at
+        FROM geofences
+        WHERE max_lat >= $1 AND min_lat <= $2 AND max_long >= $3 AND min_long <= $4
+        "#,
+        min_lat,
+        max_lat,
+        min_long,
+        max_long
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // The route is inside every geofence whose latest event is an entry.
+    let inside = sqlx::query_scalar!(
+        r#"
+        SELECT geofence_id AS "geofence_id!" FROM (
+            SELECT DISTINCT ON (geofence_id) geofence_id, event_type
+            FROM geofence_events
+            WHERE route_id = $1
+            ORDER BY geofence_id, occurred_at DESC, created_at DESC
+        ) AS latest
+        WHERE event_type = 'enter'
+        "#,
+        route.id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let transitions = detect_transitions(
+        &geofences,
+        inside.into_iter().collect::<HashSet<_>>(),
+        &points,
+    );
+
+    if transitions.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let events: Vec<SaveGeofenceEventParamsDTO> = transitions
+        .iter()
+        .map(|transition| {
+            let position = &positions[transition.position];
+
+            SaveGeofenceEventParamsDTO {
+                geofence_id: transition.geofence_id,
+                event_type: transition.event_type.to_string(),
+                latitude: position.latitude.to_owned(),
+                longitude: position.longitude.to_owned(),
+                occurred_at: position.recorded_at,
+            }
+        })
+        .collect();
+
+    let mut geofence_ids = Vec::with_capacity(events.len());
+    let mut event_types = Vec::with_capacity(events.len());
+    let mut latitudes = Vec::with_capacity(events.len());
+    let mut longitudes = Vec::with_capacity(events.len());
+    let mut occurred_ats = Vec::with_capacity(events.len());
+
+    for event in events {
+        geofence_ids.push(event.geofence_id);
+        event_types.push(event.event_type);
+        latitudes.push(event.latitude);
+        longitudes.push(event.longitude);
+        occurred_ats.push(event.occurred_at);
+    }
+
+    let saved = sqlx::query_as!(
+        GeofenceEvent,
+        r#"
+        INSERT INTO geofence_events (route_id, vehicle_id, geofence_id, event_type, latitude, longitude, occurred_at)
+        SELECT $1, $2, * FROM UNNEST($3::UUID[], $4::VARCHAR[], $5::NUMERIC[], $6::NUMERIC[], $7::TIMESTAMP[])
+        RETURNING *
+        "#,
+        route.id,
+        route.vehicle_id,
+        &geofence_ids,
+        &event_types,
+        &latitudes,
+        &longitudes,
+        &occurred_ats
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(saved)
+}
+
+/// The `[min_lat, max_lat, min_long, max_long]` box around `points`, or
+/// `None` when there are none.
+fn bounds(points: &[(f64, f64)]) -> Option<[f64; 4]> {
+    let (&(latitude, longitude), rest) = points.split_first()?;
+
+    Some(rest.iter().fold(
+        [latitude, latitude, longitude, longitude],
+        |[min_lat, max_lat, min_long, max_long], &(latitude, longitude)| {
+            [
+                min_lat.min(latitude),
+                max_lat.max(latitude),
+                min_long.min(longitude),
+                max_long.max(longitude),
+            ]
+        },
+    ))
+}
@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use tracing::instrument;
+
+use super::client::{DBClient, MIGRATOR};
+
+#[async_trait]
+pub trait HealthExt {
+    /// Runs a trivial query to check that the database answers.
+    async fn ping(&self) -> Result<(), sqlx::Error>;
+
+    /// Lists the versions of the migrations applied successfully.
+    async fn list_applied_migrations(&self) -> Result<Vec<i64>, sqlx::Error>;
+
+    /// Counts the embedded migrations that were not applied successfully yet.
+    async fn count_pending_migrations(&self) -> Result<usize, sqlx::Error>;
+}
+
+#[async_trait]
+impl HealthExt for DBClient {
+    #[instrument(skip_all)]
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_applied_migrations(&self) -> Result<Vec<i64>, sqlx::Error> {
+        // `_sqlx_migrations` is managed by sqlx itself, so it's queried at
+        // runtime instead of through the checked macros.
+        let applied = sqlx::query_scalar(
+            "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(applied)
+    }
+
+    #[instrument(skip_all)]
+    async fn count_pending_migrations(&self) -> Result<usize, sqlx::Error> {
+        let applied = self.list_applied_migrations().await?;
+
+        let pending = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .filter(|migration| !applied.contains(&migration.version))
+            .count();
+
+        Ok(pending)
+    }
+}
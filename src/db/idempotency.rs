@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use tracing::instrument;
+
+use crate::models::idempotency::IdempotencyKey;
+
+use super::client::DBClient;
+
+#[async_trait]
+pub trait IdempotencyKeyExt {
+    async fn get_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+
+    /// Reserves the key for the current request. Returns `None` when the key
+    /// was already taken by a previous (or concurrent) request.
+    ///
+    /// A key taken more than `lease_secs` ago that still has no response is
+    /// taken again, as long as the payload is the same, so a request that
+    /// never finished doesn't block its retries forever.
+    async fn lock_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+        request_hash: &str,
+        lease_secs: f64,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+
+    async fn save_idempotency_response(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+        status_code: i16,
+        content_type: Option<String>,
+        response_body: &[u8],
+    ) -> Result<IdempotencyKey, sqlx::Error>;
+
+    async fn delete_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+
+    async fn purge_idempotency_keys(&self, retention_hours: i32) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl IdempotencyKeyExt for DBClient {
+    #[instrument(skip_all)]
+    async fn get_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        let key = sqlx::query_as!(
+            IdempotencyKey,
+            r#"SELECT * FROM idempotency_keys WHERE idempotency_key = $1 AND method = $2 AND path = $3"#,
+            idempotency_key,
+            method,
+            path
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    #[instrument(skip_all)]
+    async fn lock_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+        request_hash: &str,
+        lease_secs: f64,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        let key = sqlx::query_as!(
+            IdempotencyKey,
+            r#"
+            INSERT INTO idempotency_keys (idempotency_key, method, path, request_hash)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (idempotency_key, method, path) DO UPDATE SET locked_at = NOW()
+            WHERE idempotency_keys.status_code IS NULL
+                AND idempotency_keys.request_hash = EXCLUDED.request_hash
+                AND idempotency_keys.locked_at < NOW() - make_interval(secs => $5)
+            RETURNING *"#,
+            idempotency_key,
+            method,
+            path,
+            request_hash,
+            lease_secs
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    #[instrument(skip_all)]
+    async fn save_idempotency_response(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+        status_code: i16,
+        content_type: Option<String>,
+        response_body: &[u8],
+    ) -> Result<IdempotencyKey, sqlx::Error> {
+        let key = sqlx::query_as!(
+            IdempotencyKey,
+            r#"
+            UPDATE idempotency_keys SET status_code = $4, content_type = $5, response_body = $6
+            WHERE idempotency_key = $1 AND method = $2 AND path = $3
+            RETURNING *"#,
+            idempotency_key,
+            method,
+            path,
+            status_code,
+            content_type,
+            response_body
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_idempotency_key(
+        &self,
+        idempotency_key: &str,
+        method: &str,
+        path: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        let key = sqlx::query_as!(
+            IdempotencyKey,
+            r#"DELETE FROM idempotency_keys WHERE idempotency_key = $1 AND method = $2 AND path = $3 RETURNING *"#,
+            idempotency_key,
+            method,
+            path
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
+    #[instrument(skip_all)]
+    async fn purge_idempotency_keys(&self, retention_hours: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"DELETE FROM idempotency_keys WHERE created_at < NOW() - make_interval(hours => $1)"#,
+            retention_hours
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,9 +1,18 @@
 pub mod address;
+pub mod admin;
+pub mod audit;
 pub mod city;
 pub mod client;
 pub mod collaborator;
 pub mod country;
 pub mod driver;
+pub mod geofence;
+pub mod health;
+pub mod idempotency;
 pub mod route;
+pub mod spatial;
 pub mod state;
+pub mod street;
 pub mod vehicle;
+pub mod webhook;
+pub mod zip_code;
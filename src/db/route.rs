@@ -1,41 +1,89 @@
 use async_trait::async_trait;
 use bigdecimal::{BigDecimal, FromPrimitive};
+use chrono::NaiveDateTime;
 use geoutils::Location;
-use sqlx::Error;
+use sqlx::{Error, Postgres, QueryBuilder};
+use tracing::{instrument, warn};
 use uuid::Uuid;
 
 use crate::{
-    dtos::route::SaveRouteParamsDTO,
-    models::route::{Route, RouteStatus},
+    dtos::route::{SaveRouteParamsDTO, SaveRoutePositionParamsDTO},
+    models::{
+        geofence::GeofenceEvent,
+        route::{Route, RouteEvent, RoutePosition, RouteStatus},
+    },
+    utils::spatial::SpatialFilter,
 };
 
-use super::client::DBClient;
+use super::{
+    client::DBClient,
+    geofence::save_geofence_events,
+    spatial::{push_spatial_filter, to_decimal, PointColumns},
+};
+
+/// The columns of `Route`, listed instead of `*` so the geography columns
+/// added with PostGIS are left out.
+const ROUTE_COLUMNS: &str = "id, started_at, ended_at, total_distance, created_at, updated_at, initial_lat, initial_long, final_lat, final_long, driver_id, status_id, initial_address_id, final_address_id, vehicle_id";
+
+const ROUTE_INITIAL_LOCATION: PointColumns = PointColumns {
+    location: "initial_location",
+    latitude: "initial_lat",
+    longitude: "initial_long",
+};
+
+const ROUTE_FINAL_LOCATION: PointColumns = PointColumns {
+    location: "final_location",
+    latitude: "final_lat",
+    longitude: "final_long",
+};
 
 #[async_trait]
 pub trait RouteExt {
     async fn get_route(&self, route_id: Option<Uuid>) -> Result<Option<Route>, sqlx::Error>;
 
-    async fn list_routes(&self, page: u32, limit: usize) -> Result<Vec<Route>, sqlx::Error>;
+    /// Lists a page of routes, restricted to those starting or ending inside
+    /// `area` when one is given, and to those after the id `after` when
+    /// given.
+    async fn list_routes(
+        &self,
+        page: u32,
+        limit: usize,
+        area: Option<SpatialFilter>,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Route>, sqlx::Error>;
 
     async fn save_route<B: Into<BigDecimal> + Send, S: Into<String> + Send>(
         &self,
         params: SaveRouteParamsDTO<B, S>,
     ) -> Result<Route, sqlx::Error>;
 
+    /// Updates the route, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn update_route<B: Into<BigDecimal> + Send, S: Into<String> + Send>(
         &self,
         route_id: Option<Uuid>,
         params: SaveRouteParamsDTO<B, S>,
-    ) -> Result<Route, sqlx::Error>;
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Route>, sqlx::Error>;
 
-    async fn delete_route(&self, route_id: Option<Uuid>) -> Result<Option<Route>, sqlx::Error>;
+    /// Deletes the route, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
+    async fn delete_route(
+        &self,
+        route_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Route>, sqlx::Error>;
+
+    /// Counts routes that have not ended yet.
+    async fn count_active_routes(&self) -> Result<i64, sqlx::Error>;
 }
 
 #[async_trait]
 impl RouteExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_route(&self, route_id: Option<Uuid>) -> Result<Option<Route>, sqlx::Error> {
         if let Some(route_id) = route_id {
-            let vehicle = sqlx::query_as!(Route, r#"SELECT * FROM routes WHERE id = $1"#, route_id)
+            let vehicle = sqlx::query_as!(Route, r#"SELECT id, started_at, ended_at, total_distance, created_at, updated_at, initial_lat, initial_long, final_lat, final_long, driver_id, status_id, initial_address_id, final_address_id, vehicle_id FROM routes WHERE id = $1"#, route_id)
                 .fetch_optional(&self.pool)
                 .await?;
             return Ok(vehicle);
@@ -44,14 +92,47 @@ impl RouteExt for DBClient {
         Ok(None)
     }
 
-    async fn list_routes(&self, page: u32, limit: usize) -> Result<Vec<Route>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn list_routes(
+        &self,
+        page: u32,
+        limit: usize,
+        area: Option<SpatialFilter>,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Route>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
+        if let Some(area) = area {
+            let mut builder: QueryBuilder<Postgres> =
+                QueryBuilder::new(format!("SELECT {ROUTE_COLUMNS} FROM routes WHERE (("));
+
+            push_spatial_filter(&mut builder, &area, &ROUTE_INITIAL_LOCATION, self.postgis);
+            builder.push(") OR (");
+            push_spatial_filter(&mut builder, &area, &ROUTE_FINAL_LOCATION, self.postgis);
+            builder.push("))");
+
+            if let Some(after) = after {
+                builder.push(" AND id > ").push_bind(after);
+            }
+
+            builder
+                .push(" ORDER BY id LIMIT ")
+                .push_bind(limit as i64)
+                .push(" OFFSET ")
+                .push_bind(offset as i64);
+
+            return builder
+                .build_query_as::<Route>()
+                .fetch_all(&self.pool)
+                .await;
+        }
+
         let routes = sqlx::query_as!(
             Route,
-            r#"SELECT * FROM routes LIMIT $1 OFFSET $2"#,
+            r#"SELECT id, started_at, ended_at, total_distance, created_at, updated_at, initial_lat, initial_long, final_lat, final_long, driver_id, status_id, initial_address_id, final_address_id, vehicle_id FROM routes WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
@@ -59,6 +140,7 @@ impl RouteExt for DBClient {
         Ok(routes)
     }
 
+    #[instrument(skip_all)]
     async fn save_route<B: Into<BigDecimal> + Send, S: Into<String> + Send>(
         &self,
         params: SaveRouteParamsDTO<B, S>,
@@ -101,7 +183,7 @@ impl RouteExt for DBClient {
         let float_value_a: f32 = match a.to_string().parse() {
             Ok(value) => value,
             Err(e) => {
-                println!("Error parsing string to f32: {e}");
+                warn!("Error parsing string to f32: {e}");
                 0.0
             }
         };
@@ -111,7 +193,7 @@ impl RouteExt for DBClient {
         let float_value_b: f32 = match b.to_string().parse() {
             Ok(value) => value,
             Err(e) => {
-                println!("Error parsing string to f32: {e}");
+                warn!("Error parsing string to f32: {e}");
                 0.0
             }
         };
@@ -121,7 +203,7 @@ impl RouteExt for DBClient {
         let float_value_c: f32 = match c.to_string().parse() {
             Ok(value) => value,
             Err(e) => {
-                println!("Error parsing string to f32: {e}");
+                warn!("Error parsing string to f32: {e}");
                 0.0
             }
         };
@@ -131,7 +213,7 @@ impl RouteExt for DBClient {
         let float_value_d: f32 = match d.to_string().parse() {
             Ok(value) => value,
             Err(e) => {
-                println!("Error parsing string to f32: {e}");
+                warn!("Error parsing string to f32: {e}");
                 0.0
             }
         };
@@ -141,12 +223,14 @@ impl RouteExt for DBClient {
 
         let distance = l1.haversine_distance_to(&l2);
 
+        let mut tx = self.begin_audited().await?;
+
         let route = sqlx::query_as!(
             Route,
             r#"
             INSERT INTO routes (initial_lat, initial_long, final_lat, final_long, initial_address_id, final_address_id, vehicle_id, status_id, driver_id, total_distance) 
             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) 
-            RETURNING *"#,
+            RETURNING id, started_at, ended_at, total_distance, created_at, updated_at, initial_lat, initial_long, final_lat, final_long, driver_id, status_id, initial_address_id, final_address_id, vehicle_id"#,
             a, b, c, d,
             initial_address_id,
             final_address_id,
@@ -155,17 +239,21 @@ impl RouteExt for DBClient {
             driver_id,
             BigDecimal::from_f64(distance.meters())
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(route)
     }
 
+    #[instrument(skip_all)]
     async fn update_route<B: Into<BigDecimal> + Send, S: Into<String> + Send>(
         &self,
         route_id: Option<Uuid>,
         params: SaveRouteParamsDTO<B, S>,
-    ) -> Result<Route, sqlx::Error> {
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Route>, sqlx::Error> {
         let SaveRouteParamsDTO {
             initial_lat,
             initial_long,
@@ -199,10 +287,12 @@ impl RouteExt for DBClient {
             .transpose()
             .map_err(|e| Error::Protocol(format!("Failed to parse driver_id: {e}")))?;
 
+        let mut tx = self.begin_audited().await?;
+
         let route = sqlx::query_as!(
             Route,
             r#"
-            UPDATE routes SET initial_lat = $2, initial_long = $3, final_lat = $4, final_long = $5, initial_address_id = $6, final_address_id = $7, vehicle_id = $8, status_id = $9, driver_id = $10 WHERE id = $1 RETURNING *"#,
+            UPDATE routes SET initial_lat = $2, initial_long = $3, final_lat = $4, final_long = $5, initial_address_id = $6, final_address_id = $7, vehicle_id = $8, status_id = $9, driver_id = $10 WHERE id = $1 AND ($11::TIMESTAMP IS NULL OR updated_at = $11) RETURNING id, started_at, ended_at, total_distance, created_at, updated_at, initial_lat, initial_long, final_lat, final_long, driver_id, status_id, initial_address_id, final_address_id, vehicle_id"#,
             &route_id.unwrap(),
             &initial_lat.into(),
             &initial_long.into(),
@@ -213,28 +303,52 @@ impl RouteExt for DBClient {
             &vehicle_id,
             &status_id,
             driver_id,
+            expected_updated_at,
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(route)
     }
 
-    async fn delete_route(&self, route_id: Option<Uuid>) -> Result<Option<Route>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn delete_route(
+        &self,
+        route_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Route>, sqlx::Error> {
         let mut route = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(route_id) = route_id {
             route = sqlx::query_as!(
                 Route,
-                r#"DELETE FROM routes WHERE id = $1 RETURNING *"#,
-                route_id
+                r#"DELETE FROM routes WHERE id = $1 AND ($2::TIMESTAMP IS NULL OR updated_at = $2) RETURNING id, started_at, ended_at, total_distance, created_at, updated_at, initial_lat, initial_long, final_lat, final_long, driver_id, status_id, initial_address_id, final_address_id, vehicle_id"#,
+                route_id,
+                expected_updated_at
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(route)
     }
+
+    #[instrument(skip_all)]
+    async fn count_active_routes(&self) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM routes WHERE ended_at IS NULL"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
 }
 
 #[async_trait]
@@ -245,10 +359,13 @@ pub trait RouteStatusExt {
         code: Option<String>,
     ) -> Result<Option<RouteStatus>, sqlx::Error>;
 
+    /// Lists a page of route statuses, restricted to those after the id `after`
+    /// when given.
     async fn list_route_status(
         &self,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<RouteStatus>, sqlx::Error>;
 
     async fn save_route_status<T: Into<String> + Send>(
@@ -265,6 +382,7 @@ pub trait RouteStatusExt {
 
 #[async_trait]
 impl RouteStatusExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_route_status(
         &self,
         status_id: Option<Uuid>,
@@ -293,18 +411,21 @@ impl RouteStatusExt for DBClient {
         Ok(status)
     }
 
+    #[instrument(skip_all)]
     async fn list_route_status(
         &self,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<RouteStatus>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         let statuses = sqlx::query_as!(
             RouteStatus,
-            r#"SELECT * FROM route_status LIMIT $1 OFFSET $2"#,
+            r#"SELECT * FROM route_status WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
@@ -312,39 +433,407 @@ impl RouteStatusExt for DBClient {
         Ok(statuses)
     }
 
+    #[instrument(skip_all)]
     async fn save_route_status<T: Into<String> + Send>(
         &self,
         code: Option<T>,
         description: T,
     ) -> Result<RouteStatus, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
         let state = sqlx::query_as!(
             RouteStatus,
             r#"INSERT INTO route_status (code, description) VALUES ($1, $2) RETURNING *"#,
             &code.map(Into::into).unwrap_or_default(),
             &description.into(),
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(state)
     }
 
+    #[instrument(skip_all)]
     async fn delete_route_status(
         &self,
         status_id: Option<Uuid>,
     ) -> Result<Option<RouteStatus>, sqlx::Error> {
         let mut status = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(status_id) = status_id {
             status = sqlx::query_as!(
                 RouteStatus,
                 r#"DELETE FROM route_status WHERE id = $1 RETURNING *"#,
                 status_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(status)
     }
 }
+
+#[async_trait]
+pub trait RoutePositionExt {
+    /// Lists the positions of a route, oldest first.
+    async fn list_route_positions(
+        &self,
+        route_id: Uuid,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<RoutePosition>, sqlx::Error>;
+
+    /// Lists the positions of a route recorded within `window_minutes` of its
+    /// latest one, at most `limit` of the newest, oldest first.
+    async fn list_recent_route_positions(
+        &self,
+        route_id: Uuid,
+        window_minutes: i32,
+        limit: usize,
+    ) -> Result<Vec<RoutePosition>, sqlx::Error>;
+
+    /// Saves the positions of a route in the order they were recorded, and
+    /// the geofence events they trigger. Returns `None`, saving nothing, when
+    /// a position is older than the latest one saved for the route, since the
+    /// geofences are evaluated from the route's latest state.
+    async fn save_route_positions(
+        &self,
+        route: &Route,
+        positions: Vec<SaveRoutePositionParamsDTO>,
+    ) -> Result<Option<(Vec<RoutePosition>, Vec<GeofenceEvent>)>, sqlx::Error>;
+}
+
+#[async_trait]
+impl RoutePositionExt for DBClient {
+    #[instrument(skip_all)]
+    async fn list_route_positions(
+        &self,
+        route_id: Uuid,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<RoutePosition>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        let positions = sqlx::query_as!(
+            RoutePosition,
+            r#"
+            SELECT * FROM route_positions WHERE route_id = $1
+            ORDER BY recorded_at, created_at
+            LIMIT $2 OFFSET $3
+            "#,
+            route_id,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(positions)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_recent_route_positions(
+        &self,
+        route_id: Uuid,
+        window_minutes: i32,
+        limit: usize,
+    ) -> Result<Vec<RoutePosition>, sqlx::Error> {
+        let mut positions = sqlx::query_as!(
+            RoutePosition,
+            r#"
+            SELECT * FROM route_positions
+            WHERE route_id = $1
+                AND recorded_at >= (SELECT MAX(recorded_at) FROM route_positions WHERE route_id = $1) - make_interval(mins => $2)
+            ORDER BY recorded_at DESC, created_at DESC
+            LIMIT $3
+            "#,
+            route_id,
+            window_minutes,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        positions.reverse();
+
+        Ok(positions)
+    }
+
+    #[instrument(skip_all)]
+    async fn save_route_positions(
+        &self,
+        route: &Route,
+        mut positions: Vec<SaveRoutePositionParamsDTO>,
+    ) -> Result<Option<(Vec<RoutePosition>, Vec<GeofenceEvent>)>, sqlx::Error> {
+        positions.sort_by_key(|position| position.recorded_at);
+
+        let mut latitudes = Vec::with_capacity(positions.len());
+        let mut longitudes = Vec::with_capacity(positions.len());
+        let mut recorded_ats = Vec::with_capacity(positions.len());
+
+        for position in positions {
+            latitudes.push(to_decimal(position.latitude));
+            longitudes.push(to_decimal(position.longitude));
+            recorded_ats.push(position.recorded_at);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        // Batches of the same route are evaluated one at a time, otherwise
+        // both could report entering the same geofence.
+        sqlx::query!(
+            r#"SELECT id FROM routes WHERE id = $1 FOR UPDATE"#,
+            route.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let latest_recorded_at = sqlx::query_scalar!(
+            r#"SELECT MAX(recorded_at) FROM route_positions WHERE route_id = $1"#,
+            route.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if let (Some(latest), Some(earliest)) = (latest_recorded_at, recorded_ats.first()) {
+            if *earliest < latest {
+                return Ok(None);
+            }
+        }
+
+        let saved = sqlx::query_as!(
+            RoutePosition,
+            r#"
+            INSERT INTO route_positions (route_id, latitude, longitude, recorded_at)
+            SELECT $1, * FROM UNNEST($2::NUMERIC[], $3::NUMERIC[], $4::TIMESTAMP[])
+            RETURNING *
+            "#,
+            route.id,
+            &latitudes,
+            &longitudes,
+            &recorded_ats
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let events = save_geofence_events(&mut tx, route, &saved).await?;
+
+        tx.commit().await?;
+
+        Ok(Some((saved, events)))
+    }
+}
+
+#[async_trait]
+pub trait RouteEventExt {
+    /// The ID of the latest route event, or 0 when there is none.
+    async fn latest_route_event_id(&self) -> Result<i64, sqlx::Error>;
+
+    /// Lists the events after `after_id`, oldest first, of the routes in
+    /// `route_ids` or of the vehicles in `vehicle_ids`. Without any ID, the
+    /// events of every route are listed.
+    async fn list_route_events(
+        &self,
+        after_id: i64,
+        route_ids: &[Uuid],
+        vehicle_ids: &[Uuid],
+        limit: usize,
+    ) -> Result<Vec<RouteEvent>, sqlx::Error>;
+
+    /// Deletes the events created before `before`. Returns how many were
+    /// deleted.
+    async fn delete_route_events(&self, before: NaiveDateTime) -> Result<u64, sqlx::Error>;
+
+    /// Moves up to `limit` committed events from `route_events_pending` to
+    /// `route_events`, oldest first. A single instance moves them at a time,
+    /// so the IDs follow the order the events were committed in and a
+    /// stream resuming after an ID can't miss a later one. Returns how many
+    /// were moved.
+    async fn sequence_route_events(&self, limit: usize) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl RouteEventExt for DBClient {
+    #[instrument(skip_all)]
+    async fn latest_route_event_id(&self) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query_scalar!(r#"SELECT COALESCE(MAX(id), 0) AS "id!" FROM route_events"#)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_route_events(
+        &self,
+        after_id: i64,
+        route_ids: &[Uuid],
+        vehicle_ids: &[Uuid],
+        limit: usize,
+    ) -> Result<Vec<RouteEvent>, sqlx::Error> {
+        let events = sqlx::query_as!(
+            RouteEvent,
+            r#"
+            SELECT * FROM route_events
+            WHERE id > $1
+                AND (
+                    (CARDINALITY($2::UUID[]) = 0 AND CARDINALITY($3::UUID[]) = 0)
+                    OR route_id = ANY($2)
+                    OR vehicle_id = ANY($3)
+                )
+            ORDER BY id
+            LIMIT $4
+            "#,
+            after_id,
+            route_ids,
+            vehicle_ids,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_route_events(&self, before: NaiveDateTime) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(r#"DELETE FROM route_events WHERE created_at < $1"#, before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip_all)]
+    async fn sequence_route_events(&self, limit: usize) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"SELECT 1 AS "locked!" FROM pg_advisory_xact_lock(hashtext('route_events'))"#
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            WITH moved AS (
+                DELETE FROM route_events_pending
+                WHERE id IN (SELECT id FROM route_events_pending ORDER BY id LIMIT $1)
+                RETURNING *
+            )
+            INSERT INTO route_events (route_id, vehicle_id, event_type, data, created_at)
+            SELECT route_id, vehicle_id, event_type, data, created_at FROM moved ORDER BY id
+            "#,
+            limit as i64
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    const INSERT_PENDING_EVENT: &str = r#"
+        INSERT INTO route_events_pending (route_id, vehicle_id, event_type, data)
+        VALUES ($1, $2, 'status', jsonb_build_object('test', $3::TEXT))
+    "#;
+
+    async fn marked_events(db_client: &DBClient, after_id: i64, marker: &str) -> Vec<RouteEvent> {
+        let mut events = vec![];
+        let mut after_id = after_id;
+
+        loop {
+            let page = db_client
+                .list_route_events(after_id, &[], &[], 500)
+                .await
+                .unwrap();
+
+            let Some(last) = page.last() else {
+                return events;
+            };
+
+            after_id = last.id;
+            events.extend(
+                page.into_iter()
+                    .filter(|event| event.data["test"].as_str() == Some(marker)),
+            );
+        }
+    }
+
+    /// An event committed after another, though inserted before, is still
+    /// listed after the ID of the other one.
+    #[actix_web::test]
+    #[ignore = "needs DATABASE_URL and a route"]
+    async fn sequences_events_in_commit_order() {
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let pool = PgPoolOptions::new()
+            .max_connections(3)
+            .connect(&url)
+            .await
+            .unwrap();
+        let db_client = DBClient::new(pool.clone());
+
+        let (route_id, vehicle_id) =
+            sqlx::query_as::<_, (Uuid, Uuid)>("SELECT id, vehicle_id FROM routes LIMIT 1")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        let marker = Uuid::new_v4().to_string();
+        let start = db_client.latest_route_event_id().await.unwrap();
+
+        let mut first = pool.begin().await.unwrap();
+        sqlx::query(INSERT_PENDING_EVENT)
+            .bind(route_id)
+            .bind(vehicle_id)
+            .bind(&marker)
+            .execute(&mut *first)
+            .await
+            .unwrap();
+
+        let mut second = pool.begin().await.unwrap();
+        sqlx::query(INSERT_PENDING_EVENT)
+            .bind(route_id)
+            .bind(vehicle_id)
+            .bind(&marker)
+            .execute(&mut *second)
+            .await
+            .unwrap();
+        second.commit().await.unwrap();
+
+        db_client.sequence_route_events(1000).await.unwrap();
+
+        let seen = marked_events(&db_client, start, &marker).await;
+        assert_eq!(seen.len(), 1);
+        let cursor = seen[0].id;
+
+        first.commit().await.unwrap();
+        db_client.sequence_route_events(1000).await.unwrap();
+
+        let resumed = marked_events(&db_client, cursor, &marker).await;
+
+        sqlx::query("DELETE FROM route_events WHERE data->>'test' = $1")
+            .bind(&marker)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(resumed.len(), 1);
+        assert!(resumed[0].id > cursor);
+    }
+}
@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, FromPrimitive};
+use sqlx::{Postgres, QueryBuilder};
+use tracing::instrument;
+
+use crate::utils::spatial::{degree_deltas, SpatialFilter};
+
+use super::client::DBClient;
+
+/// The mean radius of the Earth, in meters, used by the haversine formula.
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// The columns holding a point: its numeric coordinates, and the geography
+/// column generated from them when PostGIS is installed.
+pub struct PointColumns {
+    pub location: &'static str,
+    pub latitude: &'static str,
+    pub longitude: &'static str,
+}
+
+#[async_trait]
+pub trait SpatialExt {
+    /// Whether the migrations created the PostGIS geography columns, which
+    /// only happens when the extension is available.
+    async fn has_spatial_columns(&self) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+impl SpatialExt for DBClient {
+    #[instrument(skip_all)]
+    async fn has_spatial_columns(&self) -> Result<bool, sqlx::Error> {
+        let found = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) = 3 AS "found!" FROM information_schema.columns
+            WHERE table_schema = current_schema()
+                AND (table_name, column_name) IN (
+                    ('addresses', 'location'),
+                    ('routes', 'initial_location'),
+                    ('routes', 'final_location')
+                )
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(found)
+    }
+}
+
+/// The latitude and longitude bounds of a circle of `meters` around a point.
+pub fn radius_bounds(latitude: f64, longitude: f64, meters: f64) -> [BigDecimal; 4] {
+    let (latitude_delta, longitude_delta) = degree_deltas(latitude, meters);
+
+    [
+        latitude - latitude_delta,
+        latitude + latitude_delta,
+        longitude - longitude_delta,
+        longitude + longitude_delta,
+    ]
+    .map(to_decimal)
+}
+
+/// Pushes a condition matching the points of `columns` inside `filter`.
+///
+/// With PostGIS the condition goes through the GiST index of the geography
+/// column. Otherwise the numeric coordinates are bounded first, so their
+/// B-tree index discards most rows, and the radius is checked with the
+/// haversine formula.
+pub fn push_spatial_filter(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    filter: &SpatialFilter,
+    columns: &PointColumns,
+    postgis: bool,
+) {
+    let PointColumns {
+        location,
+        latitude: latitude_column,
+        longitude: longitude_column,
+    } = columns;
+
+    match *filter {
+        SpatialFilter::Radius {
+            latitude,
+            longitude,
+            meters,
+        } if postgis => {
+            builder
+                .push(format!("ST_DWithin({location}, ST_SetSRID(ST_MakePoint("))
+                .push_bind(longitude)
+                .push(", ")
+                .push_bind(latitude)
+                .push("), 4326)::GEOGRAPHY, ")
+                .push_bind(meters)
+                .push(")");
+        }
+        SpatialFilter::Radius {
+            latitude,
+            longitude,
+            meters,
+        } => {
+            let [min_latitude, max_latitude, min_longitude, max_longitude] =
+                radius_bounds(latitude, longitude, meters);
+
+            push_between(
+                builder,
+                columns,
+                [min_latitude, max_latitude, min_longitude, max_longitude],
+            );
+
+            builder
+                .push(" AND 2 * ")
+                .push_bind(EARTH_RADIUS_METERS)
+                .push(format!(
+                    " * ASIN(SQRT(POWER(SIN(RADIANS({latitude_column}::FLOAT8 - "
+                ))
+                .push_bind(latitude)
+                .push(") / 2), 2) + COS(RADIANS(")
+                .push_bind(latitude)
+                .push(format!(
+                    ")) * COS(RADIANS({latitude_column}::FLOAT8)) * POWER(SIN(RADIANS({longitude_column}::FLOAT8 - "
+                ))
+                .push_bind(longitude)
+                .push(") / 2), 2))) <= ")
+                .push_bind(meters);
+        }
+        SpatialFilter::BoundingBox {
+            min_longitude,
+            min_latitude,
+            max_longitude,
+            max_latitude,
+        } => {
+            if postgis {
+                // The envelope's edges are great circles once cast, so the
+                // index only narrows the rows and the bounds below decide.
+                builder
+                    .push(format!("{location} && ST_MakeEnvelope("))
+                    .push_bind(min_longitude)
+                    .push(", ")
+                    .push_bind(min_latitude)
+                    .push(", ")
+                    .push_bind(max_longitude)
+                    .push(", ")
+                    .push_bind(max_latitude)
+                    .push(", 4326)::GEOGRAPHY AND ");
+            }
+
+            push_between(
+                builder,
+                columns,
+                [min_latitude, max_latitude, min_longitude, max_longitude].map(to_decimal),
+            );
+        }
+    }
+}
+
+/// Bounds the numeric coordinates, binding decimals so the comparison doesn't
+/// cast the columns and can use their index.
+fn push_between(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    columns: &PointColumns,
+    [min_latitude, max_latitude, min_longitude, max_longitude]: [BigDecimal; 4],
+) {
+    builder
+        .push(format!("{} BETWEEN ", columns.latitude))
+        .push_bind(min_latitude)
+        .push(" AND ")
+        .push_bind(max_latitude)
+        .push(format!(" AND {} BETWEEN ", columns.longitude))
+        .push_bind(min_longitude)
+        .push(" AND ")
+        .push_bind(max_longitude);
+}
+
+/// Converts a coordinate computed or read as `f64` into the `NUMERIC` stored.
+pub fn to_decimal(value: f64) -> BigDecimal {
+    BigDecimal::from_f64(value).unwrap_or_default()
+}
@@ -1,7 +1,9 @@
 use async_trait::async_trait;
+use sqlx::PgConnection;
+use tracing::instrument;
 use uuid::Uuid;
 
-use crate::models::state::State;
+use crate::{dtos::state::RegisterStateDTO, models::state::State, utils::string::escape_like};
 
 use super::client::DBClient;
 
@@ -14,13 +16,27 @@ pub trait StateExt {
         code: Option<&str>,
     ) -> Result<Option<State>, sqlx::Error>;
 
-    async fn list_states(&self, page: u32, limit: usize) -> Result<Vec<State>, sqlx::Error>;
+    /// Lists a page of states ordered by name, restricted to the country
+    /// `country_id`, to the names starting with `name`, regardless of case
+    /// and accents, and to those after the `(name, id)` key `after`, when
+    /// given.
+    async fn list_states(
+        &self,
+        page: u32,
+        limit: usize,
+        name: Option<&str>,
+        country_id: Option<Uuid>,
+        after: Option<(String, Uuid)>,
+    ) -> Result<Vec<State>, sqlx::Error>;
 
+    /// Lists a page of the states of a country, restricted to those after
+    /// the id `after` when given.
     async fn list_states_by_country(
         &self,
         country_id: Option<Uuid>,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<State>, sqlx::Error>;
 
     async fn save_state<T: Into<String> + Send>(
@@ -35,6 +51,7 @@ pub trait StateExt {
 
 #[async_trait]
 impl StateExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_state(
         &self,
         state_id: Option<Uuid>,
@@ -60,14 +77,34 @@ impl StateExt for DBClient {
         Ok(state)
     }
 
-    async fn list_states(&self, page: u32, limit: usize) -> Result<Vec<State>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn list_states(
+        &self,
+        page: u32,
+        limit: usize,
+        name: Option<&str>,
+        country_id: Option<Uuid>,
+        after: Option<(String, Uuid)>,
+    ) -> Result<Vec<State>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
+        let (after_name, after_id) = after.unzip();
 
         let states = sqlx::query_as!(
             State,
-            r#"SELECT * FROM states LIMIT $1 OFFSET $2"#,
+            r#"
+            SELECT * FROM states
+            WHERE ($3::TEXT IS NULL OR immutable_unaccent(LOWER(name)) LIKE immutable_unaccent(LOWER($3)) || '%')
+                AND ($4::UUID IS NULL OR country_id = $4)
+                AND ($5::TEXT IS NULL OR (name, id) > ($5, $6))
+            ORDER BY name, id
+            LIMIT $1 OFFSET $2
+            "#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            name.map(escape_like),
+            country_id,
+            after_name,
+            after_id
         )
         .fetch_all(&self.pool)
         .await?;
@@ -75,21 +112,24 @@ impl StateExt for DBClient {
         Ok(states)
     }
 
+    #[instrument(skip_all)]
     async fn list_states_by_country(
         &self,
         country_id: Option<Uuid>,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<State>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         if let Some(country_id) = country_id {
             let states = sqlx::query_as!(
                 State,
-                r#"SELECT * FROM states WHERE country_id = $1 LIMIT $2 OFFSET $3"#,
+                r#"SELECT * FROM states WHERE country_id = $1 AND ($4::UUID IS NULL OR id > $4) ORDER BY id LIMIT $2 OFFSET $3"#,
                 country_id,
                 limit as i64,
-                offset as i64
+                offset as i64,
+                after
             )
             .fetch_all(&self.pool)
             .await?;
@@ -100,12 +140,15 @@ impl StateExt for DBClient {
         Ok(vec![])
     }
 
+    #[instrument(skip_all)]
     async fn save_state<T: Into<String> + Send>(
         &self,
         name: T,
         code: T,
         country_id: T,
     ) -> Result<State, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
         let state = sqlx::query_as!(
             State,
             r#"INSERT INTO states (name, code, country_id) VALUES ($1, $2, $3) RETURNING *"#,
@@ -113,25 +156,74 @@ impl StateExt for DBClient {
             &code.into(),
             Uuid::parse_str(&country_id.into()).unwrap(),
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(state)
     }
 
+    #[instrument(skip_all)]
     async fn delete_state(&self, state_id: Option<Uuid>) -> Result<Option<State>, sqlx::Error> {
         let mut state = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(state_id) = state_id {
             state = sqlx::query_as!(
                 State,
                 r#"DELETE FROM states WHERE id = $1 RETURNING *"#,
                 state_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(state)
     }
 }
+
+/// Inserts or updates the states by their code in a single statement.
+/// Returns one flag per inserted or changed row, `true` when the row was
+/// inserted; unchanged rows are not returned.
+pub async fn upsert_states(
+    conn: &mut PgConnection,
+    states: Vec<RegisterStateDTO>,
+) -> Result<Vec<bool>, sqlx::Error> {
+    let mut names = Vec::with_capacity(states.len());
+    let mut codes = Vec::with_capacity(states.len());
+    let mut country_ids = Vec::with_capacity(states.len());
+
+    for state in states {
+        let country_id = Uuid::parse_str(&state.country_id)
+            .map_err(|e| sqlx::Error::Protocol(format!("Failed to parse country_id: {e}")))?;
+
+        names.push(state.name);
+        codes.push(state.code);
+        country_ids.push(country_id);
+    }
+
+    // `xmax` is 0 for rows created by this statement, which tells
+    // inserts apart from updates.
+    let inserted = sqlx::query_scalar!(
+        r#"
+        INSERT INTO states (name, code, country_id)
+        SELECT * FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::UUID[])
+        ON CONFLICT (code) DO UPDATE
+            SET name = EXCLUDED.name, country_id = EXCLUDED.country_id
+            WHERE (states.name, states.country_id)
+                IS DISTINCT FROM (EXCLUDED.name, EXCLUDED.country_id)
+        RETURNING (xmax = 0) AS "inserted!"
+        "#,
+        &names,
+        &codes,
+        &country_ids,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(inserted)
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{dtos::geocoding::SaveStreetParamsDTO, models::street::Street};
+
+use super::client::DBClient;
+
+#[async_trait]
+pub trait StreetExt {
+    async fn get_street(
+        &self,
+        city_id: Uuid,
+        normalized_name: &str,
+    ) -> Result<Option<Street>, sqlx::Error>;
+
+    /// Inserts or updates the streets by city and normalized name in a single
+    /// statement, resolving each city by its IBGE code. Returns one flag per
+    /// inserted or changed row, `true` when the row was inserted; unchanged
+    /// rows are not returned.
+    async fn upsert_streets(
+        &self,
+        streets: Vec<SaveStreetParamsDTO>,
+    ) -> Result<Vec<bool>, sqlx::Error>;
+}
+
+#[async_trait]
+impl StreetExt for DBClient {
+    #[instrument(skip_all)]
+    async fn get_street(
+        &self,
+        city_id: Uuid,
+        normalized_name: &str,
+    ) -> Result<Option<Street>, sqlx::Error> {
+        let street = sqlx::query_as!(
+            Street,
+            r#"SELECT * FROM streets WHERE city_id = $1 AND normalized_name = $2"#,
+            city_id,
+            normalized_name
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(street)
+    }
+
+    #[instrument(skip_all)]
+    async fn upsert_streets(
+        &self,
+        streets: Vec<SaveStreetParamsDTO>,
+    ) -> Result<Vec<bool>, sqlx::Error> {
+        let mut names = Vec::with_capacity(streets.len());
+        let mut normalized_names = Vec::with_capacity(streets.len());
+        let mut latitudes = Vec::with_capacity(streets.len());
+        let mut longitudes = Vec::with_capacity(streets.len());
+        let mut city_codes = Vec::with_capacity(streets.len());
+
+        for street in streets {
+            names.push(street.name);
+            normalized_names.push(street.normalized_name);
+            latitudes.push(street.latitude);
+            longitudes.push(street.longitude);
+            city_codes.push(street.city_code);
+        }
+
+        // `xmax` is 0 for rows created by this statement, which tells
+        // inserts apart from updates.
+        let inserted = sqlx::query_scalar!(
+            r#"
+            INSERT INTO streets (name, normalized_name, latitude, longitude, city_id)
+            SELECT row.name, row.normalized_name, row.latitude, row.longitude, cities.id
+            FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::NUMERIC[], $4::NUMERIC[], $5::VARCHAR[])
+                AS row (name, normalized_name, latitude, longitude, city_code)
+            JOIN cities ON cities.code = row.city_code
+            ON CONFLICT (city_id, normalized_name) DO UPDATE
+                SET name = EXCLUDED.name, latitude = EXCLUDED.latitude, longitude = EXCLUDED.longitude
+                WHERE (streets.name, streets.latitude, streets.longitude)
+                    IS DISTINCT FROM (EXCLUDED.name, EXCLUDED.latitude, EXCLUDED.longitude)
+            RETURNING (xmax = 0) AS "inserted!"
+            "#,
+            &names,
+            &normalized_names,
+            &latitudes,
+            &longitudes,
+            &city_codes,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(inserted)
+    }
+}
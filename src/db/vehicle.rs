@@ -1,9 +1,10 @@
 use async_trait::async_trait;
-use log::info;
+use chrono::NaiveDateTime;
+use tracing::{info, instrument};
 use uuid::Uuid;
 
 use crate::{
-    dtos::vehicle::SaveVehicleDocumentParamsDTO,
+    dtos::vehicle::{RegisterVehicleDTO, SaveVehicleDocumentParamsDTO},
     models::vehicle::{Vehicle, VehicleDocument},
 };
 
@@ -13,7 +14,14 @@ use super::client::DBClient;
 pub trait VehicleExt {
     async fn get_vehicle(&self, vehicle_id: Option<Uuid>) -> Result<Option<Vehicle>, sqlx::Error>;
 
-    async fn list_vehicles(&self, page: u32, limit: usize) -> Result<Vec<Vehicle>, sqlx::Error>;
+    /// Lists a page of vehicles, restricted to those after the id `after`
+    /// when given.
+    async fn list_vehicles(
+        &self,
+        page: u32,
+        limit: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Vehicle>, sqlx::Error>;
 
     async fn save_vehicle<T: Into<String> + Send>(
         &self,
@@ -22,22 +30,34 @@ pub trait VehicleExt {
         actual_mileage: i32,
     ) -> Result<Vehicle, sqlx::Error>;
 
+    async fn save_vehicles(
+        &self,
+        vehicles: Vec<RegisterVehicleDTO>,
+    ) -> Result<Vec<Vehicle>, sqlx::Error>;
+
+    /// Updates the vehicle, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn update_vehicle<T: Into<String> + Send>(
         &self,
         vehicle_id: Option<Uuid>,
         name: T,
         initial_mileage: Option<i32>,
         actual_mileage: i32,
-    ) -> Result<Vehicle, sqlx::Error>;
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Vehicle>, sqlx::Error>;
 
+    /// Deletes the vehicle, only if it wasn't changed since
+    /// `expected_updated_at` when it is given.
     async fn delete_vehicle(
         &self,
         vehicle_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
     ) -> Result<Option<Vehicle>, sqlx::Error>;
 }
 
 #[async_trait]
 impl VehicleExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_vehicle(&self, vehicle_id: Option<Uuid>) -> Result<Option<Vehicle>, sqlx::Error> {
         if let Some(vehicle_id) = vehicle_id {
             let vehicle = sqlx::query_as!(
@@ -53,14 +73,21 @@ impl VehicleExt for DBClient {
         Ok(None)
     }
 
-    async fn list_vehicles(&self, page: u32, limit: usize) -> Result<Vec<Vehicle>, sqlx::Error> {
+    #[instrument(skip_all)]
+    async fn list_vehicles(
+        &self,
+        page: u32,
+        limit: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<Vehicle>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         let vehicles = sqlx::query_as!(
             Vehicle,
-            r#"SELECT * FROM vehicles LIMIT $1 OFFSET $2"#,
+            r#"SELECT * FROM vehicles WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
@@ -68,12 +95,15 @@ impl VehicleExt for DBClient {
         Ok(vehicles)
     }
 
+    #[instrument(skip_all)]
     async fn save_vehicle<T: Into<String> + Send>(
         &self,
         name: T,
         initial_mileage: i32,
         actual_mileage: i32,
     ) -> Result<Vehicle, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
         let vehicle = sqlx::query_as!(
             Vehicle,
             r#"INSERT INTO vehicles (name, initial_mileage, actual_mileage) VALUES ($1, $2, $3) RETURNING *"#,
@@ -81,53 +111,98 @@ impl VehicleExt for DBClient {
             &initial_mileage,
             &actual_mileage
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(vehicle)
     }
 
+    #[instrument(skip_all)]
+    async fn save_vehicles(
+        &self,
+        vehicles: Vec<RegisterVehicleDTO>,
+    ) -> Result<Vec<Vehicle>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+        let mut saved = Vec::with_capacity(vehicles.len());
+
+        for vehicle in vehicles {
+            let actual_mileage = vehicle.actual_mileage.unwrap_or(vehicle.initial_mileage);
+
+            let vehicle = sqlx::query_as!(
+                Vehicle,
+                r#"INSERT INTO vehicles (name, initial_mileage, actual_mileage) VALUES ($1, $2, $3) RETURNING *"#,
+                &vehicle.name,
+                &vehicle.initial_mileage,
+                &actual_mileage
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            saved.push(vehicle);
+        }
+
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    #[instrument(skip_all)]
     async fn update_vehicle<T: Into<String> + Send>(
         &self,
         vehicle_id: Option<Uuid>,
         name: T,
         initial_mileage: Option<i32>,
         actual_mileage: i32,
-    ) -> Result<Vehicle, sqlx::Error> {
+        expected_updated_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Vehicle>, sqlx::Error> {
         let name = name.into();
 
         info!("Updating the vehicle: {}", &name);
 
+        let mut tx = self.begin_audited().await?;
+
         let vehicle = sqlx::query_as!(
             Vehicle,
-            r#"UPDATE vehicles SET name = $2, initial_mileage = $3, actual_mileage = $4 WHERE id = $1 RETURNING *;"#,
+            r#"UPDATE vehicles SET name = $2, initial_mileage = $3, actual_mileage = $4 WHERE id = $1 AND ($5::TIMESTAMP IS NULL OR updated_at = $5) RETURNING *;"#,
             &vehicle_id.unwrap(),
             &name,
             &initial_mileage.unwrap(),
-            &actual_mileage
+            &actual_mileage,
+            expected_updated_at
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(vehicle)
     }
 
+    #[instrument(skip_all)]
     async fn delete_vehicle(
         &self,
         vehicle_id: Option<Uuid>,
+        expected_updated_at: Option<NaiveDateTime>,
     ) -> Result<Option<Vehicle>, sqlx::Error> {
         let mut vehicle = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(vehicle_id) = vehicle_id {
             vehicle = sqlx::query_as!(
                 Vehicle,
-                r#"DELETE FROM vehicles WHERE id = $1 RETURNING *"#,
-                vehicle_id
+                r#"DELETE FROM vehicles WHERE id = $1 AND ($2::TIMESTAMP IS NULL OR updated_at = $2) RETURNING *"#,
+                vehicle_id,
+                expected_updated_at
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(vehicle)
     }
 }
@@ -143,10 +218,13 @@ pub trait VehicleDocumentExt {
         plate: Option<String>,
     ) -> Result<Option<VehicleDocument>, sqlx::Error>;
 
+    /// Lists a page of vehicle documents, restricted to those after the
+    /// id `after` when given.
     async fn list_vehicle_documents(
         &self,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<VehicleDocument>, sqlx::Error>;
 
     async fn save_vehicle_document<T: Into<String> + Send>(
@@ -170,6 +248,7 @@ pub trait VehicleDocumentExt {
 
 #[async_trait]
 impl VehicleDocumentExt for DBClient {
+    #[instrument(skip_all)]
     async fn get_vehicle_document(
         &self,
         document_id: Option<Uuid>,
@@ -225,18 +304,21 @@ impl VehicleDocumentExt for DBClient {
         Ok(document)
     }
 
+    #[instrument(skip_all)]
     async fn list_vehicle_documents(
         &self,
         page: u32,
         limit: usize,
+        after: Option<Uuid>,
     ) -> Result<Vec<VehicleDocument>, sqlx::Error> {
         let offset = (page - 1) * limit as u32;
 
         let documents = sqlx::query_as!(
             VehicleDocument,
-            r#"SELECT * FROM vehicles_documents LIMIT $1 OFFSET $2"#,
+            r#"SELECT * FROM vehicles_documents WHERE $3::UUID IS NULL OR id > $3 ORDER BY id LIMIT $1 OFFSET $2"#,
             limit as i64,
-            offset as i64
+            offset as i64,
+            after
         )
         .fetch_all(&self.pool)
         .await?;
@@ -244,6 +326,7 @@ impl VehicleDocumentExt for DBClient {
         Ok(documents)
     }
 
+    #[instrument(skip_all)]
     async fn save_vehicle_document<T: Into<String> + Send>(
         &self,
         params: SaveVehicleDocumentParamsDTO<T>,
@@ -261,6 +344,8 @@ impl VehicleDocumentExt for DBClient {
             vehicle_id,
         } = params;
 
+        let mut tx = self.begin_audited().await?;
+
         let document = sqlx::query_as!(
             VehicleDocument,
             r#"INSERT INTO vehicles_documents (chassis_number, exercise_year, model_year, manufacture_year, registration_number, color, make, model, plate, vehicle_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *"#,
@@ -275,12 +360,15 @@ impl VehicleDocumentExt for DBClient {
             &plate.into(),
             Uuid::parse_str(&vehicle_id.unwrap().into()).unwrap(),
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(document)
     }
 
+    #[instrument(skip_all)]
     async fn update_vehicle_document<T: Into<String> + Send>(
         &self,
         document_id: Option<Uuid>,
@@ -300,6 +388,8 @@ impl VehicleDocumentExt for DBClient {
             vehicle_id: _,
         } = params;
 
+        let mut tx = self.begin_audited().await?;
+
         let document: VehicleDocument = match (vehicle_id_path, document_id) {
             (Some(vehicle_id_path), _) => {
                 sqlx::query_as!(
@@ -329,7 +419,7 @@ impl VehicleDocumentExt for DBClient {
                     &model.into(),
                     &plate.into()
                 )
-                .fetch_one(&self.pool)
+                .fetch_one(&mut *tx)
                 .await?
             }
             (None, Some(document_id)) => {
@@ -360,7 +450,7 @@ impl VehicleDocumentExt for DBClient {
                     &model.into(),
                     &plate.into()
                 )
-                .fetch_one(&self.pool)
+                .fetch_one(&mut *tx)
                 .await?
             }
             _ => {
@@ -368,9 +458,12 @@ impl VehicleDocumentExt for DBClient {
             }
         };
 
+        tx.commit().await?;
+
         Ok(document)
     }
 
+    #[instrument(skip_all)]
     async fn delete_vehicle_document(
         &self,
         document_id: Option<Uuid>,
@@ -378,13 +471,15 @@ impl VehicleDocumentExt for DBClient {
     ) -> Result<Option<VehicleDocument>, sqlx::Error> {
         let mut document = None;
 
+        let mut tx = self.begin_audited().await?;
+
         if let Some(document_id) = document_id {
             document = sqlx::query_as!(
                 VehicleDocument,
                 r#"DELETE FROM vehicles_documents WHERE id = $1 RETURNING *"#,
                 document_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         } else if let Some(vehicle_id) = vehicle_id {
             document = sqlx::query_as!(
@@ -392,10 +487,12 @@ impl VehicleDocumentExt for DBClient {
                 r#"DELETE FROM vehicles_documents WHERE vehicle_id = $1 RETURNING *"#,
                 vehicle_id
             )
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(document)
     }
 }
@@ -0,0 +1,492 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    dtos::webhook::SaveWebhookSubscriptionParamsDTO,
+    models::webhook::{PendingWebhookDelivery, WebhookDelivery, WebhookSubscription},
+};
+
+use super::client::DBClient;
+
+#[async_trait]
+pub trait WebhookExt {
+    async fn get_webhook_subscription(
+        &self,
+        subscription_id: Option<Uuid>,
+    ) -> Result<Option<WebhookSubscription>, sqlx::Error>;
+
+    async fn list_webhook_subscriptions(
+        &self,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<WebhookSubscription>, sqlx::Error>;
+
+    async fn save_webhook_subscription(
+        &self,
+        params: SaveWebhookSubscriptionParamsDTO,
+    ) -> Result<WebhookSubscription, sqlx::Error>;
+
+    async fn update_webhook_subscription(
+        &self,
+        subscription_id: Uuid,
+        params: SaveWebhookSubscriptionParamsDTO,
+    ) -> Result<Option<WebhookSubscription>, sqlx::Error>;
+
+    async fn delete_webhook_subscription(
+        &self,
+        subscription_id: Option<Uuid>,
+    ) -> Result<Option<WebhookSubscription>, sqlx::Error>;
+
+    /// Lists the deliveries to a subscription, most recent first.
+    async fn list_webhook_deliveries(
+        &self,
+        subscription_id: Uuid,
+        status: Option<&str>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error>;
+
+    async fn get_webhook_delivery(
+        &self,
+        subscription_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error>;
+
+    /// Schedules a failed delivery, or a pending one already due, to be sent
+    /// again right away, with all its attempts. Returns `None` when the
+    /// delivery can't be retried, as it may be being sent.
+    async fn retry_webhook_delivery(
+        &self,
+        subscription_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error>;
+
+    /// Records a `ping` event to be delivered to the subscription only.
+    async fn ping_webhook_subscription(
+        &self,
+        subscription_id: Uuid,
+    ) -> Result<WebhookDelivery, sqlx::Error>;
+
+    /// Claims up to `limit` deliveries due to be sent, counting the attempt
+    /// and reserving them for `lease_secs`.
+    async fn claim_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease_secs: f64,
+    ) -> Result<Vec<PendingWebhookDelivery>, sqlx::Error>;
+
+    async fn complete_webhook_delivery(
+        &self,
+        delivery_id: Uuid,
+        response_status: i32,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Records a failed attempt. The delivery is retried in `retry_in_secs`,
+    /// or given up on when it is `None`.
+    async fn fail_webhook_delivery(
+        &self,
+        delivery_id: Uuid,
+        response_status: Option<i32>,
+        error: &str,
+        retry_in_secs: Option<f64>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Records a `driver.cnh_expired` event for each driver whose CNH expired
+    /// since it was last recorded. Returns how many were recorded.
+    async fn enqueue_expired_cnh_events(&self) -> Result<i64, sqlx::Error>;
+
+    /// Deletes the finished deliveries last updated before `before`, then the
+    /// events older than it left without deliveries. Events with a dedupe key
+    /// are kept, so they aren't recorded again. Returns how many deliveries
+    /// and events were deleted.
+    async fn delete_webhook_events(&self, before: NaiveDateTime)
+        -> Result<(u64, u64), sqlx::Error>;
+}
+
+#[async_trait]
+impl WebhookExt for DBClient {
+    #[instrument(skip_all)]
+    async fn get_webhook_subscription(
+        &self,
+        subscription_id: Option<Uuid>,
+    ) -> Result<Option<WebhookSubscription>, sqlx::Error> {
+        if let Some(subscription_id) = subscription_id {
+            let subscription = sqlx::query_as!(
+                WebhookSubscription,
+                r#"SELECT * FROM webhook_subscriptions WHERE id = $1"#,
+                subscription_id
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            return Ok(subscription);
+        }
+
+        Ok(None)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_webhook_subscriptions(
+        &self,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<WebhookSubscription>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        let subscriptions = sqlx::query_as!(
+            WebhookSubscription,
+            r#"SELECT * FROM webhook_subscriptions ORDER BY created_at LIMIT $1 OFFSET $2"#,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    #[instrument(skip_all)]
+    async fn save_webhook_subscription(
+        &self,
+        params: SaveWebhookSubscriptionParamsDTO,
+    ) -> Result<WebhookSubscription, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
+        let subscription = sqlx::query_as!(
+            WebhookSubscription,
+            r#"
+            INSERT INTO webhook_subscriptions (url, event_types, secret, active)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+            params.url,
+            &params.event_types,
+            params.secret,
+            params.active
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(subscription)
+    }
+
+    #[instrument(skip_all)]
+    async fn update_webhook_subscription(
+        &self,
+        subscription_id: Uuid,
+        params: SaveWebhookSubscriptionParamsDTO,
+    ) -> Result<Option<WebhookSubscription>, sqlx::Error> {
+        let mut tx = self.begin_audited().await?;
+
+        let subscription = sqlx::query_as!(
+            WebhookSubscription,
+            r#"
+            UPDATE webhook_subscriptions SET url = $2, event_types = $3, secret = $4, active = $5
+            WHERE id = $1
+            RETURNING *
+            "#,
+            subscription_id,
+            params.url,
+            &params.event_types,
+            params.secret,
+            params.active
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(subscription)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_webhook_subscription(
+        &self,
+        subscription_id: Option<Uuid>,
+    ) -> Result<Option<WebhookSubscription>, sqlx::Error> {
+        let mut subscription = None;
+
+        let mut tx = self.begin_audited().await?;
+
+        if let Some(subscription_id) = subscription_id {
+            subscription = sqlx::query_as!(
+                WebhookSubscription,
+                r#"DELETE FROM webhook_subscriptions WHERE id = $1 RETURNING *"#,
+                subscription_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(subscription)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_webhook_deliveries(
+        &self,
+        subscription_id: Uuid,
+        status: Option<&str>,
+        page: u32,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let offset = (page - 1) * limit as u32;
+
+        let deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT d.id, d.event_id, e.event_type, d.subscription_id, d.status, d.attempts, d.next_attempt_at,
+                d.last_response_status, d.last_error, d.delivered_at, d.created_at, d.updated_at
+            FROM webhook_deliveries d
+            JOIN webhook_events e ON e.id = d.event_id
+            WHERE d.subscription_id = $1 AND ($2::VARCHAR IS NULL OR d.status = $2)
+            ORDER BY d.created_at DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            subscription_id,
+            status,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_webhook_delivery(
+        &self,
+        subscription_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT d.id, d.event_id, e.event_type, d.subscription_id, d.status, d.attempts, d.next_attempt_at,
+                d.last_response_status, d.last_error, d.delivered_at, d.created_at, d.updated_at
+            FROM webhook_deliveries d
+            JOIN webhook_events e ON e.id = d.event_id
+            WHERE d.id = $1 AND d.subscription_id = $2
+            "#,
+            delivery_id,
+            subscription_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    #[instrument(skip_all)]
+    async fn retry_webhook_delivery(
+        &self,
+        subscription_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+        // A pending delivery not due yet is either waiting for its next
+        // attempt or claimed by an instance sending it, until its lease
+        // expires. Resetting it could send it twice at once.
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            UPDATE webhook_deliveries d SET status = 'pending', attempts = 0, next_attempt_at = NOW()
+            FROM webhook_events e
+            WHERE d.id = $1 AND d.subscription_id = $2 AND e.id = d.event_id
+                AND (d.status = 'failed' OR (d.status = 'pending' AND d.next_attempt_at <= NOW()))
+            RETURNING d.id, d.event_id, e.event_type, d.subscription_id, d.status, d.attempts, d.next_attempt_at,
+                d.last_response_status, d.last_error, d.delivered_at, d.created_at, d.updated_at
+            "#,
+            delivery_id,
+            subscription_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    #[instrument(skip_all)]
+    async fn ping_webhook_subscription(
+        &self,
+        subscription_id: Uuid,
+    ) -> Result<WebhookDelivery, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let event_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO webhook_events (event_type, data)
+            VALUES ('ping', jsonb_build_object('subscriptionId', $1::UUID))
+            RETURNING id
+            "#,
+            subscription_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            INSERT INTO webhook_deliveries (event_id, subscription_id)
+            VALUES ($1, $2)
+            RETURNING id, event_id, 'ping' AS "event_type!", subscription_id, status, attempts, next_attempt_at,
+                last_response_status, last_error, delivered_at, created_at, updated_at
+            "#,
+            event_id,
+            subscription_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(delivery)
+    }
+
+    // Polled every few seconds, so its span is only logged at debug level.
+    #[instrument(skip_all, level = "debug")]
+    async fn claim_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease_secs: f64,
+    ) -> Result<Vec<PendingWebhookDelivery>, sqlx::Error> {
+        // Deliveries to inactive subscriptions wait until they are active again.
+        let deliveries = sqlx::query_as!(
+            PendingWebhookDelivery,
+            r#"
+            UPDATE webhook_deliveries d
+            SET attempts = d.attempts + 1, next_attempt_at = NOW() + make_interval(secs => $2)
+            FROM webhook_events e, webhook_subscriptions s
+            WHERE d.id IN (
+                SELECT pd.id FROM webhook_deliveries pd
+                JOIN webhook_subscriptions ps ON ps.id = pd.subscription_id
+                WHERE pd.status = 'pending' AND pd.next_attempt_at <= NOW() AND ps.active
+                ORDER BY pd.next_attempt_at
+                LIMIT $1
+                FOR UPDATE OF pd SKIP LOCKED
+            )
+                AND e.id = d.event_id AND s.id = d.subscription_id
+            RETURNING d.id, d.attempts, e.id AS event_id, e.event_type, e.data,
+                e.created_at AS event_created_at, s.url, s.secret
+            "#,
+            limit,
+            lease_secs
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    #[instrument(skip_all)]
+    async fn complete_webhook_delivery(
+        &self,
+        delivery_id: Uuid,
+        response_status: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'delivered', delivered_at = NOW(), last_response_status = $2, last_error = NULL
+            WHERE id = $1
+            "#,
+            delivery_id,
+            response_status
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn fail_webhook_delivery(
+        &self,
+        delivery_id: Uuid,
+        response_status: Option<i32>,
+        error: &str,
+        retry_in_secs: Option<f64>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = CASE WHEN $4::FLOAT8 IS NULL THEN 'failed' ELSE 'pending' END,
+                next_attempt_at = COALESCE(NOW() + make_interval(secs => $4), next_attempt_at),
+                last_response_status = $2,
+                last_error = $3
+            WHERE id = $1
+            "#,
+            delivery_id,
+            response_status,
+            error,
+            retry_in_secs
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn enqueue_expired_cnh_events(&self) -> Result<i64, sqlx::Error> {
+        // The dedupe key holds the expiration date, so a renewed CNH is
+        // recorded again when it expires.
+        let enqueued = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(enqueue_webhook_event(
+                'driver.cnh_expired',
+                jsonb_build_object(
+                    'driverId', d.id,
+                    'collaboratorId', d.collaborator_id,
+                    'cnhNumber', d.cnh_number,
+                    'cnhTypeId', d.cnh_type_id,
+                    'cnhExpirationDate', d.cnh_expiration_date
+                ),
+                'driver.cnh_expired:' || d.id || ':' || d.cnh_expiration_date
+            )) AS "enqueued!"
+            FROM drivers d
+            WHERE d.cnh_expiration_date < CURRENT_DATE
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(enqueued)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_webhook_events(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<(u64, u64), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let deliveries = sqlx::query!(
+            r#"DELETE FROM webhook_deliveries WHERE status <> 'pending' AND updated_at < $1"#,
+            before
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let events = sqlx::query!(
+            r#"
+            DELETE FROM webhook_events e
+            WHERE e.created_at < $1
+                AND e.dedupe_key IS NULL
+                AND NOT EXISTS (SELECT 1 FROM webhook_deliveries d WHERE d.event_id = e.id)
+            "#,
+            before
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok((deliveries.rows_affected(), events.rows_affected()))
+    }
+}
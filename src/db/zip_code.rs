@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use tracing::instrument;
+
+use crate::{dtos::zip_code::ImportZipCodeRowDTO, models::zip_code::ZipCode};
+
+use super::client::DBClient;
+
+#[async_trait]
+pub trait ZipCodeExt {
+    async fn get_zip_code(&self, zip_code: &str) -> Result<Option<ZipCode>, sqlx::Error>;
+
+    /// Inserts or updates the CEPs in a single statement, resolving each city
+    /// by its IBGE code. CEPs of unknown cities are skipped. Returns one flag
+    /// per inserted or changed row, `true` when the row was inserted;
+    /// unchanged rows are not returned.
+    async fn upsert_zip_codes(
+        &self,
+        zip_codes: Vec<ImportZipCodeRowDTO>,
+    ) -> Result<Vec<bool>, sqlx::Error>;
+}
+
+#[async_trait]
+impl ZipCodeExt for DBClient {
+    #[instrument(skip_all)]
+    async fn get_zip_code(&self, zip_code: &str) -> Result<Option<ZipCode>, sqlx::Error> {
+        let zip_code = sqlx::query_as!(
+            ZipCode,
+            r#"SELECT * FROM zip_codes WHERE zip_code = $1"#,
+            zip_code
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(zip_code)
+    }
+
+    #[instrument(skip_all)]
+    async fn upsert_zip_codes(
+        &self,
+        zip_codes: Vec<ImportZipCodeRowDTO>,
+    ) -> Result<Vec<bool>, sqlx::Error> {
+        let mut codes = Vec::with_capacity(zip_codes.len());
+        let mut addresses: Vec<Option<String>> = Vec::with_capacity(zip_codes.len());
+        let mut neighbourhoods: Vec<Option<String>> = Vec::with_capacity(zip_codes.len());
+        let mut city_codes = Vec::with_capacity(zip_codes.len());
+
+        for zip_code in zip_codes {
+            codes.push(zip_code.zip_code);
+            addresses.push(zip_code.address);
+            neighbourhoods.push(zip_code.neighbourhood);
+            city_codes.push(zip_code.city_code);
+        }
+
+        // `xmax` is 0 for rows created by this statement, which tells
+        // inserts apart from updates.
+        let inserted = sqlx::query_scalar!(
+            r#"
+            INSERT INTO zip_codes (zip_code, address, neighbourhood, city_id)
+            SELECT row.zip_code, row.address, row.neighbourhood, cities.id
+            FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[], $4::VARCHAR[])
+                AS row (zip_code, address, neighbourhood, city_code)
+            JOIN cities ON cities.code = row.city_code
+            ON CONFLICT (zip_code) DO UPDATE
+                SET address = EXCLUDED.address,
+                    neighbourhood = EXCLUDED.neighbourhood,
+                    city_id = EXCLUDED.city_id
+                WHERE (zip_codes.address, zip_codes.neighbourhood, zip_codes.city_id)
+                    IS DISTINCT FROM (EXCLUDED.address, EXCLUDED.neighbourhood, EXCLUDED.city_id)
+            RETURNING (xmax = 0) AS "inserted!"
+            "#,
+            &codes,
+            &addresses as &[Option<String>],
+            &neighbourhoods as &[Option<String>],
+            &city_codes,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(inserted)
+    }
+}
@@ -2,7 +2,13 @@ use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-use crate::{models::address::Address, utils::string::is_valid_uuid};
+use crate::{
+    models::address::{Address, AddressSearchResult, NearbyAddress},
+    utils::{
+        geocoding::GeocodingAccuracy,
+        string::{is_valid_uuid, is_valid_zip_code, normalize_zip_code},
+    },
+};
 
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -42,10 +48,9 @@ pub struct RegisterAddressDTO {
     ))]
     pub complement: Option<String>,
 
-    #[validate(length(
-        min = 5,
-        max = 8,
-        message = "Zip code must have a minimum of 5 and a maximum of 8 characters"
+    #[validate(custom(
+        function = "is_valid_zip_code",
+        message = "Zip code must have 8 digits"
     ))]
     pub zip_code: String,
 
@@ -57,7 +62,22 @@ pub struct RegisterAddressDTO {
 }
 
 impl RegisterAddressDTO {
+    /// Stores a CEP typed as `01001-000` or `01.001-000` as its 8 digits.
+    /// Any other value is left for the validation to reject.
+    pub fn normalize_zip_code(&mut self) {
+        if let Some(zip_code) = normalize_zip_code(&self.zip_code) {
+            self.zip_code = zip_code;
+        }
+    }
+
+    /// Coordinates sent by the client are marked as `provided`. Addresses
+    /// without them are left for the geocoder.
     pub fn into_save_address_params_dto(self) -> SaveAddressParamsDTO<String, BigDecimal> {
+        let geocoding_accuracy = match (&self.latitude, &self.longitude) {
+            (Some(_), Some(_)) => Some(GeocodingAccuracy::Provided.to_string()),
+            _ => None,
+        };
+
         SaveAddressParamsDTO {
             address: self.address,
             number: self.number,
@@ -68,10 +88,55 @@ impl RegisterAddressDTO {
             latitude: self.latitude,
             longitude: self.longitude,
             city_id: self.city_id,
+            geocoding_accuracy,
         }
     }
 }
 
+/// A row of an address CSV import. The city is referenced by its IBGE code
+/// instead of its ID so spreadsheets can be filled without looking up UUIDs.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportAddressRowDTO {
+    pub address: String,
+    pub number: String,
+    pub neighbourhood: String,
+    pub reference: Option<String>,
+    pub complement: Option<String>,
+    pub zip_code: String,
+    pub latitude: Option<BigDecimal>,
+    pub longitude: Option<BigDecimal>,
+    pub city_code: String,
+}
+
+impl ImportAddressRowDTO {
+    pub fn into_register_address_dto(self, city_id: String) -> RegisterAddressDTO {
+        let mut address = RegisterAddressDTO {
+            address: self.address,
+            number: self.number,
+            neighbourhood: self.neighbourhood,
+            reference: self.reference,
+            complement: self.complement,
+            zip_code: self.zip_code,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            city_id,
+        };
+
+        address.normalize_zip_code();
+
+        address
+    }
+
+    /// The `(address, number, zip_code)` the row would be saved with, used to
+    /// find duplicates before the cities are looked up.
+    pub fn details(&self) -> (String, String, String) {
+        let zip_code = normalize_zip_code(&self.zip_code).unwrap_or_else(|| self.zip_code.clone());
+
+        (self.address.clone(), self.number.clone(), zip_code)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SaveAddressParamsDTO<T, B> {
     pub address: T,
@@ -83,6 +148,7 @@ pub struct SaveAddressParamsDTO<T, B> {
     pub latitude: Option<B>,
     pub longitude: Option<B>,
     pub city_id: T,
+    pub geocoding_accuracy: Option<T>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -98,6 +164,7 @@ pub struct FilterAddressDTO {
     pub latitude: Option<BigDecimal>,
     pub longitude: Option<BigDecimal>,
     pub city_id: String,
+    pub geocoding_accuracy: Option<String>,
 }
 
 impl FilterAddressDTO {
@@ -113,6 +180,7 @@ impl FilterAddressDTO {
             latitude: address.latitude.to_owned(),
             longitude: address.longitude.to_owned(),
             city_id: address.city_id.to_string(),
+            geocoding_accuracy: address.geocoding_accuracy.to_owned(),
         }
     }
 
@@ -129,3 +197,138 @@ pub struct AddressListResponseDTO {
     pub addresses: Vec<FilterAddressDTO>,
     pub results: usize,
 }
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct NearestAddressQueryDTO {
+    #[validate(range(
+        min = -90.0,
+        max = 90.0,
+        message = "Latitude must be between -90 and 90"
+    ))]
+    pub lat: f64,
+
+    #[validate(range(
+        min = -180.0,
+        max = 180.0,
+        message = "Longitude must be between -180 and 180"
+    ))]
+    pub long: f64,
+
+    /// The search radius in meters.
+    #[validate(range(
+        min = 1.0,
+        max = 50000.0,
+        message = "Radius must be between 1 and 50000 meters"
+    ))]
+    pub radius: Option<f64>,
+
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50"))]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NearestAddressDTO {
+    #[serde(flatten)]
+    pub address: FilterAddressDTO,
+    /// The distance in meters to the searched point.
+    pub distance: f64,
+}
+
+impl NearestAddressDTO {
+    pub fn filter_nearest_address(nearby: &NearbyAddress) -> Self {
+        NearestAddressDTO {
+            address: FilterAddressDTO {
+                id: nearby.id.to_string(),
+                address: nearby.address.to_owned(),
+                number: nearby.number.to_owned(),
+                neighbourhood: nearby.neighbourhood.to_owned(),
+                reference: nearby.reference.to_owned(),
+                complement: nearby.complement.to_owned(),
+                zip_code: nearby.zip_code.to_owned(),
+                latitude: nearby.latitude.to_owned(),
+                longitude: nearby.longitude.to_owned(),
+                city_id: nearby.city_id.to_string(),
+                geocoding_accuracy: nearby.geocoding_accuracy.to_owned(),
+            },
+            distance: nearby.distance,
+        }
+    }
+
+    pub fn filter_nearest_addresses(addresses: &[NearbyAddress]) -> Vec<NearestAddressDTO> {
+        addresses
+            .iter()
+            .map(NearestAddressDTO::filter_nearest_address)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NearestAddressListResponseDTO {
+    pub addresses: Vec<NearestAddressDTO>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AddressSearchQueryDTO {
+    /// Terms matched against the street, number, neighbourhood and CEP,
+    /// regardless of case and accents.
+    #[validate(length(
+        min = 1,
+        max = 200,
+        message = "Search terms must have between 1 and 200 characters"
+    ))]
+    pub q: String,
+
+    #[validate(custom(function = "is_valid_uuid", message = "City ID must be a valid UUID"))]
+    pub city_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressSearchResultDTO {
+    #[serde(flatten)]
+    pub address: FilterAddressDTO,
+    pub city_name: String,
+    pub state_name: String,
+    pub state_code: String,
+    /// How well the address matches the searched terms, from 0 to 1.
+    pub score: f64,
+}
+
+impl AddressSearchResultDTO {
+    pub fn filter_search_result(result: &AddressSearchResult) -> Self {
+        AddressSearchResultDTO {
+            address: FilterAddressDTO {
+                id: result.id.to_string(),
+                address: result.address.to_owned(),
+                number: result.number.to_owned(),
+                neighbourhood: result.neighbourhood.to_owned(),
+                reference: result.reference.to_owned(),
+                complement: result.complement.to_owned(),
+                zip_code: result.zip_code.to_owned(),
+                latitude: result.latitude.to_owned(),
+                longitude: result.longitude.to_owned(),
+                city_id: result.city_id.to_string(),
+                geocoding_accuracy: result.geocoding_accuracy.to_owned(),
+            },
+            city_name: result.city_name.to_owned(),
+            state_name: result.state_name.to_owned(),
+            state_code: result.state_code.to_owned(),
+            score: result.score,
+        }
+    }
+
+    pub fn filter_search_results(results: &[AddressSearchResult]) -> Vec<AddressSearchResultDTO> {
+        results
+            .iter()
+            .map(AddressSearchResultDTO::filter_search_result)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddressSearchListResponseDTO {
+    pub addresses: Vec<AddressSearchResultDTO>,
+    pub results: usize,
+}
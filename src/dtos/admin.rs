@@ -0,0 +1,24 @@
+use validator::Validate;
+
+#[derive(Validate, Debug, Default, Clone)]
+pub struct CreateAdminDTO {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have a maximum of 100 characters"
+    ))]
+    pub name: String,
+
+    #[validate(
+        email(message = "Email must be a valid email address"),
+        length(max = 150, message = "Email must have a maximum of 150 characters")
+    )]
+    pub email: String,
+
+    #[validate(length(
+        min = 8,
+        max = 128,
+        message = "Password must have between 8 and 128 characters"
+    ))]
+    pub password: String,
+}
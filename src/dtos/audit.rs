@@ -0,0 +1,72 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    models::audit::AuditLogEntry,
+    utils::{audit::is_valid_audit_entity, string::is_valid_uuid},
+};
+
+/// Narrows the audit log to an entity type, and to one entity of it.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AuditQueryDTO {
+    #[validate(custom(
+        function = "is_valid_audit_entity",
+        message = "Entity must be one of country, state, city, address, collaborator, driver, cnh_type, vehicle, vehicle_document, route, route_status, geofence, webhook_subscription"
+    ))]
+    pub entity: Option<String>,
+
+    #[validate(custom(function = "is_valid_uuid", message = "ID must be a valid UUID"))]
+    pub id: Option<String>,
+}
+
+impl AuditQueryDTO {
+    /// The parsed `id`, once validated.
+    pub fn id(&self) -> Option<Uuid> {
+        self.id.as_deref().and_then(|id| Uuid::parse_str(id).ok())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterAuditLogEntryDTO {
+    pub id: i64,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub action: String,
+    pub actor: Option<String>,
+    pub request_id: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub occurred_at: NaiveDateTime,
+}
+
+impl FilterAuditLogEntryDTO {
+    pub fn filter_audit_log_entry(entry: &AuditLogEntry) -> Self {
+        FilterAuditLogEntryDTO {
+            id: entry.id,
+            entity_type: entry.entity_type.to_owned(),
+            entity_id: entry.entity_id.to_string(),
+            action: entry.action.to_owned(),
+            actor: entry.actor.to_owned(),
+            request_id: entry.request_id.to_owned(),
+            before: entry.before.to_owned(),
+            after: entry.after.to_owned(),
+            occurred_at: entry.occurred_at.to_owned(),
+        }
+    }
+
+    pub fn filter_audit_log_entries(entries: &[AuditLogEntry]) -> Vec<FilterAuditLogEntryDTO> {
+        entries
+            .iter()
+            .map(FilterAuditLogEntryDTO::filter_audit_log_entry)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogListResponseDTO {
+    pub entries: Vec<FilterAuditLogEntryDTO>,
+    pub results: usize,
+}
@@ -1,4 +1,6 @@
+use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use validator::Validate;
 
 use crate::{models::city::City, utils::string::is_valid_uuid};
@@ -20,6 +22,61 @@ pub struct RegisterCityDTO {
     pub state_id: String,
 }
 
+/// A row of a city CSV import. The state is referenced by its code (e.g.
+/// `SP`) instead of its ID.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportCityRowDTO {
+    pub name: String,
+    pub code: String,
+    pub state_code: String,
+}
+
+impl ImportCityRowDTO {
+    pub fn into_register_city_dto(self, state_id: String) -> RegisterCityDTO {
+        RegisterCityDTO {
+            name: self.name,
+            code: self.code,
+            state_id,
+        }
+    }
+}
+
+/// A municipality of the IBGE territorial division together with its state,
+/// as read by `import geography`.
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportIbgeCityRowDTO {
+    #[validate(length(min = 7, max = 7, message = "Code must be 7 characters long"))]
+    pub code: String,
+
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have a maximum of 100 characters"
+    ))]
+    pub name: String,
+
+    #[validate(length(min = 2, max = 2, message = "State code must be 2 characters long"))]
+    pub state_code: String,
+
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "State name must have a maximum of 100 characters"
+    ))]
+    pub state_name: String,
+}
+
+/// A row of a city centroid CSV import, used by the offline geocoder.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportCityCentroidRowDTO {
+    pub code: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FilterCityDTO {
@@ -27,6 +84,8 @@ pub struct FilterCityDTO {
     pub name: String,
     pub code: String,
     pub state_id: String,
+    pub latitude: Option<BigDecimal>,
+    pub longitude: Option<BigDecimal>,
 }
 
 impl FilterCityDTO {
@@ -36,6 +95,8 @@ impl FilterCityDTO {
             name: city.name.to_owned(),
             code: city.code.to_owned(),
             state_id: city.state_id.to_string(),
+            latitude: city.latitude.to_owned(),
+            longitude: city.longitude.to_owned(),
         }
     }
 
@@ -49,3 +110,28 @@ pub struct CityListResponseDTO {
     pub cities: Vec<FilterCityDTO>,
     pub results: usize,
 }
+
+/// Narrows the list of cities by name and state.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CityQueryDTO {
+    /// Keeps the names starting with this prefix, regardless of case and
+    /// accents.
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have between 1 and 100 characters"
+    ))]
+    pub name: Option<String>,
+
+    #[validate(custom(function = "is_valid_uuid", message = "State ID must be a valid UUID"))]
+    pub state_id: Option<String>,
+}
+
+impl CityQueryDTO {
+    /// The parsed `state_id`, once validated.
+    pub fn state_id(&self) -> Option<Uuid> {
+        self.state_id
+            .as_deref()
+            .and_then(|state_id| Uuid::parse_str(state_id).ok())
+    }
+}
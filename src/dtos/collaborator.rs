@@ -24,7 +24,7 @@ pub struct RegisterCollaboratorDTO {
     #[validate(length(min = 1, max = 9, message = "RG must have a maximum of 9 characters"))]
     pub rg: String,
 
-    #[validate(email)]
+    #[validate(email(message = "Email must be a valid email address"))]
     pub email: String,
 }
 
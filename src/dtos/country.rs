@@ -57,3 +57,27 @@ pub struct CountryListResponseDTO {
     pub countries: Vec<FilterCountryDTO>,
     pub results: usize,
 }
+
+/// The outcome of a country sync, with one count per row of the source.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountrySyncReportDTO {
+    pub source: String,
+    pub total: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Narrows the list of countries by name.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CountryQueryDTO {
+    /// Keeps the names starting with this prefix, regardless of case and
+    /// accents.
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have between 1 and 100 characters"
+    ))]
+    pub name: Option<String>,
+}
@@ -28,6 +28,32 @@ pub struct RegisterDriverDTO {
     pub collaborator_id: String,
 }
 
+/// A row of a driver CSV import. The CNH type is referenced by its code and
+/// the collaborator by its CPF.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportDriverRowDTO {
+    pub cnh_number: String,
+    pub cnh_expiration_date: NaiveDate,
+    pub cnh_type: String,
+    pub collaborator_cpf: String,
+}
+
+impl ImportDriverRowDTO {
+    pub fn into_register_driver_dto(
+        self,
+        id_cnh_type: String,
+        collaborator_id: String,
+    ) -> RegisterDriverDTO {
+        RegisterDriverDTO {
+            cnh_number: self.cnh_number,
+            cnh_expiration_date: self.cnh_expiration_date,
+            id_cnh_type,
+            collaborator_id,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FilterDriverDTO {
@@ -0,0 +1,43 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// A row of a street index CSV import. The city is referenced by its IBGE
+/// code, and the street is located at a single point.
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportStreetRowDTO {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have a maximum of 100 characters"
+    ))]
+    pub name: String,
+
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+
+    #[validate(length(min = 7, max = 7, message = "Code must be 7 characters long"))]
+    pub city_code: String,
+}
+
+/// The street index row as saved, with the name the geocoder compares.
+#[derive(Debug, Clone)]
+pub struct SaveStreetParamsDTO {
+    pub name: String,
+    pub normalized_name: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub city_code: String,
+}
+
+/// The outcome of `geocode backfill`, with one count per accuracy level.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeocodingBackfillReportDTO {
+    pub dry_run: bool,
+    pub total: usize,
+    pub street: usize,
+    pub city: usize,
+    pub not_found: usize,
+}
@@ -0,0 +1,199 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    models::geofence::{Geofence, GeofenceEvent, GeofenceVertex},
+    utils::{geofence::is_valid_geofence_kind, string::is_valid_uuid},
+};
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterGeofenceDTO {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have between 1 and 100 characters"
+    ))]
+    pub name: String,
+
+    #[validate(custom(
+        function = "is_valid_geofence_kind",
+        message = "Kind must be one of depot, customer_site, restricted_zone"
+    ))]
+    pub kind: String,
+
+    #[validate(range(
+        min = -90.0,
+        max = 90.0,
+        message = "Latitude must be between -90 and 90"
+    ))]
+    pub center_lat: Option<f64>,
+
+    #[validate(range(
+        min = -180.0,
+        max = 180.0,
+        message = "Longitude must be between -180 and 180"
+    ))]
+    pub center_long: Option<f64>,
+
+    #[validate(range(
+        min = 1.0,
+        max = 50000.0,
+        message = "Radius must be between 1 and 50000 meters"
+    ))]
+    pub radius_meters: Option<f64>,
+
+    #[validate(
+        length(
+            min = 3,
+            max = 1000,
+            message = "A polygon must have between 3 and 1000 vertices"
+        ),
+        nested
+    )]
+    pub vertices: Option<Vec<GeofenceVertexDTO>>,
+
+    /// The address the geofence surrounds. Circles without a center are
+    /// centered on it.
+    #[validate(custom(
+        function = "is_valid_uuid",
+        message = "Address ID must be a valid UUID"
+    ))]
+    pub address_id: Option<String>,
+}
+
+#[derive(Validate, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct GeofenceVertexDTO {
+    #[validate(range(
+        min = -90.0,
+        max = 90.0,
+        message = "Latitude must be between -90 and 90"
+    ))]
+    pub lat: f64,
+
+    #[validate(range(
+        min = -180.0,
+        max = 180.0,
+        message = "Longitude must be between -180 and 180"
+    ))]
+    pub long: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveGeofenceParamsDTO {
+    pub name: String,
+    pub kind: String,
+    pub shape: String,
+    pub center_lat: Option<BigDecimal>,
+    pub center_long: Option<BigDecimal>,
+    pub radius_meters: Option<BigDecimal>,
+    pub vertices: Option<Vec<GeofenceVertex>>,
+    /// `[min_lat, max_lat, min_long, max_long]`
+    pub bounds: [f64; 4],
+    pub address_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterGeofenceDTO {
+    pub id: String,
+    pub name: String,
+    pub kind: String,
+    pub shape: String,
+    pub center_lat: Option<BigDecimal>,
+    pub center_long: Option<BigDecimal>,
+    pub radius_meters: Option<BigDecimal>,
+    pub vertices: Option<Vec<GeofenceVertex>>,
+    pub address_id: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl FilterGeofenceDTO {
+    pub fn filter_geofence(geofence: &Geofence) -> Self {
+        FilterGeofenceDTO {
+            id: geofence.id.to_string(),
+            name: geofence.name.to_owned(),
+            kind: geofence.kind.to_owned(),
+            shape: geofence.shape.to_owned(),
+            center_lat: geofence.center_lat.to_owned(),
+            center_long: geofence.center_long.to_owned(),
+            radius_meters: geofence.radius_meters.to_owned(),
+            vertices: geofence
+                .vertices
+                .as_ref()
+                .map(|vertices| vertices.0.to_owned()),
+            address_id: geofence.address_id.map(|id| id.to_string()),
+            created_at: geofence.created_at.to_owned(),
+            updated_at: geofence.updated_at.to_owned(),
+        }
+    }
+
+    pub fn filter_geofences(geofences: &[Geofence]) -> Vec<FilterGeofenceDTO> {
+        geofences
+            .iter()
+            .map(FilterGeofenceDTO::filter_geofence)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeofenceListResponseDTO {
+    pub geofences: Vec<FilterGeofenceDTO>,
+    pub results: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveGeofenceEventParamsDTO {
+    pub geofence_id: Uuid,
+    pub event_type: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub occurred_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterGeofenceEventDTO {
+    pub id: String,
+    pub geofence_id: String,
+    pub route_id: String,
+    pub vehicle_id: String,
+    pub event_type: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub occurred_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl FilterGeofenceEventDTO {
+    pub fn filter_geofence_event(event: &GeofenceEvent) -> Self {
+        FilterGeofenceEventDTO {
+            id: event.id.to_string(),
+            geofence_id: event.geofence_id.to_string(),
+            route_id: event.route_id.to_string(),
+            vehicle_id: event.vehicle_id.to_string(),
+            event_type: event.event_type.to_owned(),
+            latitude: event.latitude.to_owned(),
+            longitude: event.longitude.to_owned(),
+            occurred_at: event.occurred_at.to_owned(),
+            created_at: event.created_at.to_owned(),
+        }
+    }
+
+    pub fn filter_geofence_events(events: &[GeofenceEvent]) -> Vec<FilterGeofenceEventDTO> {
+        events
+            .iter()
+            .map(FilterGeofenceEventDTO::filter_geofence_event)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeofenceEventListResponseDTO {
+    pub events: Vec<FilterGeofenceEventDTO>,
+    pub results: usize,
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealthDTO {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ComponentHealthDTO {
+    pub fn up() -> Self {
+        ComponentHealthDTO {
+            status: HealthStatus::Up,
+            latency_ms: None,
+            message: None,
+            details: None,
+        }
+    }
+
+    pub fn down(message: impl Into<String>) -> Self {
+        ComponentHealthDTO {
+            status: HealthStatus::Down,
+            message: Some(message.into()),
+            ..ComponentHealthDTO::up()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LivenessResponseDTO {
+    pub status: HealthStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponseDTO {
+    pub status: HealthStatus,
+    pub components: BTreeMap<String, ComponentHealthDTO>,
+}
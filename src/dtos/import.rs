@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors};
+
+use crate::{
+    error::validation_fields,
+    utils::{
+        i18n::{translate, Translate},
+        string::to_camel_case,
+    },
+};
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportQueryDTO {
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRowErrorDTO {
+    pub line: u64,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReportDTO {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub imported: usize,
+    pub errors: Vec<ImportRowErrorDTO>,
+}
+
+impl ImportReportDTO {
+    pub fn new(dry_run: bool, total_rows: usize) -> Self {
+        ImportReportDTO {
+            dry_run,
+            total_rows,
+            valid_rows: 0,
+            imported: 0,
+            errors: vec![],
+        }
+    }
+
+    pub fn add_error(&mut self, line: u64, field: Option<&str>, message: impl Translate) {
+        self.errors.push(ImportRowErrorDTO {
+            line,
+            field: field.map(to_camel_case),
+            message: message.translate(),
+        });
+    }
+
+    /// Runs the `Validate` implementation of the row and records every field
+    /// error. Returns `true` when the row is valid.
+    pub fn validate<T: Validate>(&mut self, line: u64, row: &T) -> bool {
+        match row.validate() {
+            Ok(_) => true,
+            Err(errors) => {
+                self.add_validation_errors(line, &errors);
+                false
+            }
+        }
+    }
+
+    fn add_validation_errors(&mut self, line: u64, errors: &ValidationErrors) {
+        for (field, messages) in validation_fields(errors) {
+            for message in messages {
+                self.errors.push(ImportRowErrorDTO {
+                    line,
+                    field: Some(field.clone()),
+                    message: translate(&message),
+                });
+            }
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
+/// How many rows an upsert inserted, updated or left unchanged.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpsertCountsDTO {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl UpsertCountsDTO {
+    /// Counts the flags returned by an `upsert_*` method for `total` rows:
+    /// `true` for inserted rows, `false` for updated ones, and nothing for
+    /// rows that were already up to date.
+    pub fn from_flags(total: usize, changed: &[bool]) -> Self {
+        let inserted = changed.iter().filter(|inserted| **inserted).count();
+
+        UpsertCountsDTO {
+            inserted,
+            updated: changed.len() - inserted,
+            unchanged: total - changed.len(),
+        }
+    }
+}
+
+/// The report of `import geography`. The counts are only set once the states
+/// and cities were saved.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeographyImportReportDTO {
+    #[serde(flatten)]
+    pub report: ImportReportDTO,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub states: Option<UpsertCountsDTO>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cities: Option<UpsertCountsDTO>,
+}
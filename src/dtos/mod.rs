@@ -1,9 +1,17 @@
 pub mod address;
+pub mod admin;
+pub mod audit;
 pub mod city;
 pub mod collaborator;
 pub mod country;
 pub mod driver;
+pub mod geocoding;
+pub mod geofence;
+pub mod health;
+pub mod import;
 pub mod request;
 pub mod route;
 pub mod state;
 pub mod vehicle;
+pub mod webhook;
+pub mod zip_code;
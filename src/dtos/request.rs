@@ -1,11 +1,74 @@
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::utils::{
+    export::ExportFormat,
+    spatial::{is_valid_bbox, parse_bbox, SpatialFilter},
+};
+
 #[derive(Serialize, Deserialize, Validate)]
 pub struct RequestQueryDTO {
-    #[validate(range(min = 1))]
+    #[validate(range(min = 1, message = "Page must be greater than or equal to 1"))]
     pub page: Option<usize>,
 
-    #[validate(range(min = 1, max = 50))]
+    #[validate(range(min = 1, max = 50, message = "Limit must be between 1 and 50"))]
     pub limit: Option<usize>,
+
+    pub format: Option<ExportFormat>,
+}
+
+/// Restricts a listing to the points within `radius` meters of `lat`/`long`,
+/// or inside `bbox`.
+#[derive(Serialize, Deserialize, Validate)]
+pub struct SpatialQueryDTO {
+    #[validate(range(
+        min = -90.0,
+        max = 90.0,
+        message = "Latitude must be between -90 and 90"
+    ))]
+    pub lat: Option<f64>,
+
+    #[validate(range(
+        min = -180.0,
+        max = 180.0,
+        message = "Longitude must be between -180 and 180"
+    ))]
+    pub long: Option<f64>,
+
+    #[validate(range(
+        min = 1.0,
+        max = 50000.0,
+        message = "Radius must be between 1 and 50000 meters"
+    ))]
+    pub radius: Option<f64>,
+
+    /// `minLong,minLat,maxLong,maxLat`
+    #[validate(custom(
+        function = "is_valid_bbox",
+        message = "Bounding box must be minLong,minLat,maxLong,maxLat"
+    ))]
+    pub bbox: Option<String>,
+}
+
+impl SpatialQueryDTO {
+    /// Returns the area to filter by, if any, or why the parameters don't
+    /// describe one. Expects the DTO to be validated already.
+    pub fn spatial_filter(&self) -> Result<Option<SpatialFilter>, &'static str> {
+        let radius = match (self.lat, self.long, self.radius) {
+            (Some(latitude), Some(longitude), Some(meters)) => Some(SpatialFilter::Radius {
+                latitude,
+                longitude,
+                meters,
+            }),
+            (None, None, None) => None,
+            _ => return Err("lat, long and radius must be given together"),
+        };
+
+        match (radius, self.bbox.as_deref()) {
+            (Some(_), Some(_)) => Err("Filter either by radius or by bbox, not both"),
+            (Some(radius), None) => Ok(Some(radius)),
+            (None, Some(bbox)) => Ok(parse_bbox(bbox)),
+            (None, None) => Ok(None),
+        }
+    }
 }
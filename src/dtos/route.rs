@@ -1,11 +1,16 @@
 use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
-    models::route::{Route, RouteStatus},
-    utils::string::is_valid_uuid,
+    dtos::geofence::FilterGeofenceEventDTO,
+    models::route::{Route, RouteEvent, RoutePosition, RouteStatus},
+    utils::{
+        eta::RouteProgress,
+        string::{is_valid_uuid, is_valid_uuid_list, parse_uuid_list},
+    },
 };
 
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
@@ -178,3 +183,192 @@ pub struct RegisterRandomRouteDTO {
     #[validate(custom(function = "is_valid_uuid", message = "Driver ID must be a valid UUID"))]
     pub driver_id: String,
 }
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterRoutePositionDTO {
+    #[validate(range(
+        min = -90.0,
+        max = 90.0,
+        message = "Latitude must be between -90 and 90"
+    ))]
+    pub lat: f64,
+
+    #[validate(range(
+        min = -180.0,
+        max = 180.0,
+        message = "Longitude must be between -180 and 180"
+    ))]
+    pub long: f64,
+
+    /// When the position was taken. Defaults to when it is received.
+    pub recorded_at: Option<NaiveDateTime>,
+}
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterRoutePositionsDTO {
+    #[validate(
+        length(
+            min = 1,
+            max = 500,
+            message = "Positions must have between 1 and 500 items"
+        ),
+        nested
+    )]
+    pub positions: Vec<RegisterRoutePositionDTO>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveRoutePositionParamsDTO {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub recorded_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterRoutePositionDTO {
+    pub id: String,
+    pub route_id: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub recorded_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl FilterRoutePositionDTO {
+    pub fn filter_route_position(position: &RoutePosition) -> Self {
+        FilterRoutePositionDTO {
+            id: position.id.to_string(),
+            route_id: position.route_id.to_string(),
+            latitude: position.latitude.to_owned(),
+            longitude: position.longitude.to_owned(),
+            recorded_at: position.recorded_at.to_owned(),
+            created_at: position.created_at.to_owned(),
+        }
+    }
+
+    pub fn filter_route_positions(positions: &[RoutePosition]) -> Vec<FilterRoutePositionDTO> {
+        positions
+            .iter()
+            .map(FilterRoutePositionDTO::filter_route_position)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoutePositionListResponseDTO {
+    pub positions: Vec<FilterRoutePositionDTO>,
+    pub results: usize,
+}
+
+/// The positions saved for a route, with the geofences they entered or left.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutePositionsResponseDTO {
+    pub positions: Vec<FilterRoutePositionDTO>,
+    pub geofence_events: Vec<FilterGeofenceEventDTO>,
+}
+
+/// How far a route is from its final coordinates and when it should arrive.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteEtaDTO {
+    pub route_id: String,
+    /// The latest known position, or the start of the route before any was
+    /// recorded.
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub position_recorded_at: Option<NaiveDateTime>,
+    /// The straight-line distance left, in meters.
+    pub remaining_distance: f64,
+    pub percent_complete: f64,
+    /// The average speed over the recent positions, in meters per second.
+    pub average_speed: Option<f64>,
+    pub remaining_seconds: Option<i64>,
+    pub estimated_arrival: Option<NaiveDateTime>,
+}
+
+impl RouteEtaDTO {
+    pub fn from_progress(
+        route: &Route,
+        (latitude, longitude): (BigDecimal, BigDecimal),
+        position_recorded_at: Option<NaiveDateTime>,
+        progress: RouteProgress,
+    ) -> Self {
+        RouteEtaDTO {
+            route_id: route.id.to_string(),
+            latitude,
+            longitude,
+            position_recorded_at,
+            remaining_distance: progress.remaining_distance,
+            percent_complete: progress.percent_complete,
+            average_speed: progress.average_speed,
+            remaining_seconds: progress.remaining_seconds,
+            estimated_arrival: progress.estimated_arrival,
+        }
+    }
+}
+
+/// Selects the routes whose events are streamed: those in `route_ids` or
+/// driven by the vehicles in `vehicle_ids`, both comma-separated. Without
+/// any ID, the events of every route are streamed.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RouteEventQueryDTO {
+    #[validate(custom(
+        function = "is_valid_uuid_list",
+        message = "Route IDs must be up to 100 UUIDs separated by commas"
+    ))]
+    pub route_ids: Option<String>,
+
+    #[validate(custom(
+        function = "is_valid_uuid_list",
+        message = "Vehicle IDs must be up to 100 UUIDs separated by commas"
+    ))]
+    pub vehicle_ids: Option<String>,
+
+    /// Resumes after this event when the `Last-Event-ID` header isn't sent.
+    pub last_event_id: Option<i64>,
+}
+
+impl RouteEventQueryDTO {
+    /// The parsed `route_ids`, once validated.
+    pub fn route_ids(&self) -> Vec<Uuid> {
+        self.route_ids
+            .as_deref()
+            .and_then(parse_uuid_list)
+            .unwrap_or_default()
+    }
+
+    /// The parsed `vehicle_ids`, once validated.
+    pub fn vehicle_ids(&self) -> Vec<Uuid> {
+        self.vehicle_ids
+            .as_deref()
+            .and_then(parse_uuid_list)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterRouteEventDTO {
+    pub id: i64,
+    pub route_id: String,
+    pub vehicle_id: String,
+    pub event_type: String,
+    pub data: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl FilterRouteEventDTO {
+    pub fn filter_route_event(event: &RouteEvent) -> Self {
+        FilterRouteEventDTO {
+            id: event.id,
+            route_id: event.route_id.to_string(),
+            vehicle_id: event.vehicle_id.to_string(),
+            event_type: event.event_type.to_owned(),
+            data: event.data.to_owned(),
+            created_at: event.created_at,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use validator::Validate;
 
 use crate::{models::state::State, utils::string::is_valid_uuid};
@@ -58,3 +59,31 @@ pub struct StateListResponseDTO {
     pub states: Vec<FilterStateDTO>,
     pub results: usize,
 }
+
+/// Narrows the list of states by name and country.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct StateQueryDTO {
+    /// Keeps the names starting with this prefix, regardless of case and
+    /// accents.
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Name must have between 1 and 100 characters"
+    ))]
+    pub name: Option<String>,
+
+    #[validate(custom(
+        function = "is_valid_uuid",
+        message = "Country ID must be a valid UUID"
+    ))]
+    pub country_id: Option<String>,
+}
+
+impl StateQueryDTO {
+    /// The parsed `country_id`, once validated.
+    pub fn country_id(&self) -> Option<Uuid> {
+        self.country_id
+            .as_deref()
+            .and_then(|country_id| Uuid::parse_str(country_id).ok())
+    }
+}
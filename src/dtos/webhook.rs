@@ -0,0 +1,159 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    models::webhook::{WebhookDelivery, WebhookSubscription},
+    utils::webhook::{
+        is_valid_webhook_delivery_status, is_valid_webhook_event_types, is_valid_webhook_url,
+    },
+};
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterWebhookSubscriptionDTO {
+    #[validate(
+        length(max = 2048, message = "URL must have up to 2048 characters"),
+        custom(
+            function = "is_valid_webhook_url",
+            message = "URL must be an absolute http or https URL"
+        )
+    )]
+    pub url: String,
+
+    #[validate(
+        length(
+            min = 1,
+            max = 10,
+            message = "Event types must have between 1 and 10 items"
+        ),
+        custom(
+            function = "is_valid_webhook_event_types",
+            message = "Event types must be among route.created, route.started, route.finished, driver.cnh_expired"
+        )
+    )]
+    pub event_types: Vec<String>,
+
+    /// The key payloads are signed with. A random one is generated when
+    /// creating a subscription without it, and kept when updating one.
+    #[validate(length(
+        min = 16,
+        max = 128,
+        message = "Secret must have between 16 and 128 characters"
+    ))]
+    pub secret: Option<String>,
+
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveWebhookSubscriptionParamsDTO {
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub secret: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterWebhookSubscriptionDTO {
+    pub id: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    /// Only returned when the subscription is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl FilterWebhookSubscriptionDTO {
+    pub fn filter_webhook_subscription(subscription: &WebhookSubscription) -> Self {
+        FilterWebhookSubscriptionDTO {
+            id: subscription.id.to_string(),
+            url: subscription.url.to_owned(),
+            event_types: subscription.event_types.to_owned(),
+            secret: None,
+            active: subscription.active,
+            created_at: subscription.created_at.to_owned(),
+            updated_at: subscription.updated_at.to_owned(),
+        }
+    }
+
+    pub fn filter_webhook_subscriptions(
+        subscriptions: &[WebhookSubscription],
+    ) -> Vec<FilterWebhookSubscriptionDTO> {
+        subscriptions
+            .iter()
+            .map(FilterWebhookSubscriptionDTO::filter_webhook_subscription)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookSubscriptionListResponseDTO {
+    pub subscriptions: Vec<FilterWebhookSubscriptionDTO>,
+    pub results: usize,
+}
+
+/// Narrows the delivery log by status.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct WebhookDeliveryQueryDTO {
+    #[validate(custom(
+        function = "is_valid_webhook_delivery_status",
+        message = "Status must be one of pending, delivered, failed"
+    ))]
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterWebhookDeliveryDTO {
+    pub id: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub subscription_id: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl FilterWebhookDeliveryDTO {
+    pub fn filter_webhook_delivery(delivery: &WebhookDelivery) -> Self {
+        FilterWebhookDeliveryDTO {
+            id: delivery.id.to_string(),
+            event_id: delivery.event_id.to_string(),
+            event_type: delivery.event_type.to_owned(),
+            subscription_id: delivery.subscription_id.to_string(),
+            status: delivery.status.to_owned(),
+            attempts: delivery.attempts,
+            next_attempt_at: delivery.next_attempt_at.to_owned(),
+            last_response_status: delivery.last_response_status,
+            last_error: delivery.last_error.to_owned(),
+            delivered_at: delivery.delivered_at.to_owned(),
+            created_at: delivery.created_at.to_owned(),
+            updated_at: delivery.updated_at.to_owned(),
+        }
+    }
+
+    pub fn filter_webhook_deliveries(
+        deliveries: &[WebhookDelivery],
+    ) -> Vec<FilterWebhookDeliveryDTO> {
+        deliveries
+            .iter()
+            .map(FilterWebhookDeliveryDTO::filter_webhook_delivery)
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveryListResponseDTO {
+    pub deliveries: Vec<FilterWebhookDeliveryDTO>,
+    pub results: usize,
+}
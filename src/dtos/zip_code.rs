@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{models::zip_code::ZipCode, utils::string::is_valid_zip_code};
+
+/// A row of a CEP CSV import. The city is referenced by its IBGE code.
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportZipCodeRowDTO {
+    #[validate(custom(
+        function = "is_valid_zip_code",
+        message = "Zip code must have 8 digits"
+    ))]
+    pub zip_code: String,
+
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Address must have a maximum of 100 characters"
+    ))]
+    pub address: Option<String>,
+
+    #[validate(length(
+        min = 1,
+        max = 60,
+        message = "Neighbourhood must have a maximum of 60 characters"
+    ))]
+    pub neighbourhood: Option<String>,
+
+    #[validate(length(min = 7, max = 7, message = "Code must be 7 characters long"))]
+    pub city_code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterZipCodeDTO {
+    pub zip_code: String,
+    pub address: Option<String>,
+    pub neighbourhood: Option<String>,
+    pub city_id: String,
+    /// The provider that answered the lookup.
+    pub source: String,
+}
+
+impl FilterZipCodeDTO {
+    pub fn filter_zip_code(zip_code: &ZipCode, source: &str) -> Self {
+        FilterZipCodeDTO {
+            zip_code: zip_code.zip_code.to_owned(),
+            address: zip_code.address.to_owned(),
+            neighbourhood: zip_code.neighbourhood.to_owned(),
+            city_id: zip_code.city_id.to_string(),
+            source: source.to_string(),
+        }
+    }
+}
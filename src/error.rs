@@ -1,7 +1,20 @@
-use std::fmt::{self};
+use std::{
+    collections::BTreeMap,
+    fmt::{self},
+};
 
 use actix_web::{HttpResponse, ResponseError};
 use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use validator::ValidationErrors;
+
+use crate::{
+    middlewares::request_id::CURRENT_REQUEST_ID,
+    utils::{
+        i18n::{translate, translate_error},
+        string::to_camel_case,
+    },
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
@@ -16,11 +29,19 @@ impl fmt::Display for ErrorResponse {
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResponseDetails {
     pub status: String,
+    /// The HTTP status code, as a string.
     pub code: String,
+    /// The stable error code, e.g. `COUNTRY_NOT_FOUND`.
+    pub error: String,
     pub message: String,
     pub hint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -28,7 +49,7 @@ pub struct Response {
     pub error: ResponseDetails,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ErrorMessage {
     ServerError,
     CountryExist,
@@ -39,6 +60,7 @@ pub enum ErrorMessage {
     CityNotFound,
     AddressExist,
     AddressNotFound,
+    ZipCodeNotFound,
     CollaboratorExist,
     CollaboratorNotFound,
     VehicleExist,
@@ -46,11 +68,21 @@ pub enum ErrorMessage {
     VehicleDocumentExist,
     VehicleDocumentNotFound,
     RouteNotFound,
+    RoutePositionsOutOfOrder,
+    GeofenceNotFound,
+    WebhookSubscriptionNotFound,
+    WebhookDeliveryNotFound,
+    WebhookDeliveryNotRetryable,
     RouteStatusExist,
     RouteStatusNotFound,
     DriverExist,
     DriverNotFound,
     CnhTypeNotFound,
+    PreconditionFailed,
+    IdempotencyKeyInvalid,
+    IdempotencyKeyInProgress,
+    IdempotencyKeyMismatch,
+    IdempotencyPayloadTooLarge,
 }
 
 impl fmt::Display for ErrorMessage {
@@ -66,7 +98,46 @@ impl From<ErrorMessage> for String {
 }
 
 impl ErrorMessage {
-    fn to_str(&self) -> &str {
+    /// A stable identifier clients can switch on. Unlike the message, it never
+    /// changes once released.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorMessage::ServerError => "SERVER_ERROR",
+            ErrorMessage::CountryExist => "COUNTRY_EXISTS",
+            ErrorMessage::CountryNotFound => "COUNTRY_NOT_FOUND",
+            ErrorMessage::StateExist => "STATE_EXISTS",
+            ErrorMessage::StateNotFound => "STATE_NOT_FOUND",
+            ErrorMessage::CityExist => "CITY_EXISTS",
+            ErrorMessage::CityNotFound => "CITY_NOT_FOUND",
+            ErrorMessage::AddressExist => "ADDRESS_EXISTS",
+            ErrorMessage::AddressNotFound => "ADDRESS_NOT_FOUND",
+            ErrorMessage::ZipCodeNotFound => "ZIP_CODE_NOT_FOUND",
+            ErrorMessage::CollaboratorExist => "COLLABORATOR_EXISTS",
+            ErrorMessage::CollaboratorNotFound => "COLLABORATOR_NOT_FOUND",
+            ErrorMessage::VehicleExist => "VEHICLE_EXISTS",
+            ErrorMessage::VehicleNotFound => "VEHICLE_NOT_FOUND",
+            ErrorMessage::VehicleDocumentExist => "VEHICLE_DOCUMENT_EXISTS",
+            ErrorMessage::VehicleDocumentNotFound => "VEHICLE_DOCUMENT_NOT_FOUND",
+            ErrorMessage::RouteNotFound => "ROUTE_NOT_FOUND",
+            ErrorMessage::RoutePositionsOutOfOrder => "ROUTE_POSITIONS_OUT_OF_ORDER",
+            ErrorMessage::GeofenceNotFound => "GEOFENCE_NOT_FOUND",
+            ErrorMessage::WebhookSubscriptionNotFound => "WEBHOOK_SUBSCRIPTION_NOT_FOUND",
+            ErrorMessage::WebhookDeliveryNotFound => "WEBHOOK_DELIVERY_NOT_FOUND",
+            ErrorMessage::WebhookDeliveryNotRetryable => "WEBHOOK_DELIVERY_NOT_RETRYABLE",
+            ErrorMessage::RouteStatusExist => "ROUTE_STATUS_EXISTS",
+            ErrorMessage::RouteStatusNotFound => "ROUTE_STATUS_NOT_FOUND",
+            ErrorMessage::DriverExist => "DRIVER_EXISTS",
+            ErrorMessage::DriverNotFound => "DRIVER_NOT_FOUND",
+            ErrorMessage::CnhTypeNotFound => "CNH_TYPE_NOT_FOUND",
+            ErrorMessage::PreconditionFailed => "PRECONDITION_FAILED",
+            ErrorMessage::IdempotencyKeyInvalid => "IDEMPOTENCY_KEY_INVALID",
+            ErrorMessage::IdempotencyKeyInProgress => "IDEMPOTENCY_KEY_IN_PROGRESS",
+            ErrorMessage::IdempotencyKeyMismatch => "IDEMPOTENCY_KEY_MISMATCH",
+            ErrorMessage::IdempotencyPayloadTooLarge => "IDEMPOTENCY_PAYLOAD_TOO_LARGE",
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
         match self {
             ErrorMessage::ServerError => "A server error occurred. Please try again later",
             ErrorMessage::CountryExist => "A country with the provided data already exists",
@@ -77,6 +148,7 @@ impl ErrorMessage {
             ErrorMessage::CityNotFound => "The city with the provided ID does not exist in our records. Please verify and try again",
             ErrorMessage::AddressExist => "An address with the provided details (address, number, zipCode) already exists",
             ErrorMessage::AddressNotFound => "The address with the provided ID does not exist in our records. Please verify and try again",
+            ErrorMessage::ZipCodeNotFound => "The provided zip code was not found. Please verify and try again",
             ErrorMessage::CollaboratorExist => "A collaborator with the provided email or cpf already exists",
             ErrorMessage::CollaboratorNotFound => "The collaborator with the provided ID, email or cpf does not exist in our records. Please verify and try again",
             ErrorMessage::VehicleExist => "A vehicle with the provided data already exists",
@@ -84,15 +156,25 @@ impl ErrorMessage {
             ErrorMessage::VehicleDocumentExist => "A document for the vehicle with the provided chassisNumber, registrationNumber or plate already exists",
             ErrorMessage::VehicleDocumentNotFound => "The document for the vehicle with the provided ID does not exist in our records. Please verify and try again",
             ErrorMessage::RouteNotFound => "The route with the provided ID does not exist in our records. Please verify and try again",
+            ErrorMessage::RoutePositionsOutOfOrder => "The positions are older than the latest position recorded for the route",
+            ErrorMessage::GeofenceNotFound => "The geofence with the provided ID does not exist in our records. Please verify and try again",
+            ErrorMessage::WebhookSubscriptionNotFound => "The webhook subscription with the provided ID does not exist in our records. Please verify and try again",
+            ErrorMessage::WebhookDeliveryNotFound => "The webhook delivery with the provided ID does not exist in our records. Please verify and try again",
+            ErrorMessage::WebhookDeliveryNotRetryable => "The webhook delivery is pending and may be being sent",
             ErrorMessage::RouteStatusExist => "A status with the provided data already exists for this route",
             ErrorMessage::RouteStatusNotFound => "The status for the route with the provided ID does not exist in our records. Please verify and try again",
             ErrorMessage::DriverNotFound => "The driver with the provided ID does not exist in our records",
             ErrorMessage::DriverExist => "There is already a driver with the provided data",
-            ErrorMessage::CnhTypeNotFound => "The cnh type with the provided ID does not exist in our records"
+            ErrorMessage::CnhTypeNotFound => "The cnh type with the provided ID or code does not exist in our records",
+            ErrorMessage::PreconditionFailed => "The resource has been modified since it was last retrieved",
+            ErrorMessage::IdempotencyKeyInvalid => "The Idempotency-Key header must have between 1 and 255 characters",
+            ErrorMessage::IdempotencyKeyInProgress => "A request with the provided Idempotency-Key is still being processed",
+            ErrorMessage::IdempotencyKeyMismatch => "The provided Idempotency-Key was already used with a different request payload",
+            ErrorMessage::IdempotencyPayloadTooLarge => "Requests with an Idempotency-Key must have a body of up to 2 MiB"
         }
     }
 
-    fn hint(&self) -> &str {
+    pub fn hint(self) -> &'static str {
         match self {
             ErrorMessage::ServerError => "Check server logs for more details and ensure the server is running correctly",
             ErrorMessage::CountryExist => "Verify the country data you are trying to add is unique and does not already exist",
@@ -103,6 +185,7 @@ impl ErrorMessage {
             ErrorMessage::CityNotFound => "Ensure the cityId is correct and exists in the database. Use 'GET /api/v1/cities' to retrieve available city IDs",
             ErrorMessage::AddressExist => "Verify the address details (address, number, zipCode) are unique and do not already exist",
             ErrorMessage::AddressNotFound => "Ensure the addressId is correct and exists in the database. Use 'GET /api/v1/addresses' to retrieve available address IDs",
+            ErrorMessage::ZipCodeNotFound => "Ensure the zip code is correct. It must have 8 digits, with or without the hyphen",
             ErrorMessage::CollaboratorExist => "Verify the collaborator details (email, cpf) are unique and do not already exist",
             ErrorMessage::CollaboratorNotFound => "Ensure the collaboratorId, email or cpf is correct and exists in the database. Use 'GET /api/v1/collaborators' to retrieve available collaborator IDs",
             ErrorMessage::VehicleExist => "Ensure the vehicle information is unique and does not already exist",
@@ -110,72 +193,148 @@ impl ErrorMessage {
             ErrorMessage::VehicleDocumentExist => "Verify the vehicle document details (chassisNumber, registrationNumber, plate) are unique and do not already exist",
             ErrorMessage::VehicleDocumentNotFound => "Ensure the vehicleId, chassisNumber, registrationNumber or plate is correct and exists in the database. Use 'GET /api/v1/vehicles' and 'GET /api/v1/vehicles/{vehicleId}/documents' to retrieve available vehicle IDs and documents",
             ErrorMessage::RouteNotFound => "Ensure the routeId is correct and exists in the database. Use 'GET /api/v1/routes' to retrieve available route IDs",
+            ErrorMessage::RoutePositionsOutOfOrder => "Send the positions in the order they were recorded. Use 'GET /api/v1/routes/{routeId}/positions' to retrieve the latest one",
+            ErrorMessage::GeofenceNotFound => "Ensure the geofenceId is correct and exists in the database. Use 'GET /api/v1/geofences' to retrieve available geofence IDs",
+            ErrorMessage::WebhookSubscriptionNotFound => "Ensure the subscriptionId is correct and exists in the database. Use 'GET /api/v1/webhooks' to retrieve available subscription IDs",
+            ErrorMessage::WebhookDeliveryNotFound => "Ensure the deliveryId is correct and belongs to the subscription. Use 'GET /api/v1/webhooks/{subscriptionId}/deliveries' to retrieve its deliveries",
+            ErrorMessage::WebhookDeliveryNotRetryable => "Only failed deliveries, or pending ones past their 'nextAttemptAt', can be retried. Try again after it",
             ErrorMessage::RouteStatusExist => "Verify the route status code is unique and does not already exist",
             ErrorMessage::RouteStatusNotFound => "Ensure the routeId is correct and exists in the database. Use 'GET /api/v1/routes' to retrieve available route IDs",
             ErrorMessage::DriverExist => "Ensure the cnhNumber and collaboratorId information are uique and do not already exist",
             ErrorMessage::DriverNotFound => "Ensure the driverId, cnhNumber or collaboratorId are correct and exists in the database. Use the 'GET /api/v1/collaborators' endpoint to retrieve available collaborator IDs and the 'GET /api/v1/collaborators/drivers' to retrieve available driver IDs",
-            ErrorMessage::CnhTypeNotFound => "Something"
+            ErrorMessage::CnhTypeNotFound => "Ensure the cnhTypeId or code is correct and exists in the database. Use 'GET /api/v1/collaborators/drivers/cnh' to retrieve available CNH types",
+            ErrorMessage::PreconditionFailed => "Retrieve the resource again to get its current ETag and retry the request with an updated If-Match header",
+            ErrorMessage::IdempotencyKeyInvalid => "Send a unique value, such as a UUID, in the Idempotency-Key header",
+            ErrorMessage::IdempotencyKeyInProgress => "Wait for the original request to finish and retry with the same Idempotency-Key",
+            ErrorMessage::IdempotencyKeyMismatch => "Use a new Idempotency-Key for a different request or resend the original payload",
+            ErrorMessage::IdempotencyPayloadTooLarge => "Split the payload into smaller requests, each with its own Idempotency-Key"
         }
     }
 }
 
+pub const BAD_REQUEST: &str = "BAD_REQUEST";
+pub const VALIDATION_FAILED: &str = "VALIDATION_FAILED";
+
 #[derive(Debug, Clone)]
 pub struct HttpError {
     pub status: u16,
+    pub code: String,
     pub message: String,
     pub hint: String,
+    pub fields: Option<BTreeMap<String, Vec<String>>>,
+    /// The error the message and hint come from, which translates them.
+    /// `None` for free-text messages, translated by their English text.
+    pub error_message: Option<ErrorMessage>,
 }
 
 impl HttpError {
+    /// Logs `message` and answers with the generic server error, so internal
+    /// details such as SQL never reach the client.
     pub fn server_error(message: impl Into<String>) -> Self {
-        HttpError {
-            message: message.into(),
-            hint: ErrorMessage::ServerError.hint().to_string(),
-            status: 500,
-        }
+        error!("{}", message.into());
+
+        HttpError::from_error_message(ErrorMessage::ServerError)
     }
 
     pub fn bad_request(message: impl Into<String>) -> Self {
         HttpError {
+            status: 400,
+            code: BAD_REQUEST.to_string(),
             message: message.into(),
             hint: "Check the request parameters and try again".to_string(),
+            fields: None,
+            error_message: None,
+        }
+    }
+
+    pub fn validation_error(errors: ValidationErrors) -> Self {
+        HttpError {
             status: 400,
+            code: VALIDATION_FAILED.to_string(),
+            message: "One or more fields are invalid".to_string(),
+            hint: "Check the fields listed in the response and try again".to_string(),
+            fields: Some(validation_fields(&errors)),
+            error_message: None,
         }
     }
 
-    pub fn unique_constraint_violation(message: impl Into<String>) -> Self {
+    /// Used when the request references a related resource that does not
+    /// exist, which is a client error rather than a missing route resource.
+    pub fn invalid_reference(error_message: ErrorMessage) -> Self {
+        HttpError {
+            status: 400,
+            ..HttpError::from_error_message(error_message)
+        }
+    }
+
+    pub fn unique_constraint_violation(error_message: ErrorMessage) -> Self {
         HttpError {
-            message: message.into(),
-            hint: "Ensure the data you are trying to add is unique".to_string(),
             status: 409,
+            ..HttpError::from_error_message(error_message)
         }
     }
 
     pub fn from_error_message(error_message: ErrorMessage) -> Self {
         HttpError {
-            message: error_message.to_string(),
-            hint: error_message.hint().to_string(),
             status: match error_message {
                 ErrorMessage::ServerError => 500,
                 ErrorMessage::CountryExist
                 | ErrorMessage::StateExist
                 | ErrorMessage::CityExist
-                | ErrorMessage::AddressExist => 409,
+                | ErrorMessage::AddressExist
+                | ErrorMessage::CollaboratorExist
+                | ErrorMessage::VehicleExist
+                | ErrorMessage::VehicleDocumentExist
+                | ErrorMessage::RouteStatusExist
+                | ErrorMessage::DriverExist
+                | ErrorMessage::RoutePositionsOutOfOrder
+                | ErrorMessage::WebhookDeliveryNotRetryable => 409,
+                ErrorMessage::PreconditionFailed => 412,
+                ErrorMessage::IdempotencyKeyInvalid => 400,
+                ErrorMessage::IdempotencyKeyInProgress => 409,
+                ErrorMessage::IdempotencyKeyMismatch => 422,
+                ErrorMessage::IdempotencyPayloadTooLarge => 413,
                 _ => 404,
             },
+            code: error_message.code().to_string(),
+            message: error_message.to_string(),
+            hint: error_message.hint().to_string(),
+            fields: None,
+            error_message: Some(error_message),
         }
     }
 
     pub fn into_http_response(self) -> HttpResponse {
+        let request_id = CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok();
+
+        let (message, hint) = match self.error_message {
+            Some(error_message) => {
+                let (message, hint) = translate_error(error_message);
+
+                (message.to_string(), hint.to_string())
+            }
+            None => (translate(&self.message), translate(&self.hint)),
+        };
+
         let response = Response {
             error: ResponseDetails {
                 status: match self.status {
-                    400 | 409 => "fail".to_string(),
+                    400 | 409 | 412 | 413 | 422 => "fail".to_string(),
                     _ => "error".to_string(),
                 },
                 code: self.status.to_string(),
-                message: self.message,
-                hint: self.hint,
+                error: self.code,
+                message,
+                hint,
+                fields: self.fields.map(|fields| {
+                    fields
+                        .into_iter()
+                        .map(|(field, messages)| {
+                            (field, messages.iter().map(|m| translate(m)).collect())
+                        })
+                        .collect()
+                }),
+                request_id: request_id.clone(),
             },
         };
 
@@ -184,10 +343,13 @@ impl HttpError {
             401 => HttpResponse::Unauthorized().json(response),
             404 => HttpResponse::NotFound().json(response),
             409 => HttpResponse::Conflict().json(response),
+            412 => HttpResponse::PreconditionFailed().json(response),
+            413 => HttpResponse::PayloadTooLarge().json(response),
+            422 => HttpResponse::UnprocessableEntity().json(response),
             500 => HttpResponse::InternalServerError().json(response),
             _ => {
-                eprintln!(
-                    "Warning: Missing pattern match. Converted status code {} to 500",
+                warn!(
+                    "Missing pattern match. Converted status code {} to 500",
                     self.status
                 );
 
@@ -195,8 +357,11 @@ impl HttpError {
                     error: ResponseDetails {
                         status: "error".to_string(),
                         code: "500".to_string(),
-                        message: ErrorMessage::ServerError.to_string(),
-                        hint: ErrorMessage::ServerError.hint().to_string(),
+                        error: ErrorMessage::ServerError.code().to_string(),
+                        message: translate_error(ErrorMessage::ServerError).0.to_string(),
+                        hint: translate_error(ErrorMessage::ServerError).1.to_string(),
+                        fields: None,
+                        request_id,
                     },
                 })
             }
@@ -208,14 +373,20 @@ impl fmt::Display for HttpError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "HttpError: message: {}, status: {}",
-            self.message, self.status
+            "HttpError: code: {}, message: {}, status: {}",
+            self.code, self.message, self.status
         )
     }
 }
 
 impl std::error::Error for HttpError {}
 
+impl From<sqlx::Error> for HttpError {
+    fn from(error: sqlx::Error) -> Self {
+        HttpError::server_error(format!("Database error: {error}"))
+    }
+}
+
 impl ResponseError for HttpError {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
         let cloned = self.clone();
@@ -223,3 +394,26 @@ impl ResponseError for HttpError {
         cloned.into_http_response()
     }
 }
+
+/// Groups the messages of every invalid field under its camelCase name, the
+/// same name used in request bodies.
+pub fn validation_fields(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|error| {
+                    error
+                        .message
+                        .as_ref()
+                        .map(|message| message.to_string())
+                        .unwrap_or_else(|| error.code.to_string())
+                })
+                .collect();
+
+            (to_camel_case(field), messages)
+        })
+        .collect()
+}
@@ -0,0 +1,78 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::HeaderName,
+};
+
+pub const ACTOR: HeaderName = HeaderName::from_static("x-actor");
+
+tokio::task_local! {
+    /// Who is making the changes of the current task, recorded in the audit
+    /// log by the database connections it uses.
+    pub static CURRENT_ACTOR: String;
+}
+
+/// Runs every request with the actor named in the `X-Actor` header.
+///
+/// There is no authentication yet, so the actor is whatever the client
+/// declares. Requests without a usable header are recorded with no actor.
+pub struct Actor;
+
+impl<S, B> Transform<S, ServiceRequest> for Actor
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ActorMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ActorMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ActorMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ActorMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let actor = req
+            .headers()
+            .get(ACTOR)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| is_valid_actor(value))
+            .map(str::to_string)
+            .unwrap_or_default();
+
+        Box::pin(CURRENT_ACTOR.scope(actor, async move { service.call(req).await }))
+    }
+}
+
+/// The audit log keeps up to 200 characters of the actor.
+fn is_valid_actor(value: &str) -> bool {
+    !value.is_empty() && value.chars().count() <= 200
+}
@@ -0,0 +1,257 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    time::Duration,
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::PayloadError,
+    http::{
+        header::{self, HeaderName},
+        Method, StatusCode,
+    },
+    web, HttpMessage, HttpResponse,
+};
+use futures_util::{stream, StreamExt};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{
+    db::{client::DBClient, idempotency::IdempotencyKeyExt},
+    error::{ErrorMessage, HttpError},
+    AppState,
+};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENCY_REPLAYED: HeaderName = HeaderName::from_static("idempotency-replayed");
+
+/// The largest body buffered to be hashed, the default limit of
+/// `web::JsonConfig` and so the largest body any handler accepts.
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// How long a key stays reserved for a request that never stored its
+/// response, e.g. because the client went away or the instance stopped.
+const LOCK_LEASE: Duration = Duration::from_secs(5 * 60);
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Makes `POST` requests carrying an `Idempotency-Key` header safe to retry.
+///
+/// The first request for a key is executed and its response stored. Repeated
+/// requests with the same key and payload get the stored response back, while
+/// reusing the key with a different payload is rejected.
+pub struct Idempotency;
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = IdempotencyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let key = match req.headers().get(IDEMPOTENCY_KEY) {
+                Some(key) if req.method() == Method::POST => key,
+                _ => return Ok(service.call(req).await?.map_into_boxed_body()),
+            };
+
+            let key = match key.to_str() {
+                Ok(key) if !key.is_empty() && key.len() <= 255 => key.to_owned(),
+                _ => {
+                    return Err(
+                        HttpError::from_error_message(ErrorMessage::IdempotencyKeyInvalid).into(),
+                    )
+                }
+            };
+
+            let app_state = match req.app_data::<web::Data<AppState>>() {
+                Some(app_state) => app_state.clone(),
+                None => return Ok(service.call(req).await?.map_into_boxed_body()),
+            };
+
+            let method = req.method().to_string();
+            let path = req.path().to_owned();
+
+            // Buffer the body so it can be hashed and then handed back to the handler.
+            let mut payload = req.take_payload();
+            let mut buffer = web::BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk?;
+
+                if buffer.len() + chunk.len() > MAX_BODY_SIZE {
+                    return Err(HttpError::from_error_message(
+                        ErrorMessage::IdempotencyPayloadTooLarge,
+                    )
+                    .into());
+                }
+
+                buffer.extend_from_slice(&chunk);
+            }
+            let request_body = buffer.freeze();
+            let request_hash = hex::encode(Sha256::digest(&request_body));
+
+            let replay_body = request_body.clone();
+            req.set_payload(Payload::from(Box::pin(stream::once(async move {
+                Ok::<_, PayloadError>(replay_body)
+            }))
+                as Pin<Box<dyn futures_util::Stream<Item = _>>>));
+
+            let locked = app_state
+                .db_client
+                .lock_idempotency_key(
+                    &key,
+                    &method,
+                    &path,
+                    &request_hash,
+                    LOCK_LEASE.as_secs_f64(),
+                )
+                .await
+                .map_err(HttpError::from)?;
+
+            if locked.is_none() {
+                let stored = app_state
+                    .db_client
+                    .get_idempotency_key(&key, &method, &path)
+                    .await
+                    .map_err(HttpError::from)?;
+
+                return match stored {
+                    Some(stored) if stored.request_hash != request_hash => Err(
+                        HttpError::from_error_message(ErrorMessage::IdempotencyKeyMismatch).into(),
+                    ),
+                    Some(stored) => match (stored.status_code, stored.response_body) {
+                        (Some(status_code), Some(response_body)) => {
+                            let mut response = HttpResponse::build(
+                                StatusCode::from_u16(status_code as u16).unwrap_or(StatusCode::OK),
+                            );
+                            if let Some(content_type) = stored.content_type {
+                                response.insert_header((header::CONTENT_TYPE, content_type));
+                            }
+                            response.insert_header((IDEMPOTENCY_REPLAYED, "true"));
+
+                            Ok(req.into_response(response.body(response_body)))
+                        }
+                        _ => Err(HttpError::from_error_message(
+                            ErrorMessage::IdempotencyKeyInProgress,
+                        )
+                        .into()),
+                    },
+                    None => Err(HttpError::from_error_message(
+                        ErrorMessage::IdempotencyKeyInProgress,
+                    )
+                    .into()),
+                };
+            }
+
+            let response = match service.call(req).await {
+                Ok(response) => response,
+                Err(e) => {
+                    release_key(&app_state, &key, &method, &path).await;
+                    return Err(e);
+                }
+            };
+
+            let status = response.status();
+            let (request, response) = response.into_parts();
+            let (response, response_body) = response.into_parts();
+
+            let response_body = match body::to_bytes(response_body).await {
+                Ok(response_body) => response_body,
+                Err(e) => {
+                    release_key(&app_state, &key, &method, &path).await;
+                    return Err(HttpError::server_error(e.into().to_string()).into());
+                }
+            };
+
+            // Server errors are not stored so the client can retry with the same key.
+            if status.is_server_error() {
+                release_key(&app_state, &key, &method, &path).await;
+            } else {
+                let content_type = response
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned);
+
+                // The handler already made its changes, so its response is
+                // returned anyway. The key is taken again once its lease is
+                // over.
+                if let Err(e) = app_state
+                    .db_client
+                    .save_idempotency_response(
+                        &key,
+                        &method,
+                        &path,
+                        status.as_u16() as i16,
+                        content_type,
+                        &response_body,
+                    )
+                    .await
+                {
+                    warn!("Failed to store the response for the idempotency key {key}: {e}");
+                }
+            }
+
+            let response = response.set_body(response_body).map_into_boxed_body();
+
+            Ok(ServiceResponse::new(request, response))
+        })
+    }
+}
+
+async fn release_key(app_state: &AppState, key: &str, method: &str, path: &str) {
+    if let Err(e) = app_state
+        .db_client
+        .delete_idempotency_key(key, method, path)
+        .await
+    {
+        warn!("Failed to release the idempotency key {key}: {e}");
+    }
+}
+
+/// Deletes the keys past their retention every hour.
+pub async fn purge_idempotency_keys(db_client: DBClient, retention_hours: i32) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match db_client.purge_idempotency_keys(retention_hours).await {
+            Ok(deleted) if deleted > 0 => info!(deleted, "Old idempotency keys deleted."),
+            Ok(_) => {}
+            Err(e) => warn!("Error deleting old idempotency keys: {e}"),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{self, HeaderValue},
+    web,
+};
+
+use crate::{
+    utils::i18n::{Language, LANGUAGE},
+    AppState,
+};
+
+/// Negotiates the response language from the `Accept-Language` header.
+///
+/// The inner service runs with the language set in `i18n::LANGUAGE`, so
+/// errors are rendered in that language. Errors returned by inner middlewares
+/// are rendered here, while the language is still set.
+pub struct Locale;
+
+impl<S, B> Transform<S, ServiceRequest> for Locale
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = LocaleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LocaleMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct LocaleMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for LocaleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let default = req
+            .app_data::<web::Data<AppState>>()
+            .map(|app_state| app_state.env.default_language)
+            .unwrap_or(Language::En);
+
+        let language = Language::from_request(req.request(), default);
+
+        Box::pin(LANGUAGE.scope(language, async move {
+            let content_language = HeaderValue::from_static(language.tag());
+
+            match service.call(req).await {
+                Ok(response) => {
+                    let mut response = response.map_into_boxed_body();
+
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_LANGUAGE, content_language);
+
+                    Ok(response)
+                }
+                Err(e) => {
+                    let mut response = e.error_response();
+
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_LANGUAGE, content_language);
+
+                    Err(InternalError::from_response(e, response).into())
+                }
+            }
+        }))
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    time::Instant,
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    web,
+};
+
+use crate::{utils::metrics::UNMATCHED_ROUTE, AppState};
+
+/// Records the count and latency of every request in `AppState::metrics`.
+///
+/// Requests are labelled with their route pattern instead of their path, so
+/// `/api/v1/routes/{id}` is a single series no matter how many ids are used.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let metrics = req
+            .app_data::<web::Data<AppState>>()
+            .filter(|app_state| app_state.env.features.metrics)
+            .map(|app_state| app_state.metrics.clone());
+
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+        let started_at = Instant::now();
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+
+            if let Some(metrics) = metrics {
+                let status = match &result {
+                    Ok(response) => response.status(),
+                    Err(e) => e.as_response_error().status_code(),
+                };
+
+                metrics.observe_request(
+                    &method,
+                    &route,
+                    status.as_u16(),
+                    started_at.elapsed().as_secs_f64(),
+                );
+            }
+
+            Ok(result?.map_into_boxed_body())
+        })
+    }
+}
@@ -0,0 +1,5 @@
+pub mod actor;
+pub mod idempotency;
+pub mod locale;
+pub mod metrics;
+pub mod request_id;
@@ -0,0 +1,118 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderName, HeaderValue},
+};
+use tracing::{field, info_span, Instrument};
+use uuid::Uuid;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    /// The id of the request being handled, read by `HttpError` so that error
+    /// responses can be matched with the logs.
+    pub static CURRENT_REQUEST_ID: String;
+}
+
+/// Assigns an id to every request and runs it inside a `request` span.
+///
+/// The id is taken from the `X-Request-Id` header when the client sends a
+/// usable one, or generated otherwise, and is echoed back in the response.
+pub struct RequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| is_valid_request_id(value))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let span = info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.path(),
+            status = field::Empty,
+        );
+
+        let header_value =
+            HeaderValue::from_str(&request_id).expect("request ids are visible ASCII");
+
+        let future = CURRENT_REQUEST_ID.scope(request_id, async move {
+            match service.call(req).await {
+                Ok(response) => {
+                    let mut response = response.map_into_boxed_body();
+
+                    tracing::Span::current().record("status", response.status().as_u16());
+                    response.headers_mut().insert(REQUEST_ID, header_value);
+
+                    Ok(response)
+                }
+                Err(e) => {
+                    let mut response = e.error_response();
+
+                    tracing::Span::current().record("status", response.status().as_u16());
+                    response.headers_mut().insert(REQUEST_ID, header_value);
+
+                    Err(InternalError::from_response(e, response).into())
+                }
+            }
+        });
+
+        Box::pin(future.instrument(span))
+    }
+}
+
+/// Ids coming from clients are logged and echoed back, so only short tokens
+/// made of letters, digits, `-`, `_` and `.` are accepted.
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 128
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
@@ -14,4 +14,46 @@ pub struct Address {
     pub latitude: Option<BigDecimal>,
     pub longitude: Option<BigDecimal>,
     pub city_id: Uuid,
+    /// How the coordinates were obtained, see `GeocodingAccuracy`. Empty
+    /// when the address has no coordinates.
+    pub geocoding_accuracy: Option<String>,
+}
+
+/// An address found by a proximity search, with its distance in meters to
+/// the searched point.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct NearbyAddress {
+    pub id: Uuid,
+    pub address: String,
+    pub number: String,
+    pub neighbourhood: String,
+    pub reference: Option<String>,
+    pub complement: Option<String>,
+    pub zip_code: String,
+    pub latitude: Option<BigDecimal>,
+    pub longitude: Option<BigDecimal>,
+    pub city_id: Uuid,
+    pub geocoding_accuracy: Option<String>,
+    pub distance: f64,
+}
+
+/// An address found by a text search, with the names of its city and state
+/// and how well it matches the searched terms, from 0 to 1.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct AddressSearchResult {
+    pub id: Uuid,
+    pub address: String,
+    pub number: String,
+    pub neighbourhood: String,
+    pub reference: Option<String>,
+    pub complement: Option<String>,
+    pub zip_code: String,
+    pub latitude: Option<BigDecimal>,
+    pub longitude: Option<BigDecimal>,
+    pub city_id: Uuid,
+    pub geocoding_accuracy: Option<String>,
+    pub city_name: String,
+    pub state_name: String,
+    pub state_code: String,
+    pub score: f64,
 }
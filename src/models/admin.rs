@@ -0,0 +1,15 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, sqlx::FromRow, sqlx::Type, Serialize, Clone)]
+pub struct Admin {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    /// The Argon2 hash of the password, in the PHC string format.
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
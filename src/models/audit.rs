@@ -0,0 +1,21 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A change made to an entity, as recorded by the audit triggers.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    /// One of `AUDITED_ENTITIES`.
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    /// `create`, `update` or `delete`.
+    pub action: String,
+    pub actor: Option<String>,
+    pub request_id: Option<String>,
+    /// The columns that changed, with their previous values.
+    pub before: Option<serde_json::Value>,
+    /// The columns that changed, with their new values.
+    pub after: Option<serde_json::Value>,
+    pub occurred_at: NaiveDateTime,
+}
@@ -1,3 +1,4 @@
+use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -7,4 +8,8 @@ pub struct City {
     pub name: String,
     pub code: String,
     pub state_id: Uuid,
+    /// The centroid of the city, used to geocode addresses when their street
+    /// isn't known.
+    pub latitude: Option<BigDecimal>,
+    pub longitude: Option<BigDecimal>,
 }
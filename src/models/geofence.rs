@@ -0,0 +1,43 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct Geofence {
+    pub id: Uuid,
+    pub name: String,
+    /// One of `GeofenceKind`.
+    pub kind: String,
+    /// One of `GeofenceShape`. Circles have a center and a radius, polygons
+    /// have vertices.
+    pub shape: String,
+    pub center_lat: Option<BigDecimal>,
+    pub center_long: Option<BigDecimal>,
+    pub radius_meters: Option<BigDecimal>,
+    pub vertices: Option<Json<Vec<GeofenceVertex>>>,
+    pub address_id: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct GeofenceVertex {
+    pub lat: f64,
+    pub long: f64,
+}
+
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct GeofenceEvent {
+    pub id: Uuid,
+    pub geofence_id: Uuid,
+    pub route_id: Uuid,
+    pub vehicle_id: Uuid,
+    /// One of `GeofenceEventType`.
+    pub event_type: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub occurred_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
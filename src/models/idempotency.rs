@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct IdempotencyKey {
+    pub idempotency_key: String,
+    pub method: String,
+    pub path: String,
+    pub request_hash: String,
+    pub status_code: Option<i16>,
+    pub content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+    pub created_at: NaiveDateTime,
+    /// When the request holding the key was taken, see
+    /// `lock_idempotency_key`.
+    pub locked_at: NaiveDateTime,
+}
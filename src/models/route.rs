@@ -28,3 +28,26 @@ pub struct RouteStatus {
     pub code: String,
     pub description: String,
 }
+
+/// A GPS position reported for a route.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct RoutePosition {
+    pub id: Uuid,
+    pub route_id: Uuid,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub recorded_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+/// A change of a route streamed to clients: a new status or position. The
+/// `data` holds the fields of the change.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct RouteEvent {
+    pub id: i64,
+    pub route_id: Uuid,
+    pub vehicle_id: Uuid,
+    pub event_type: String,
+    pub data: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
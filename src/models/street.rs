@@ -0,0 +1,15 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A street of the local geocoding index, located at a single point.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct Street {
+    pub id: Uuid,
+    pub name: String,
+    /// The name as compared by the geocoder, see `normalize_street_name`.
+    pub normalized_name: String,
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub city_id: Uuid,
+}
@@ -0,0 +1,47 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    /// Each one of `WebhookEventType`.
+    pub event_types: Vec<String>,
+    /// The key the payloads sent to `url` are signed with.
+    pub secret: String,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// An event sent, or to be sent, to a subscription.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub subscription_id: Uuid,
+    /// One of `WebhookDeliveryStatus`.
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A delivery claimed by the dispatcher, with what it needs to send it.
+#[derive(Debug, Clone)]
+pub struct PendingWebhookDelivery {
+    pub id: Uuid,
+    pub attempts: i32,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub data: serde_json::Value,
+    pub event_created_at: NaiveDateTime,
+    pub url: String,
+    pub secret: String,
+}
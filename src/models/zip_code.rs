@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A CEP with the street and neighbourhood it covers. Both are empty for
+/// cities served by a single CEP.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct ZipCode {
+    pub zip_code: String,
+    pub address: Option<String>,
+    pub neighbourhood: Option<String>,
+    pub city_id: Uuid,
+}
@@ -1,19 +1,39 @@
-use actix_web::{web, HttpResponse, Scope};
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{web, HttpRequest, HttpResponse, Scope};
+use futures_util::TryStreamExt;
 use validator::Validate;
 
 use crate::{
-    db::address::AddressExt,
+    db::{address::AddressExt, city::CityExt},
     dtos::{
-        address::{AddressListResponseDTO, FilterAddressDTO, RegisterAddressDTO},
-        request::RequestQueryDTO,
+        address::{
+            AddressListResponseDTO, AddressSearchListResponseDTO, AddressSearchQueryDTO,
+            AddressSearchResultDTO, FilterAddressDTO, ImportAddressRowDTO, NearestAddressDTO,
+            NearestAddressListResponseDTO, NearestAddressQueryDTO, RegisterAddressDTO,
+        },
+        import::{ImportQueryDTO, ImportReportDTO},
+        request::{RequestQueryDTO, SpatialQueryDTO},
+        zip_code::FilterZipCodeDTO,
     },
     error::{ErrorMessage, HttpError},
+    models::address::Address,
+    utils::{
+        export::{export_format, export_response, paginate},
+        geocoding::geocode_address,
+        import::{import_response, parse_csv},
+        string::normalize_zip_code,
+    },
     AppState,
 };
 
 pub fn address_scope() -> Scope {
     web::scope("/api/v1/addresses")
         .route("", web::get().to(list_addresses))
+        .route("/import", web::post().to(import_addresses))
+        .route("/cep/{cep}", web::get().to(lookup_zip_code))
+        .route("/nearest", web::get().to(list_nearest_addresses))
+        .route("/search", web::get().to(search_addresses))
         .route("/{id}", web::get().to(get_address))
         .route("", web::post().to(save_address))
         .route("/{id}", web::delete().to(delete_address))
@@ -27,7 +47,7 @@ pub async fn get_address(
         .db_client
         .get_address(Some(id.into_inner()), None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match address {
         Some(address) => Ok(HttpResponse::Ok().json(FilterAddressDTO::filter_address(&address))),
@@ -35,24 +55,209 @@ pub async fn get_address(
     }
 }
 
+/// Fills in the street, neighbourhood and city of a CEP, typed with or
+/// without the hyphen, using the provider selected in the configuration.
+pub async fn lookup_zip_code(
+    cep: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let Some(zip_code) = normalize_zip_code(&cep) else {
+        return Err(HttpError::bad_request("Zip code must have 8 digits"));
+    };
+
+    let provider = &app_state.zip_code_provider;
+
+    match provider.lookup(&app_state.db_client, &zip_code).await? {
+        Some(zip_code) => Ok(HttpResponse::Ok().json(FilterZipCodeDTO::filter_zip_code(
+            &zip_code,
+            provider.name(),
+        ))),
+        None => Err(HttpError::from_error_message(ErrorMessage::ZipCodeNotFound)),
+    }
+}
+
+/// Finds the known addresses closest to a coordinate, within `radius` meters
+/// (500 by default).
+pub async fn list_nearest_addresses(
+    query: web::Query<NearestAddressQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let addresses = app_state
+        .db_client
+        .list_nearest_addresses(
+            query_params.lat,
+            query_params.long,
+            query_params.radius.unwrap_or(500.0),
+            query_params.limit.unwrap_or(10),
+        )
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(NearestAddressListResponseDTO {
+        addresses: NearestAddressDTO::filter_nearest_addresses(&addresses),
+        results: addresses.len(),
+    }))
+}
+
+/// Searches addresses by their street, number, neighbourhood or CEP, for
+/// autocompletion. Returns 10 results per page by default.
+pub async fn search_addresses(
+    query: web::Query<RequestQueryDTO>,
+    search_query: web::Query<AddressSearchQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let search_query = search_query.into_inner();
+
+    search_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let terms = search_query.q.trim();
+
+    if terms.is_empty() {
+        return Err(HttpError::bad_request("Search terms must not be blank"));
+    }
+
+    let city_id = search_query
+        .city_id
+        .as_deref()
+        .map(uuid::Uuid::parse_str)
+        .transpose()
+        .map_err(|_| HttpError::bad_request("City ID must be a valid UUID"))?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(10);
+
+    let addresses = app_state
+        .db_client
+        .search_addresses(terms, city_id, page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(AddressSearchListResponseDTO {
+        addresses: AddressSearchResultDTO::filter_search_results(&addresses),
+        results: addresses.len(),
+    }))
+}
+
+/// Lists addresses, optionally within `radius` meters of `lat`/`long` or
+/// inside `bbox`.
 pub async fn list_addresses(
     query: web::Query<RequestQueryDTO>,
+    spatial_query: web::Query<SpatialQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    spatial_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let area = spatial_query
+        .spatial_filter()
+        .map_err(HttpError::bad_request)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |address: &Address| address.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_addresses(page, limit, area, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "addresses",
+            rows.map_ok(|address| FilterAddressDTO::filter_address(&address)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let addresses = app_state
         .db_client
-        .list_addresses(page as u32, limit)
+        .list_addresses(page as u32, limit, area, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(AddressListResponseDTO {
+        addresses: FilterAddressDTO::filter_addresses(&addresses),
+        results: addresses.len(),
+    }))
+}
+
+/// Responds with a page of the addresses of a city, or exports all of them
+/// when another format is requested.
+pub async fn list_addresses_of_city(
+    city_id: uuid::Uuid,
+    query_params: RequestQueryDTO,
+    app_state: &AppState,
+    request: &HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |address: &Address| {
+                (
+                    address.address.clone(),
+                    address.number.clone(),
+                    address.zip_code.clone(),
+                    address.id,
+                )
+            },
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move {
+                    db_client
+                        .list_addresses_by_city(city_id, page, limit, after)
+                        .await
+                }
+            },
+        );
+
+        return export_response(
+            format,
+            "addresses",
+            rows.map_ok(|address| FilterAddressDTO::filter_address(&address)),
+        )
+        .await;
+    }
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let addresses = app_state
+        .db_client
+        .list_addresses_by_city(city_id, page as u32, limit, None)
+        .await
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(AddressListResponseDTO {
         addresses: FilterAddressDTO::filter_addresses(&addresses),
@@ -64,15 +269,16 @@ pub async fn save_address(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterAddressDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    let mut body = body.into_inner();
+    body.normalize_zip_code();
 
-    let dto = body.into_inner();
+    body.validate().map_err(HttpError::validation_error)?;
 
-    let result = app_state
-        .db_client
-        .save_address(dto.into_save_address_params_dto())
-        .await;
+    let mut params = body.into_save_address_params_dto();
+
+    geocode_address(&app_state.db_client, &app_state.env, &mut params).await?;
+
+    let result = app_state.db_client.save_address(params).await;
 
     match result {
         Ok(address) => Ok(HttpResponse::Created().json(FilterAddressDTO::filter_address(&address))),
@@ -82,12 +288,126 @@ pub async fn save_address(
                     ErrorMessage::AddressExist,
                 ))
             } else if db_err.is_foreign_key_violation() {
-                Err(HttpError::bad_request(ErrorMessage::CityNotFound))
+                Err(HttpError::invalid_reference(ErrorMessage::CityNotFound))
+            } else {
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
+            }
+        }
+        Err(e) => Err(HttpError::from(e)),
+    }
+}
+
+pub async fn import_addresses(
+    query: web::Query<ImportQueryDTO>,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let rows = parse_csv::<ImportAddressRowDTO>(&body)?;
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut addresses = vec![];
+    let mut details = HashSet::new();
+
+    // The cities and the existing addresses are looked up once for the whole
+    // file, so the queries don't grow with the number of rows.
+    let mut city_codes = HashSet::new();
+    let mut row_details = HashSet::new();
+
+    for row_dto in rows.iter().filter_map(|row| row.record.as_ref().ok()) {
+        city_codes.insert(row_dto.city_code.clone());
+        row_details.insert(row_dto.details());
+    }
+
+    let cities: HashMap<String, uuid::Uuid> = app_state
+        .db_client
+        .get_cities_by_codes(&city_codes.into_iter().collect::<Vec<_>>())
+        .await
+        .map_err(HttpError::from)?
+        .into_iter()
+        .map(|city| (city.code, city.id))
+        .collect();
+
+    let existing: HashSet<(String, String, String)> = app_state
+        .db_client
+        .get_addresses_by_details(&row_details.into_iter().collect::<Vec<_>>())
+        .await
+        .map_err(HttpError::from)?
+        .into_iter()
+        .map(|address| (address.address, address.number, address.zip_code))
+        .collect();
+
+    for row in rows {
+        let row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let Some(city_id) = cities.get(&row_dto.city_code) else {
+            report.add_error(row.line, Some("city_code"), "Unknown city code");
+            continue;
+        };
+
+        let address = row_dto.into_register_address_dto(city_id.to_string());
+
+        let mut valid = report.validate(row.line, &address);
+
+        let key = (
+            address.address.clone(),
+            address.number.clone(),
+            address.zip_code.clone(),
+        );
+
+        if !details.insert(key.clone()) {
+            report.add_error(
+                row.line,
+                None,
+                "Duplicate address (address, number, zipCode) in the file",
+            );
+            valid = false;
+        } else if existing.contains(&key) {
+            report.add_error(row.line, None, ErrorMessage::AddressExist);
+            valid = false;
+        }
+
+        if valid {
+            addresses.push(address.into_save_address_params_dto());
+        }
+    }
+
+    report.valid_rows = addresses.len();
+
+    if dry_run || report.has_errors() {
+        return Ok(import_response(report));
+    }
+
+    for params in addresses.iter_mut() {
+        geocode_address(&app_state.db_client, &app_state.env, params).await?;
+    }
+
+    let result = app_state.db_client.save_addresses(addresses).await;
+
+    match result {
+        Ok(addresses) => {
+            report.imported = addresses.len();
+
+            Ok(import_response(report))
+        }
+        Err(sqlx::Error::Database(db_err)) => {
+            if db_err.is_unique_violation() {
+                Err(HttpError::unique_constraint_violation(
+                    ErrorMessage::AddressExist,
+                ))
+            } else if db_err.is_foreign_key_violation() {
+                Err(HttpError::invalid_reference(ErrorMessage::CityNotFound))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -99,7 +419,7 @@ pub async fn delete_address(
         .db_client
         .delete_address(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match address {
         Some(address) => Ok(HttpResponse::Ok().json(FilterAddressDTO::filter_address(&address))),
@@ -0,0 +1,53 @@
+use actix_web::{web, HttpResponse, Scope};
+use validator::Validate;
+
+use crate::{
+    db::audit::AuditExt,
+    dtos::{
+        audit::{AuditLogListResponseDTO, AuditQueryDTO, FilterAuditLogEntryDTO},
+        request::RequestQueryDTO,
+    },
+    error::HttpError,
+    AppState,
+};
+
+pub fn audit_scope() -> Scope {
+    web::scope("/api/v1/audit").route("", web::get().to(list_audit_log_entries))
+}
+
+/// Lists the changes recorded in the audit log, most recent first, e.g.
+/// `?entity=vehicle&id=...` for the history of a vehicle.
+pub async fn list_audit_log_entries(
+    query: web::Query<RequestQueryDTO>,
+    audit_query: web::Query<AuditQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    audit_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let entries = app_state
+        .db_client
+        .list_audit_log_entries(
+            audit_query.entity.as_deref(),
+            audit_query.id(),
+            page as u32,
+            limit,
+        )
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(AuditLogListResponseDTO {
+        entries: FilterAuditLogEntryDTO::filter_audit_log_entries(&entries),
+        results: entries.len(),
+    }))
+}
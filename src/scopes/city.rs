@@ -1,13 +1,17 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_web::{web, HttpRequest, HttpResponse, Scope};
+use futures_util::TryStreamExt;
 use validator::Validate;
 
 use crate::{
     db::city::CityExt,
     dtos::{
-        city::{CityListResponseDTO, FilterCityDTO, RegisterCityDTO},
+        city::{CityListResponseDTO, CityQueryDTO, FilterCityDTO, RegisterCityDTO},
         request::RequestQueryDTO,
     },
     error::{ErrorMessage, HttpError},
+    models::city::City,
+    scopes::address::list_addresses_of_city,
+    utils::export::{export_format, export_response, paginate},
     AppState,
 };
 
@@ -15,6 +19,7 @@ pub fn city_scope() -> Scope {
     web::scope("/api/v1/cities")
         .route("", web::get().to(list_cities))
         .route("/{id}", web::get().to(get_city))
+        .route("/{id}/addresses", web::get().to(list_city_addresses))
         .route("", web::post().to(save_city))
         .route("/{id}", web::delete().to(delete_city))
 }
@@ -27,7 +32,7 @@ pub async fn get_city(
         .db_client
         .get_city(Some(id.into_inner()), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match city {
         Some(city) => Ok(HttpResponse::Ok().json(FilterCityDTO::filter_city(&city))),
@@ -35,24 +40,122 @@ pub async fn get_city(
     }
 }
 
+/// Lists cities, optionally by `name` prefix and `state_id`.
 pub async fn list_cities(
     query: web::Query<RequestQueryDTO>,
+    city_query: web::Query<CityQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    city_query.validate().map_err(HttpError::validation_error)?;
+
+    let name = city_query.name.to_owned();
+    let state_id = city_query.state_id();
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |city: &City| (city.name.clone(), city.id),
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                let name = name.clone();
+                async move {
+                    db_client
+                        .list_cities(page, limit, name.as_deref(), state_id, after)
+                        .await
+                }
+            },
+        );
+
+        return export_response(
+            format,
+            "cities",
+            rows.map_ok(|city| FilterCityDTO::filter_city(&city)),
+        )
+        .await;
+    }
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let cities = app_state
+        .db_client
+        .list_cities(page as u32, limit, name.as_deref(), state_id, None)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(CityListResponseDTO {
+        cities: FilterCityDTO::filter_cities(&cities),
+        results: cities.len(),
+    }))
+}
+
+pub async fn list_city_addresses(
+    id: web::Path<uuid::Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let city = app_state
+        .db_client
+        .get_city(Some(id.into_inner()), None)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::CityNotFound))?;
+
+    list_addresses_of_city(city.id, query.into_inner(), &app_state, &request).await
+}
+
+/// Responds with a page of the cities of a state, ordered by name, or
+/// exports all of them when another format is requested.
+pub async fn list_cities_of_state(
+    state_id: uuid::Uuid,
+    query_params: RequestQueryDTO,
+    app_state: &AppState,
+    request: &HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |city: &City| (city.name.clone(), city.id),
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move {
+                    db_client
+                        .list_cities(page, limit, None, Some(state_id), after)
+                        .await
+                }
+            },
+        );
+
+        return export_response(
+            format,
+            "cities",
+            rows.map_ok(|city| FilterCityDTO::filter_city(&city)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let cities = app_state
         .db_client
-        .list_cities(page as u32, limit)
+        .list_cities(page as u32, limit, None, Some(state_id), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(CityListResponseDTO {
         cities: FilterCityDTO::filter_cities(&cities),
@@ -64,8 +167,7 @@ pub async fn save_city(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterCityDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let result = app_state
         .db_client
@@ -80,12 +182,12 @@ pub async fn save_city(
                     ErrorMessage::CityExist,
                 ))
             } else if db_err.is_foreign_key_violation() {
-                Err(HttpError::bad_request(ErrorMessage::StateNotFound))
+                Err(HttpError::invalid_reference(ErrorMessage::StateNotFound))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -97,7 +199,7 @@ pub async fn delete_city(
         .db_client
         .delete_city(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match city {
         Some(city) => Ok(HttpResponse::Ok().json(FilterCityDTO::filter_city(&city))),
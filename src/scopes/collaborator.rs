@@ -1,4 +1,7 @@
-use actix_web::{web, HttpResponse, Scope};
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Scope};
+use futures_util::TryStreamExt;
 use validator::Validate;
 
 use crate::{
@@ -12,19 +15,31 @@ use crate::{
         },
         driver::{
             CnhTypeListResponseDTO, DriverListResponseDTO, FilterCnhTypeDTO, FilterDriverDTO,
-            RegisterDriverDTO,
+            ImportDriverRowDTO, RegisterDriverDTO,
         },
+        import::{ImportQueryDTO, ImportReportDTO},
         request::RequestQueryDTO,
     },
     error::{ErrorMessage, HttpError},
+    models::{
+        collaborator::Collaborator,
+        driver::{CnhType, Driver},
+    },
+    utils::{
+        etag::{check_if_match, is_not_modified, missing_or_modified, Versioned},
+        export::{export_format, export_response, paginate},
+        import::{import_response, parse_csv},
+    },
     AppState,
 };
 
 pub fn collaborator_scope() -> Scope {
     web::scope("/api/v1/collaborators")
         .route("", web::get().to(list_collaborators))
+        .route("/import", web::post().to(import_collaborators))
         .route("/drivers", web::post().to(save_driver))
         .route("/drivers", web::get().to(list_drivers))
+        .route("/drivers/import", web::post().to(import_drivers))
         .route("/drivers/cnh", web::get().to(list_cnh_types))
         .route("/drivers/cnh/{id}", web::get().to(get_cnh_type))
         .route("/drivers/{id}", web::get().to(get_driver))
@@ -48,16 +63,27 @@ pub fn collaborator_scope() -> Scope {
 pub async fn get_collaborator(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let collaborator = app_state
         .db_client
         .get_collaborator(Some(id.into_inner()), None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match collaborator {
         Some(collaborator) => {
-            Ok(HttpResponse::Ok().json(FilterCollaboratorDTO::filter_collaborator(&collaborator)))
+            let etag = collaborator.entity_tag();
+
+            if is_not_modified(&request, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header(header::ETag(etag))
+                    .finish());
+            }
+
+            Ok(HttpResponse::Ok()
+                .insert_header(header::ETag(etag))
+                .json(FilterCollaboratorDTO::filter_collaborator(&collaborator)))
         }
         None => Err(HttpError::from_error_message(
             ErrorMessage::CollaboratorNotFound,
@@ -68,21 +94,41 @@ pub async fn get_collaborator(
 pub async fn list_collaborators(
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |collaborator: &Collaborator| collaborator.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_collaborators(page, limit, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "collaborators",
+            rows.map_ok(|collaborator| FilterCollaboratorDTO::filter_collaborator(&collaborator)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let collaborators = app_state
         .db_client
-        .list_collaborators(page as u32, limit)
+        .list_collaborators(page as u32, limit, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(CollaboratorListResponseDTO {
         collaborators: FilterCollaboratorDTO::filter_collaborators(&collaborators),
@@ -94,8 +140,7 @@ pub async fn save_collaborator(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterCollaboratorDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let result = app_state
         .db_client
@@ -113,10 +158,106 @@ pub async fn save_collaborator(
                     ErrorMessage::CollaboratorExist,
                 ))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
+            }
+        }
+        Err(e) => Err(HttpError::from(e)),
+    }
+}
+
+pub async fn import_collaborators(
+    query: web::Query<ImportQueryDTO>,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let rows = parse_csv::<RegisterCollaboratorDTO>(&body)?;
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut collaborators = vec![];
+    let mut cpfs = HashSet::new();
+    let mut emails = HashSet::new();
+
+    // The existing collaborators are looked up once for the whole file, so
+    // the queries don't grow with the number of rows.
+    let (row_cpfs, row_emails): (Vec<String>, Vec<String>) = rows
+        .iter()
+        .filter_map(|row| row.record.as_ref().ok())
+        .map(|collaborator| (collaborator.cpf.clone(), collaborator.email.clone()))
+        .unzip();
+
+    let existing = app_state
+        .db_client
+        .get_collaborators_by_cpfs_or_emails(&row_cpfs, &row_emails)
+        .await
+        .map_err(HttpError::from)?;
+    let existing_cpfs: HashSet<String> = existing.iter().map(|c| c.cpf.clone()).collect();
+    let existing_emails: HashSet<String> = existing.into_iter().map(|c| c.email).collect();
+
+    for row in rows {
+        let collaborator = match row.record {
+            Ok(collaborator) => collaborator,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let mut valid = report.validate(row.line, &collaborator);
+
+        if !cpfs.insert(collaborator.cpf.clone()) {
+            report.add_error(row.line, Some("cpf"), "Duplicate CPF in the file");
+            valid = false;
+        } else if existing_cpfs.contains(&collaborator.cpf) {
+            report.add_error(
+                row.line,
+                Some("cpf"),
+                "A collaborator with this CPF already exists",
+            );
+            valid = false;
+        }
+
+        if !emails.insert(collaborator.email.clone()) {
+            report.add_error(row.line, Some("email"), "Duplicate email in the file");
+            valid = false;
+        } else if existing_emails.contains(&collaborator.email) {
+            report.add_error(
+                row.line,
+                Some("email"),
+                "A collaborator with this email already exists",
+            );
+            valid = false;
+        }
+
+        if valid {
+            collaborators.push(collaborator);
+        }
+    }
+
+    report.valid_rows = collaborators.len();
+
+    if dry_run || report.has_errors() {
+        return Ok(import_response(report));
+    }
+
+    let result = app_state.db_client.save_collaborators(collaborators).await;
+
+    match result {
+        Ok(collaborators) => {
+            report.imported = collaborators.len();
+
+            Ok(import_response(report))
+        }
+        Err(sqlx::Error::Database(db_err)) => {
+            if db_err.is_unique_violation() {
+                Err(HttpError::unique_constraint_violation(
+                    ErrorMessage::CollaboratorExist,
+                ))
+            } else {
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -124,9 +265,9 @@ pub async fn update_collaborator(
     id: web::Path<uuid::Uuid>,
     body: web::Json<RegisterCollaboratorDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let collaborator_id = Some(id.into_inner());
 
@@ -134,10 +275,12 @@ pub async fn update_collaborator(
         .db_client
         .get_collaborator(collaborator_id, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match collaborator {
-        Some(_) => {
+        Some(collaborator) => {
+            let expected_updated_at = check_if_match(&request, &collaborator)?;
+
             let result = app_state
                 .db_client
                 .update_collaborator(
@@ -146,22 +289,28 @@ pub async fn update_collaborator(
                     &body.cpf,
                     &body.rg,
                     &body.email,
+                    expected_updated_at,
                 )
                 .await;
 
             match result {
-                Ok(collaborator) => Ok(HttpResponse::Created()
+                Ok(Some(collaborator)) => Ok(HttpResponse::Created()
+                    .insert_header(header::ETag(collaborator.entity_tag()))
                     .json(FilterCollaboratorDTO::filter_collaborator(&collaborator))),
+                Ok(None) => Err(missing_or_modified(
+                    expected_updated_at,
+                    ErrorMessage::CollaboratorNotFound,
+                )),
                 Err(sqlx::Error::Database(db_err)) => {
                     if db_err.is_unique_violation() {
                         Err(HttpError::unique_constraint_violation(
                             ErrorMessage::CollaboratorExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(
@@ -173,20 +322,34 @@ pub async fn update_collaborator(
 pub async fn delete_collaborator(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let collaborator_id = Some(id.into_inner());
+    let mut expected_updated_at = None;
+
+    if request.headers().contains_key(header::IF_MATCH) {
+        let collaborator = app_state
+            .db_client
+            .get_collaborator(collaborator_id, None, None)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::from_error_message(ErrorMessage::CollaboratorNotFound))?;
+
+        expected_updated_at = check_if_match(&request, &collaborator)?;
+    }
 
     let collaborator = app_state
         .db_client
-        .delete_collaborator(collaborator_id)
+        .delete_collaborator(collaborator_id, expected_updated_at)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match collaborator {
         Some(collaborator) => {
             Ok(HttpResponse::Ok().json(FilterCollaboratorDTO::filter_collaborator(&collaborator)))
         }
-        None => Err(HttpError::from_error_message(
+        None => Err(missing_or_modified(
+            expected_updated_at,
             ErrorMessage::CollaboratorNotFound,
         )),
     }
@@ -195,15 +358,28 @@ pub async fn delete_collaborator(
 pub async fn get_driver(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let driver = app_state
         .db_client
         .get_driver(Some(id.into_inner()), None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match driver {
-        Some(driver) => Ok(HttpResponse::Ok().json(FilterDriverDTO::filter_driver(&driver))),
+        Some(driver) => {
+            let etag = driver.entity_tag();
+
+            if is_not_modified(&request, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header(header::ETag(etag))
+                    .finish());
+            }
+
+            Ok(HttpResponse::Ok()
+                .insert_header(header::ETag(etag))
+                .json(FilterDriverDTO::filter_driver(&driver)))
+        }
         None => Err(HttpError::from_error_message(ErrorMessage::DriverNotFound)),
     }
 }
@@ -211,15 +387,28 @@ pub async fn get_driver(
 pub async fn get_driver_from_collaborator(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let driver = app_state
         .db_client
         .get_driver(None, None, Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match driver {
-        Some(driver) => Ok(HttpResponse::Ok().json(FilterDriverDTO::filter_driver(&driver))),
+        Some(driver) => {
+            let etag = driver.entity_tag();
+
+            if is_not_modified(&request, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header(header::ETag(etag))
+                    .finish());
+            }
+
+            Ok(HttpResponse::Ok()
+                .insert_header(header::ETag(etag))
+                .json(FilterDriverDTO::filter_driver(&driver)))
+        }
         None => Err(HttpError::from_error_message(ErrorMessage::DriverNotFound)),
     }
 }
@@ -227,21 +416,41 @@ pub async fn get_driver_from_collaborator(
 pub async fn list_drivers(
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |driver: &Driver| driver.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_drivers(page, limit, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "drivers",
+            rows.map_ok(|driver| FilterDriverDTO::filter_driver(&driver)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let drivers = app_state
         .db_client
-        .list_drivers(page as u32, limit)
+        .list_drivers(page as u32, limit, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(DriverListResponseDTO {
         drivers: FilterDriverDTO::filter_drivers(&drivers),
@@ -253,8 +462,7 @@ pub async fn save_driver(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterDriverDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let result = app_state
         .db_client
@@ -277,29 +485,192 @@ pub async fn save_driver(
                         {
                             Err(HttpError::from_error_message(ErrorMessage::DriverExist))
                         } else {
-                            Err(HttpError::server_error(db_err.to_string()))
+                            Err(HttpError::from(sqlx::Error::Database(db_err)))
                         }
                     }
-                    None => Err(HttpError::server_error(db_err.to_string())),
+                    None => Err(HttpError::from(sqlx::Error::Database(db_err))),
                 }
             } else if db_err.is_foreign_key_violation() {
                 match db_err.constraint() {
                     Some(constraint) => {
                         if constraint == "fk_drivers_collaborator_id" {
-                            Err(HttpError::from_error_message(
+                            Err(HttpError::invalid_reference(
                                 ErrorMessage::CollaboratorNotFound,
                             ))
                         } else {
-                            Err(HttpError::from_error_message(ErrorMessage::CnhTypeNotFound))
+                            Err(HttpError::invalid_reference(ErrorMessage::CnhTypeNotFound))
                         }
                     }
-                    None => Err(HttpError::server_error(db_err.to_string())),
+                    None => Err(HttpError::from(sqlx::Error::Database(db_err))),
+                }
+            } else {
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
+            }
+        }
+        Err(e) => Err(HttpError::from(e)),
+    }
+}
+
+pub async fn import_drivers(
+    query: web::Query<ImportQueryDTO>,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let rows = parse_csv::<ImportDriverRowDTO>(&body)?;
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut drivers = vec![];
+    let mut cnh_numbers = HashSet::new();
+    let mut collaborator_ids = HashSet::new();
+
+    // The CNH types, collaborators and existing drivers are looked up once
+    // for the whole file, so the queries don't grow with the number of rows.
+    let mut row_cnh_types = HashSet::new();
+    let mut row_cpfs = HashSet::new();
+    let mut row_cnh_numbers = HashSet::new();
+
+    for row_dto in rows.iter().filter_map(|row| row.record.as_ref().ok()) {
+        row_cnh_types.insert(row_dto.cnh_type.clone());
+        row_cpfs.insert(row_dto.collaborator_cpf.clone());
+        row_cnh_numbers.insert(row_dto.cnh_number.clone());
+    }
+
+    let cnh_types: HashMap<String, CnhType> = app_state
+        .db_client
+        .get_cnh_types_by_codes(&row_cnh_types.into_iter().collect::<Vec<_>>())
+        .await
+        .map_err(HttpError::from)?
+        .into_iter()
+        .map(|cnh_type| (cnh_type.code.clone(), cnh_type))
+        .collect();
+
+    let collaborators: HashMap<String, Collaborator> = app_state
+        .db_client
+        .get_collaborators_by_cpfs_or_emails(&row_cpfs.into_iter().collect::<Vec<_>>(), &[])
+        .await
+        .map_err(HttpError::from)?
+        .into_iter()
+        .map(|collaborator| (collaborator.cpf.clone(), collaborator))
+        .collect();
+
+    let existing = app_state
+        .db_client
+        .get_drivers_by_cnh_numbers_or_collaborators(
+            &row_cnh_numbers.into_iter().collect::<Vec<_>>(),
+            &collaborators.values().map(|c| c.id).collect::<Vec<_>>(),
+        )
+        .await
+        .map_err(HttpError::from)?;
+    let existing_cnh_numbers: HashSet<String> =
+        existing.iter().map(|d| d.cnh_number.clone()).collect();
+    let existing_collaborator_ids: HashSet<uuid::Uuid> =
+        existing.into_iter().map(|d| d.collaborator_id).collect();
+
+    for row in rows {
+        let row_dto = match row.record {
+            Ok(row_dto) => row_dto,
+            Err(e) => {
+                report.add_error(row.line, None, e);
+                continue;
+            }
+        };
+
+        let mut valid = true;
+
+        let cnh_type = cnh_types.get(&row_dto.cnh_type);
+
+        if cnh_type.is_none() {
+            report.add_error(row.line, Some("cnh_type"), "Unknown CNH type code");
+            valid = false;
+        }
+
+        let collaborator = collaborators.get(&row_dto.collaborator_cpf);
+
+        match collaborator {
+            Some(collaborator) => {
+                if !collaborator_ids.insert(collaborator.id) {
+                    report.add_error(
+                        row.line,
+                        Some("collaborator_cpf"),
+                        "Duplicate collaborator CPF in the file",
+                    );
+                    valid = false;
+                } else if existing_collaborator_ids.contains(&collaborator.id) {
+                    report.add_error(
+                        row.line,
+                        Some("collaborator_cpf"),
+                        "The collaborator with this CPF is already a driver",
+                    );
+                    valid = false;
                 }
+            }
+            None => {
+                report.add_error(
+                    row.line,
+                    Some("collaborator_cpf"),
+                    "No collaborator with this CPF exists",
+                );
+                valid = false;
+            }
+        }
+
+        if !cnh_numbers.insert(row_dto.cnh_number.clone()) {
+            report.add_error(
+                row.line,
+                Some("cnh_number"),
+                "Duplicate CNH number in the file",
+            );
+            valid = false;
+        } else if existing_cnh_numbers.contains(&row_dto.cnh_number) {
+            report.add_error(
+                row.line,
+                Some("cnh_number"),
+                "A driver with this CNH number already exists",
+            );
+            valid = false;
+        }
+
+        let (Some(cnh_type), Some(collaborator)) = (cnh_type, collaborator) else {
+            continue;
+        };
+
+        let driver =
+            row_dto.into_register_driver_dto(cnh_type.id.to_string(), collaborator.id.to_string());
+
+        if report.validate(row.line, &driver) && valid {
+            drivers.push(driver);
+        }
+    }
+
+    report.valid_rows = drivers.len();
+
+    if dry_run || report.has_errors() {
+        return Ok(import_response(report));
+    }
+
+    let result = app_state.db_client.save_drivers(drivers).await;
+
+    match result {
+        Ok(drivers) => {
+            report.imported = drivers.len();
+
+            Ok(import_response(report))
+        }
+        Err(sqlx::Error::Database(db_err)) => {
+            if db_err.is_unique_violation() {
+                Err(HttpError::unique_constraint_violation(
+                    ErrorMessage::DriverExist,
+                ))
+            } else if db_err.is_foreign_key_violation() {
+                Err(HttpError::invalid_reference(
+                    ErrorMessage::CollaboratorNotFound,
+                ))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -307,9 +678,9 @@ pub async fn update_driver(
     id: web::Path<uuid::Uuid>,
     body: web::Json<RegisterDriverDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let driver_id = Some(id.into_inner());
 
@@ -317,10 +688,12 @@ pub async fn update_driver(
         .db_client
         .get_driver(driver_id, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match driver {
-        Some(_) => {
+        Some(driver) => {
+            let expected_updated_at = check_if_match(&request, &driver)?;
+
             let result = app_state
                 .db_client
                 .update_driver(
@@ -329,23 +702,28 @@ pub async fn update_driver(
                     &body.cnh_number,
                     body.cnh_expiration_date,
                     &body.id_cnh_type,
+                    expected_updated_at,
                 )
                 .await;
 
             match result {
-                Ok(driver) => {
-                    Ok(HttpResponse::Accepted().json(FilterDriverDTO::filter_driver(&driver)))
-                }
+                Ok(Some(driver)) => Ok(HttpResponse::Accepted()
+                    .insert_header(header::ETag(driver.entity_tag()))
+                    .json(FilterDriverDTO::filter_driver(&driver))),
+                Ok(None) => Err(missing_or_modified(
+                    expected_updated_at,
+                    ErrorMessage::DriverNotFound,
+                )),
                 Err(sqlx::Error::Database(db_err)) => {
                     if db_err.is_unique_violation() {
                         Err(HttpError::unique_constraint_violation(
                             ErrorMessage::DriverExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(ErrorMessage::DriverNotFound)),
@@ -356,9 +734,9 @@ pub async fn update_driver_from_collaborator(
     id: web::Path<uuid::Uuid>,
     body: web::Json<RegisterDriverDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let collaborator_id = Some(id.into_inner());
 
@@ -366,10 +744,12 @@ pub async fn update_driver_from_collaborator(
         .db_client
         .get_driver(None, None, collaborator_id)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match driver {
-        Some(_) => {
+        Some(driver) => {
+            let expected_updated_at = check_if_match(&request, &driver)?;
+
             let result = app_state
                 .db_client
                 .update_driver(
@@ -378,23 +758,28 @@ pub async fn update_driver_from_collaborator(
                     &body.cnh_number,
                     body.cnh_expiration_date,
                     &body.id_cnh_type,
+                    expected_updated_at,
                 )
                 .await;
 
             match result {
-                Ok(driver) => {
-                    Ok(HttpResponse::Accepted().json(FilterDriverDTO::filter_driver(&driver)))
-                }
+                Ok(Some(driver)) => Ok(HttpResponse::Accepted()
+                    .insert_header(header::ETag(driver.entity_tag()))
+                    .json(FilterDriverDTO::filter_driver(&driver))),
+                Ok(None) => Err(missing_or_modified(
+                    expected_updated_at,
+                    ErrorMessage::DriverNotFound,
+                )),
                 Err(sqlx::Error::Database(db_err)) => {
                     if db_err.is_unique_violation() {
                         Err(HttpError::unique_constraint_violation(
                             ErrorMessage::DriverExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(ErrorMessage::DriverNotFound)),
@@ -409,32 +794,52 @@ pub async fn get_cnh_type(
         .db_client
         .get_cnh_type(Some(id.into_inner()), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match cnh_type {
         Some(cnh_type) => Ok(HttpResponse::Ok().json(FilterCnhTypeDTO::filter_cnh_type(&cnh_type))),
-        None => Err(HttpError::from_error_message(ErrorMessage::DriverNotFound)),
+        None => Err(HttpError::from_error_message(ErrorMessage::CnhTypeNotFound)),
     }
 }
 
 pub async fn list_cnh_types(
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |cnh_type: &CnhType| cnh_type.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_cnh_type(page, limit, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "cnh_types",
+            rows.map_ok(|cnh_type| FilterCnhTypeDTO::filter_cnh_type(&cnh_type)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let cnh_types = app_state
         .db_client
-        .list_cnh_type(page as u32, limit)
+        .list_cnh_type(page as u32, limit, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(CnhTypeListResponseDTO {
         types: FilterCnhTypeDTO::filter_cnh_types(&cnh_types),
@@ -445,19 +850,33 @@ pub async fn list_cnh_types(
 pub async fn delete_driver(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let driver_id = Some(id.into_inner());
+    let mut expected_updated_at = None;
+
+    if request.headers().contains_key(header::IF_MATCH) {
+        let driver = app_state
+            .db_client
+            .get_driver(driver_id, None, None)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::from_error_message(ErrorMessage::DriverNotFound))?;
+
+        expected_updated_at = check_if_match(&request, &driver)?;
+    }
 
     let driver = app_state
         .db_client
-        .delete_driver(driver_id, None)
+        .delete_driver(driver_id, None, expected_updated_at)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match driver {
         Some(driver) => Ok(HttpResponse::Ok().json(FilterDriverDTO::filter_driver(&driver))),
-        None => Err(HttpError::from_error_message(
-            ErrorMessage::CollaboratorNotFound,
+        None => Err(missing_or_modified(
+            expected_updated_at,
+            ErrorMessage::DriverNotFound,
         )),
     }
 }
@@ -465,19 +884,33 @@ pub async fn delete_driver(
 pub async fn delete_driver_from_collaborator(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let collaborator_id = Some(id.into_inner());
+    let mut expected_updated_at = None;
+
+    if request.headers().contains_key(header::IF_MATCH) {
+        let driver = app_state
+            .db_client
+            .get_driver(None, None, collaborator_id)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::from_error_message(ErrorMessage::DriverNotFound))?;
+
+        expected_updated_at = check_if_match(&request, &driver)?;
+    }
 
     let driver = app_state
         .db_client
-        .delete_driver(None, collaborator_id)
+        .delete_driver(None, collaborator_id, expected_updated_at)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match driver {
         Some(driver) => Ok(HttpResponse::Ok().json(FilterDriverDTO::filter_driver(&driver))),
-        None => Err(HttpError::from_error_message(
-            ErrorMessage::CollaboratorNotFound,
+        None => Err(missing_or_modified(
+            expected_updated_at,
+            ErrorMessage::DriverNotFound,
         )),
     }
 }
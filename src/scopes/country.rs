@@ -1,17 +1,23 @@
 use std::str::FromStr;
 
 use actix_web::{body::MessageBody, web, HttpRequest, HttpResponse, Scope};
+use futures_util::TryStreamExt;
 use validator::Validate;
 
 use crate::{
-    db::{country::CountryExt, state::StateExt},
+    db::{city::CityExt, country::CountryExt, state::StateExt},
     dtos::{
-        country::{CountryListResponseDTO, FilterCountryDTO, RegisterCountryDTO},
+        country::{CountryListResponseDTO, CountryQueryDTO, FilterCountryDTO, RegisterCountryDTO},
         request::RequestQueryDTO,
         state::{FilterStateDTO, StateListResponseDTO},
     },
     error::{ErrorMessage, HttpError},
-    utils::string::extract_endpoint_from_path,
+    models::{country::Country, state::State},
+    scopes::{address::list_addresses_of_city, city::list_cities_of_state},
+    utils::{
+        export::{export_format, export_response, paginate},
+        string::extract_endpoint_from_path,
+    },
     AppState,
 };
 
@@ -38,6 +44,30 @@ pub fn country_scope() -> Scope {
             "/numeric3/{code}/states",
             web::get().to(list_country_states_by_code),
         )
+        .route(
+            "/alpha2/{code}/states/{state_code}/cities",
+            web::get().to(list_state_cities_by_code),
+        )
+        .route(
+            "/alpha3/{code}/states/{state_code}/cities",
+            web::get().to(list_state_cities_by_code),
+        )
+        .route(
+            "/numeric3/{code}/states/{state_code}/cities",
+            web::get().to(list_state_cities_by_code),
+        )
+        .route(
+            "/alpha2/{code}/states/{state_code}/cities/{city_code}/addresses",
+            web::get().to(list_city_addresses_by_code),
+        )
+        .route(
+            "/alpha3/{code}/states/{state_code}/cities/{city_code}/addresses",
+            web::get().to(list_city_addresses_by_code),
+        )
+        .route(
+            "/numeric3/{code}/states/{state_code}/cities/{city_code}/addresses",
+            web::get().to(list_city_addresses_by_code),
+        )
 }
 
 pub async fn get_country(
@@ -48,7 +78,7 @@ pub async fn get_country(
         .db_client
         .get_country(Some(id.into_inner()), None, None, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match country {
         Some(country) => Ok(HttpResponse::Ok().json(FilterCountryDTO::filter_country(&country))),
@@ -61,50 +91,111 @@ pub async fn get_country_by_code(
     app_state: web::Data<AppState>,
     request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    let endpoint = extract_endpoint_from_path(r"/countries/([^/]+)/", &request)?;
+    let country = find_country_by_code(&app_state, &request, &code).await?;
+
+    Ok(HttpResponse::Ok().json(FilterCountryDTO::filter_country(&country)))
+}
+
+/// Finds a country by the kind of code named in the path: `alpha2`, `alpha3`
+/// or `numeric3`.
+async fn find_country_by_code(
+    app_state: &AppState,
+    request: &HttpRequest,
+    code: &str,
+) -> Result<Country, HttpError> {
+    let endpoint = extract_endpoint_from_path(r"/countries/([^/]+)/", request)?;
 
     let country = match &endpoint[..] {
         "alpha2" => app_state
             .db_client
-            .get_country(None, None, Some(&code), None, None)
+            .get_country(None, None, Some(code), None, None)
             .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?,
+            .map_err(HttpError::from)?,
         "alpha3" => app_state
             .db_client
-            .get_country(None, None, None, Some(&code), None)
+            .get_country(None, None, None, Some(code), None)
             .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?,
+            .map_err(HttpError::from)?,
         _ => app_state
             .db_client
-            .get_country(None, None, None, None, Some(&code))
+            .get_country(None, None, None, None, Some(code))
             .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?,
+            .map_err(HttpError::from)?,
     };
 
-    match country {
-        Some(country) => Ok(HttpResponse::Ok().json(FilterCountryDTO::filter_country(&country))),
-        None => Err(HttpError::from_error_message(ErrorMessage::CountryNotFound)),
-    }
+    country.ok_or_else(|| HttpError::from_error_message(ErrorMessage::CountryNotFound))
+}
+
+/// Finds a state by its code, among those of the country found by
+/// `find_country_by_code`.
+async fn find_country_state_by_code(
+    app_state: &AppState,
+    request: &HttpRequest,
+    code: &str,
+    state_code: &str,
+) -> Result<State, HttpError> {
+    let country = find_country_by_code(app_state, request, code).await?;
+
+    app_state
+        .db_client
+        .get_state(None, None, Some(state_code))
+        .await
+        .map_err(HttpError::from)?
+        .filter(|state| state.country_id == country.id)
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::StateNotFound))
 }
 
+/// Lists countries, optionally by `name` prefix.
 pub async fn list_countries(
     query: web::Query<RequestQueryDTO>,
+    country_query: web::Query<CountryQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    country_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let name = country_query.name.to_owned();
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |country: &Country| (country.name.clone(), country.id),
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                let name = name.clone();
+                async move {
+                    db_client
+                        .list_countries(page, limit, name.as_deref(), after)
+                        .await
+                }
+            },
+        );
+
+        return export_response(
+            format,
+            "countries",
+            rows.map_ok(|country| FilterCountryDTO::filter_country(&country)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let countries = app_state
         .db_client
-        .list_countries(page as u32, limit)
+        .list_countries(page as u32, limit, name.as_deref(), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(CountryListResponseDTO {
         countries: FilterCountryDTO::filter_countries(&countries),
@@ -116,8 +207,7 @@ pub async fn save_country(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterCountryDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let result = app_state
         .db_client
@@ -132,10 +222,10 @@ pub async fn save_country(
                     ErrorMessage::CountryExist,
                 ))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -148,7 +238,7 @@ pub async fn update_country(
         .db_client
         .get_country(Some(id.into_inner()), None, None, None, None)
         .await
-        .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+        .map_err(HttpError::from)?;
 
     match country {
         Some(country) => {
@@ -162,7 +252,7 @@ pub async fn update_country(
                     &body.numeric_3,
                 )
                 .await
-                .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+                .map_err(HttpError::from)?;
 
             Ok(HttpResponse::Ok().json(FilterCountryDTO::filter_country(&country)))
         }
@@ -178,7 +268,7 @@ pub async fn delete_country(
         .db_client
         .delete_country(Some(id.into_inner()))
         .await
-        .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+        .map_err(HttpError::from)?;
 
     match country {
         Some(country) => Ok(HttpResponse::Ok().json(FilterCountryDTO::filter_country(&country))),
@@ -190,21 +280,46 @@ pub async fn list_country_states(
     id: web::Path<uuid::Uuid>,
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let country_id = *id;
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |state: &State| state.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move {
+                    db_client
+                        .list_states_by_country(Some(country_id), page, limit, after)
+                        .await
+                }
+            },
+        );
+
+        return export_response(
+            format,
+            "states",
+            rows.map_ok(|state| FilterStateDTO::filter_state(&state)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let states = app_state
         .db_client
-        .list_states_by_country(Some(id.into_inner()), page as u32, limit)
+        .list_states_by_country(Some(id.into_inner()), page as u32, limit, None)
         .await
-        .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(StateListResponseDTO {
         states: FilterStateDTO::filter_states(&states),
@@ -218,7 +333,7 @@ pub async fn list_country_states_by_code(
     app_state: web::Data<AppState>,
     request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    let country_result = get_country_by_code(code, app_state.clone(), request).await;
+    let country_result = get_country_by_code(code, app_state.clone(), request.clone()).await;
 
     match country_result {
         Ok(country_response) => {
@@ -228,9 +343,9 @@ pub async fn list_country_states_by_code(
                 let bytes = body.try_into_bytes().unwrap();
 
                 let str_body = std::str::from_utf8(&bytes)
-                    .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+                    .map_err(|e| HttpError::server_error(e.to_string()))?;
                 let country_dto: FilterCountryDTO = serde_json::from_str(str_body)
-                    .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+                    .map_err(|e| HttpError::server_error(e.to_string()))?;
                 country_dto.id
             };
 
@@ -238,7 +353,31 @@ pub async fn list_country_states_by_code(
 
             query_params
                 .validate()
-                .map_err(|e| HttpError::bad_request(e.to_string()))?;
+                .map_err(HttpError::validation_error)?;
+
+            if let Some(format) = export_format(&request, query_params.format) {
+                let country_id = uuid::Uuid::from_str(&country_id).unwrap();
+                let db_client = app_state.db_client.clone();
+                let rows = paginate(
+                    &query_params,
+                    |state: &State| state.id,
+                    move |page, limit, after| {
+                        let db_client = db_client.clone();
+                        async move {
+                            db_client
+                                .list_states_by_country(Some(country_id), page, limit, after)
+                                .await
+                        }
+                    },
+                );
+
+                return export_response(
+                    format,
+                    "states",
+                    rows.map_ok(|state| FilterStateDTO::filter_state(&state)),
+                )
+                .await;
+            }
 
             let page = query_params.page.unwrap_or(1);
             let limit = query_params.limit.unwrap_or(50);
@@ -250,9 +389,10 @@ pub async fn list_country_states_by_code(
                     Some(uuid::Uuid::from_str(&country_id).unwrap()),
                     page as u32,
                     limit,
+                    None,
                 )
                 .await
-                .map_err(|_| HttpError::from_error_message(ErrorMessage::ServerError))?;
+                .map_err(HttpError::from)?;
 
             Ok(HttpResponse::Ok().json(StateListResponseDTO {
                 states: FilterStateDTO::filter_states(&states),
@@ -262,3 +402,41 @@ pub async fn list_country_states_by_code(
         Err(e) => Err(e),
     }
 }
+
+/// Lists the cities of a state, both found by their codes, e.g.
+/// `/countries/alpha2/BR/states/PR/cities`.
+pub async fn list_state_cities_by_code(
+    path: web::Path<(String, String)>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let (code, state_code) = path.into_inner();
+
+    let state = find_country_state_by_code(&app_state, &request, &code, &state_code).await?;
+
+    list_cities_of_state(state.id, query.into_inner(), &app_state, &request).await
+}
+
+/// Lists the addresses of a city, found by its IBGE code within a state and
+/// a country also found by their codes.
+pub async fn list_city_addresses_by_code(
+    path: web::Path<(String, String, String)>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let (code, state_code, city_code) = path.into_inner();
+
+    let state = find_country_state_by_code(&app_state, &request, &code, &state_code).await?;
+
+    let city = app_state
+        .db_client
+        .get_city(None, Some(&city_code))
+        .await
+        .map_err(HttpError::from)?
+        .filter(|city| city.state_id == state.id)
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::CityNotFound))?;
+
+    list_addresses_of_city(city.id, query.into_inner(), &app_state, &request).await
+}
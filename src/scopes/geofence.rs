@@ -0,0 +1,329 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Scope};
+use bigdecimal::ToPrimitive;
+use sqlx::error::DatabaseError;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    db::{address::AddressExt, geofence::GeofenceExt, spatial::to_decimal},
+    dtos::{
+        geofence::{
+            FilterGeofenceDTO, FilterGeofenceEventDTO, GeofenceEventListResponseDTO,
+            GeofenceListResponseDTO, RegisterGeofenceDTO, SaveGeofenceParamsDTO,
+        },
+        request::RequestQueryDTO,
+    },
+    error::{ErrorMessage, HttpError},
+    models::geofence::GeofenceVertex,
+    utils::{
+        etag::{check_if_match, is_not_modified, missing_or_modified, Versioned},
+        geofence::{circle_bounds, polygon_bounds, GeofenceShape},
+    },
+    AppState,
+};
+
+pub fn geofence_scope() -> Scope {
+    web::scope("/api/v1/geofences")
+        .route("", web::get().to(list_geofences))
+        .route("", web::post().to(save_geofence))
+        .route("/{id}", web::get().to(get_geofence))
+        .route("/{id}", web::put().to(update_geofence))
+        .route("/{id}", web::delete().to(delete_geofence))
+        .route("/{id}/events", web::get().to(list_events_from_geofence))
+}
+
+pub async fn get_geofence(
+    id: web::Path<Uuid>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let geofence = app_state
+        .db_client
+        .get_geofence(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?;
+
+    match geofence {
+        Some(geofence) => {
+            let etag = geofence.entity_tag();
+
+            if is_not_modified(&request, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header(header::ETag(etag))
+                    .finish());
+            }
+
+            Ok(HttpResponse::Ok()
+                .insert_header(header::ETag(etag))
+                .json(FilterGeofenceDTO::filter_geofence(&geofence)))
+        }
+        None => Err(HttpError::from_error_message(
+            ErrorMessage::GeofenceNotFound,
+        )),
+    }
+}
+
+pub async fn list_geofences(
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let geofences = app_state
+        .db_client
+        .list_geofences(page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(GeofenceListResponseDTO {
+        geofences: FilterGeofenceDTO::filter_geofences(&geofences),
+        results: geofences.len(),
+    }))
+}
+
+pub async fn save_geofence(
+    app_state: web::Data<AppState>,
+    body: web::Json<RegisterGeofenceDTO>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate().map_err(HttpError::validation_error)?;
+
+    let params = into_save_geofence_params(&app_state, body.into_inner()).await?;
+
+    let result = app_state.db_client.save_geofence(params).await;
+
+    match result {
+        Ok(geofence) => {
+            Ok(HttpResponse::Created().json(FilterGeofenceDTO::filter_geofence(&geofence)))
+        }
+        Err(sqlx::Error::Database(db_err)) => match_foreign_key_violation(db_err),
+        Err(e) => Err(HttpError::from(e)),
+    }
+}
+
+pub async fn update_geofence(
+    id: web::Path<Uuid>,
+    body: web::Json<RegisterGeofenceDTO>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    body.validate().map_err(HttpError::validation_error)?;
+
+    let geofence_id = id.into_inner();
+
+    let geofence = app_state
+        .db_client
+        .get_geofence(Some(geofence_id))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::GeofenceNotFound))?;
+
+    let expected_updated_at = check_if_match(&request, &geofence)?;
+
+    let params = into_save_geofence_params(&app_state, body.into_inner()).await?;
+
+    let result = app_state
+        .db_client
+        .update_geofence(geofence_id, params, expected_updated_at)
+        .await;
+
+    match result {
+        Ok(Some(geofence)) => Ok(HttpResponse::Accepted()
+            .insert_header(header::ETag(geofence.entity_tag()))
+            .json(FilterGeofenceDTO::filter_geofence(&geofence))),
+        Ok(None) => Err(missing_or_modified(
+            expected_updated_at,
+            ErrorMessage::GeofenceNotFound,
+        )),
+        Err(sqlx::Error::Database(db_err)) => match_foreign_key_violation(db_err),
+        Err(e) => Err(HttpError::from(e)),
+    }
+}
+
+pub async fn delete_geofence(
+    id: web::Path<Uuid>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let geofence_id = Some(id.into_inner());
+    let mut expected_updated_at = None;
+
+    if request.headers().contains_key(header::IF_MATCH) {
+        let geofence = app_state
+            .db_client
+            .get_geofence(geofence_id)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::from_error_message(ErrorMessage::GeofenceNotFound))?;
+
+        expected_updated_at = check_if_match(&request, &geofence)?;
+    }
+
+    let geofence = app_state
+        .db_client
+        .delete_geofence(geofence_id, expected_updated_at)
+        .await
+        .map_err(HttpError::from)?;
+
+    match geofence {
+        Some(geofence) => {
+            Ok(HttpResponse::Ok().json(FilterGeofenceDTO::filter_geofence(&geofence)))
+        }
+        None => Err(missing_or_modified(
+            expected_updated_at,
+            ErrorMessage::GeofenceNotFound,
+        )),
+    }
+}
+
+pub async fn list_events_from_geofence(
+    id: web::Path<Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let geofence_id = id.into_inner();
+
+    app_state
+        .db_client
+        .get_geofence(Some(geofence_id))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::GeofenceNotFound))?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let events = app_state
+        .db_client
+        .list_geofence_events(None, None, Some(geofence_id), page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(GeofenceEventListResponseDTO {
+        events: FilterGeofenceEventDTO::filter_geofence_events(&events),
+        results: events.len(),
+    }))
+}
+
+/// Works out the shape of the geofence and its bounding box. A circle sent
+/// without a center is centered on its address.
+async fn into_save_geofence_params(
+    app_state: &AppState,
+    dto: RegisterGeofenceDTO,
+) -> Result<SaveGeofenceParamsDTO, HttpError> {
+    let address_id = dto
+        .address_id
+        .as_deref()
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| HttpError::bad_request("Address ID must be a valid UUID"))?;
+
+    let mut params = SaveGeofenceParamsDTO {
+        name: dto.name,
+        kind: dto.kind,
+        shape: String::new(),
+        center_lat: None,
+        center_long: None,
+        radius_meters: None,
+        vertices: None,
+        bounds: [0.0; 4],
+        address_id,
+    };
+
+    match (dto.radius_meters, dto.vertices) {
+        (Some(_), Some(_)) => Err(HttpError::bad_request(
+            "A geofence has either a radiusMeters or vertices, not both",
+        )),
+        (None, None) => Err(HttpError::bad_request(
+            "A geofence needs a radiusMeters for a circle or vertices for a polygon",
+        )),
+        (None, Some(vertices)) => {
+            if dto.center_lat.is_some() || dto.center_long.is_some() {
+                return Err(HttpError::bad_request(
+                    "A polygon is described by its vertices, without centerLat and centerLong",
+                ));
+            }
+
+            let vertices: Vec<GeofenceVertex> = vertices
+                .into_iter()
+                .map(|vertex| GeofenceVertex {
+                    lat: vertex.lat,
+                    long: vertex.long,
+                })
+                .collect();
+
+            params.shape = GeofenceShape::Polygon.to_string();
+            params.bounds = polygon_bounds(&vertices);
+            params.vertices = Some(vertices);
+
+            Ok(params)
+        }
+        (Some(radius_meters), None) => {
+            let (center_lat, center_long) = match (dto.center_lat, dto.center_long) {
+                (Some(center_lat), Some(center_long)) => (center_lat, center_long),
+                (None, None) => address_coordinates(app_state, address_id).await?,
+                _ => {
+                    return Err(HttpError::bad_request(
+                        "centerLat and centerLong must be given together",
+                    ))
+                }
+            };
+
+            params.shape = GeofenceShape::Circle.to_string();
+            params.bounds = circle_bounds(center_lat, center_long, radius_meters);
+            params.center_lat = Some(to_decimal(center_lat));
+            params.center_long = Some(to_decimal(center_long));
+            params.radius_meters = Some(to_decimal(radius_meters));
+
+            Ok(params)
+        }
+    }
+}
+
+async fn address_coordinates(
+    app_state: &AppState,
+    address_id: Option<Uuid>,
+) -> Result<(f64, f64), HttpError> {
+    let Some(address_id) = address_id else {
+        return Err(HttpError::bad_request(
+            "A circle needs centerLat and centerLong, or an addressId with coordinates",
+        ));
+    };
+
+    let address = app_state
+        .db_client
+        .get_address(Some(address_id), None, None)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::invalid_reference(ErrorMessage::AddressNotFound))?;
+
+    match (
+        address.latitude.as_ref().and_then(ToPrimitive::to_f64),
+        address.longitude.as_ref().and_then(ToPrimitive::to_f64),
+    ) {
+        (Some(latitude), Some(longitude)) => Ok((latitude, longitude)),
+        _ => Err(HttpError::bad_request(
+            "A circle needs centerLat and centerLong, or an addressId with coordinates",
+        )),
+    }
+}
+
+fn match_foreign_key_violation(db_err: Box<dyn DatabaseError>) -> Result<HttpResponse, HttpError> {
+    if db_err.is_foreign_key_violation() && db_err.constraint() == Some("fk_geofences_address_id") {
+        return Err(HttpError::invalid_reference(ErrorMessage::AddressNotFound));
+    }
+
+    Err(HttpError::from(sqlx::Error::Database(db_err)))
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use actix_web::{web, HttpResponse, Scope};
+use sqlx::{Pool, Postgres};
+use tracing::warn;
+
+use crate::{
+    db::health::HealthExt,
+    dtos::health::{ComponentHealthDTO, HealthStatus, LivenessResponseDTO, ReadinessResponseDTO},
+    AppState,
+};
+
+/// How long a single readiness check may take before the component is
+/// reported as down. Keeps the probe fast when the database hangs.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn health_scope() -> Scope {
+    web::scope("/api/v1/health")
+        .route("/live", web::get().to(liveness))
+        .route("/ready", web::get().to(readiness))
+}
+
+/// Answers as long as the process is able to serve requests. It doesn't
+/// touch any dependency, so a database outage doesn't restart the service.
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(LivenessResponseDTO {
+        status: HealthStatus::Up,
+    })
+}
+
+/// Checks the dependencies needed to serve traffic: database connectivity,
+/// pending migrations and pool saturation.
+///
+/// Answers with 200 (OK) when every component is up, or 503 (Service
+/// Unavailable) otherwise, with the status and latency of each component.
+pub async fn readiness(app_state: web::Data<AppState>) -> HttpResponse {
+    let db_client = &app_state.db_client;
+
+    let mut components = BTreeMap::new();
+
+    components.insert(
+        "database".to_string(),
+        timed(db_client.ping(), |_| ComponentHealthDTO::up()).await,
+    );
+
+    components.insert(
+        "migrations".to_string(),
+        timed(db_client.count_pending_migrations(), |pending| {
+            let mut component = match pending {
+                0 => ComponentHealthDTO::up(),
+                _ => ComponentHealthDTO::down(format!("{pending} migration(s) pending")),
+            };
+
+            component.details = Some(serde_json::json!({ "pending": pending }));
+            component
+        })
+        .await,
+    );
+
+    components.insert("pool".to_string(), check_pool(&db_client.pool));
+
+    let status = match components
+        .values()
+        .all(|component| component.status == HealthStatus::Up)
+    {
+        true => HealthStatus::Up,
+        false => HealthStatus::Down,
+    };
+
+    let response = ReadinessResponseDTO { status, components };
+
+    match status {
+        HealthStatus::Up => HttpResponse::Ok().json(response),
+        HealthStatus::Down => HttpResponse::ServiceUnavailable().json(response),
+    }
+}
+
+/// Runs `check` under `CHECK_TIMEOUT` and records how long it took. Errors are
+/// logged and reported with a generic message, so no connection details leak.
+async fn timed<T, F>(
+    check: impl Future<Output = Result<T, sqlx::Error>>,
+    on_success: F,
+) -> ComponentHealthDTO
+where
+    F: FnOnce(T) -> ComponentHealthDTO,
+{
+    let started_at = Instant::now();
+
+    let mut component = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok(value)) => on_success(value),
+        Ok(Err(e)) => {
+            warn!("Readiness check failed: {e}");
+            ComponentHealthDTO::down("Database query failed")
+        }
+        Err(_) => ComponentHealthDTO::down("Timed out"),
+    };
+
+    component.latency_ms = Some(started_at.elapsed().as_secs_f64() * 1000.0);
+    component
+}
+
+/// The pool is saturated when every connection is open and none is idle, in
+/// which case new requests would wait for a connection.
+fn check_pool(pool: &Pool<Postgres>) -> ComponentHealthDTO {
+    let size = pool.size();
+    let idle = pool.num_idle();
+    let max = pool.options().get_max_connections();
+
+    let mut component = match size >= max && idle == 0 {
+        true => ComponentHealthDTO::down("Every connection in the pool is in use"),
+        false => ComponentHealthDTO::up(),
+    };
+
+    component.details = Some(serde_json::json!({
+        "size": size,
+        "idle": idle,
+        "max": max,
+    }));
+    component
+}
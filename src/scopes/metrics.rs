@@ -0,0 +1,44 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use prometheus::TEXT_FORMAT;
+
+use crate::{
+    db::{driver::DriverExt, route::RouteExt},
+    error::HttpError,
+    AppState,
+};
+
+/// Handles HTTP GET requests to '/metrics', exposing the application metrics
+/// in the Prometheus text format.
+///
+/// Pool and domain gauges are refreshed before encoding, so every scrape
+/// reflects the current state of the database.
+///
+/// # Returns
+///
+/// Returns the encoded metrics with status code 200 (OK), or an `HttpError`
+/// if the domain gauges can't be read from the database.
+#[get("/metrics")]
+pub async fn metrics_handler(app_state: web::Data<AppState>) -> Result<impl Responder, HttpError> {
+    let db_client = &app_state.db_client;
+    let metrics = &app_state.metrics;
+
+    metrics.set_pool(&db_client.pool);
+
+    let active_routes = db_client
+        .count_active_routes()
+        .await
+        .map_err(HttpError::from)?;
+
+    let drivers_with_expired_cnh = db_client
+        .count_drivers_with_expired_cnh()
+        .await
+        .map_err(HttpError::from)?;
+
+    metrics.set_domain(active_routes, drivers_with_expired_cnh);
+
+    let body = metrics
+        .encode()
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().content_type(TEXT_FORMAT).body(body))
+}
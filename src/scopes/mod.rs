@@ -1,7 +1,12 @@
 pub mod address;
+pub mod audit;
 pub mod city;
 pub mod collaborator;
 pub mod country;
+pub mod geofence;
+pub mod health;
+pub mod metrics;
 pub mod route;
 pub mod state;
 pub mod vehicle;
+pub mod webhook;
@@ -1,20 +1,37 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Scope};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use futures_util::TryStreamExt;
 use sqlx::error::DatabaseError;
 use validator::Validate;
 
 use crate::{
     db::{
         address::AddressExt,
-        route::{RouteExt, RouteStatusExt},
+        geofence::GeofenceExt,
+        route::{RouteEventExt, RouteExt, RoutePositionExt, RouteStatusExt},
     },
     dtos::{
-        request::RequestQueryDTO,
+        geofence::{FilterGeofenceEventDTO, GeofenceEventListResponseDTO},
+        request::{RequestQueryDTO, SpatialQueryDTO},
         route::{
-            FilterRouteDTO, FilterRouteStatusDTO, RegisterRandomRouteDTO, RegisterRouteDTO,
-            RegisterRouteStatusDTO, RouteListResponseDTO, RouteStatusListResponseDTO,
+            FilterRouteDTO, FilterRoutePositionDTO, FilterRouteStatusDTO, RegisterRandomRouteDTO,
+            RegisterRouteDTO, RegisterRoutePositionsDTO, RegisterRouteStatusDTO, RouteEtaDTO,
+            RouteEventQueryDTO, RouteListResponseDTO, RoutePositionListResponseDTO,
+            RoutePositionsResponseDTO, RouteStatusListResponseDTO, SaveRouteParamsDTO,
+            SaveRoutePositionParamsDTO,
         },
     },
     error::{ErrorMessage, HttpError},
+    models::route::{Route, RouteStatus},
+    utils::{
+        eta::{
+            estimate_progress, RouteProgress, TimedPoint, SPEED_WINDOW_MINUTES,
+            SPEED_WINDOW_POSITIONS,
+        },
+        etag::{check_if_match, is_not_modified, missing_or_modified, Versioned},
+        export::{export_format, export_response, paginate},
+        tracking::{route_event_stream, RouteEventFilter},
+    },
     AppState,
 };
 
@@ -26,47 +43,99 @@ pub fn route_scope() -> Scope {
         .route("/status/{id}", web::get().to(get_route_status))
         .route("/status/{id}", web::delete().to(delete_route_status))
         .route("/status", web::get().to(list_route_status))
+        .route("/events", web::get().to(stream_route_events))
         .route("/{id}", web::get().to(get_route))
         .route("/{id}", web::put().to(update_route))
         .route("/{id}", web::delete().to(delete_route))
         .route("/{id}/status", web::get().to(get_route_status_from_route))
+        .route("/{id}/positions", web::get().to(list_route_positions))
+        .route("/{id}/positions", web::post().to(save_route_positions))
+        .route("/{id}/eta", web::get().to(get_route_eta))
+        .route(
+            "/{id}/geofence-events",
+            web::get().to(list_geofence_events_from_route),
+        )
         .route("/random", web::post().to(create_random_route))
 }
 
 pub async fn get_route(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let route = app_state
         .db_client
         .get_route(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match route {
-        Some(route) => Ok(HttpResponse::Ok().json(FilterRouteDTO::filter_route(&route))),
+        Some(route) => {
+            let etag = route.entity_tag();
+
+            if is_not_modified(&request, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header(header::ETag(etag))
+                    .finish());
+            }
+
+            Ok(HttpResponse::Ok()
+                .insert_header(header::ETag(etag))
+                .json(FilterRouteDTO::filter_route(&route)))
+        }
         None => Err(HttpError::from_error_message(ErrorMessage::RouteNotFound)),
     }
 }
 
+/// Lists routes, optionally those starting or ending within `radius` meters
+/// of `lat`/`long` or inside `bbox`.
 pub async fn list_routes(
     query: web::Query<RequestQueryDTO>,
+    spatial_query: web::Query<SpatialQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    spatial_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let area = spatial_query
+        .spatial_filter()
+        .map_err(HttpError::bad_request)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |route: &Route| route.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_routes(page, limit, area, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "routes",
+            rows.map_ok(|route| FilterRouteDTO::filter_route(&route)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let routes = app_state
         .db_client
-        .list_routes(page as u32, limit)
+        .list_routes(page as u32, limit, area, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(RouteListResponseDTO {
         routes: FilterRouteDTO::filter_routes(&routes),
@@ -78,15 +147,13 @@ pub async fn save_route(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterRouteDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
-    let dto = body.into_inner();
+    let mut params = body.into_inner().into_save_route_params_dto();
 
-    let result = app_state
-        .db_client
-        .save_route(dto.into_save_route_params_dto())
-        .await;
+    link_nearest_addresses(&app_state, &mut params).await?;
+
+    let result = app_state.db_client.save_route(params).await;
 
     match result {
         Ok(route) => Ok(HttpResponse::Created().json(FilterRouteDTO::filter_route(&route))),
@@ -94,10 +161,10 @@ pub async fn save_route(
             if db_err.is_foreign_key_violation() {
                 match_foreign_key_violation(&*db_err)
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -105,9 +172,9 @@ pub async fn update_route(
     id: web::Path<uuid::Uuid>,
     body: web::Json<RegisterRouteDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let route_id = Some(id.into_inner());
 
@@ -115,21 +182,31 @@ pub async fn update_route(
         .db_client
         .get_route(route_id)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     let dto = body.into_inner();
 
     match route {
-        Some(_) => {
+        Some(route) => {
+            let expected_updated_at = check_if_match(&request, &route)?;
+
             let result = app_state
                 .db_client
-                .update_route(route_id, dto.into_save_route_params_dto())
+                .update_route(
+                    route_id,
+                    dto.into_save_route_params_dto(),
+                    expected_updated_at,
+                )
                 .await;
 
             match result {
-                Ok(driver) => {
-                    Ok(HttpResponse::Accepted().json(FilterRouteDTO::filter_route(&driver)))
-                }
+                Ok(Some(route)) => Ok(HttpResponse::Accepted()
+                    .insert_header(header::ETag(route.entity_tag()))
+                    .json(FilterRouteDTO::filter_route(&route))),
+                Ok(None) => Err(missing_or_modified(
+                    expected_updated_at,
+                    ErrorMessage::RouteNotFound,
+                )),
                 Err(sqlx::Error::Database(db_err)) => {
                     if db_err.is_unique_violation() {
                         // Will never happen
@@ -137,10 +214,10 @@ pub async fn update_route(
                             ErrorMessage::DriverExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(ErrorMessage::RouteNotFound)),
@@ -150,16 +227,34 @@ pub async fn update_route(
 pub async fn delete_route(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
+    let route_id = Some(id.into_inner());
+    let mut expected_updated_at = None;
+
+    if request.headers().contains_key(header::IF_MATCH) {
+        let route = app_state
+            .db_client
+            .get_route(route_id)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::from_error_message(ErrorMessage::RouteNotFound))?;
+
+        expected_updated_at = check_if_match(&request, &route)?;
+    }
+
     let route = app_state
         .db_client
-        .delete_route(Some(id.into_inner()))
+        .delete_route(route_id, expected_updated_at)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match route {
         Some(route) => Ok(HttpResponse::Ok().json(FilterRouteDTO::filter_route(&route))),
-        None => Err(HttpError::from_error_message(ErrorMessage::RouteNotFound)),
+        None => Err(missing_or_modified(
+            expected_updated_at,
+            ErrorMessage::RouteNotFound,
+        )),
     }
 }
 
@@ -171,7 +266,7 @@ pub async fn get_route_status(
         .db_client
         .get_route_status(Some(id.into_inner()), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match status {
         Some(status) => {
@@ -191,7 +286,7 @@ pub async fn get_route_status_from_route(
         .db_client
         .get_route(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     let status_id = match route {
         Some(route) => route.status_id,
@@ -202,7 +297,7 @@ pub async fn get_route_status_from_route(
         .db_client
         .get_route_status(Some(status_id), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match status {
         Some(status) => {
@@ -214,24 +309,286 @@ pub async fn get_route_status_from_route(
     }
 }
 
+pub async fn list_route_positions(
+    id: web::Path<uuid::Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let route = app_state
+        .db_client
+        .get_route(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::RouteNotFound))?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let positions = app_state
+        .db_client
+        .list_route_positions(route.id, page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(RoutePositionListResponseDTO {
+        positions: FilterRoutePositionDTO::filter_route_positions(&positions),
+        results: positions.len(),
+    }))
+}
+
+/// Saves GPS positions of a route and answers with the geofences they made
+/// the route enter or leave.
+pub async fn save_route_positions(
+    id: web::Path<uuid::Uuid>,
+    body: web::Json<RegisterRoutePositionsDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate().map_err(HttpError::validation_error)?;
+
+    let route = app_state
+        .db_client
+        .get_route(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::RouteNotFound))?;
+
+    let received_at = chrono::Utc::now().naive_utc();
+
+    let positions = body
+        .into_inner()
+        .positions
+        .into_iter()
+        .map(|position| SaveRoutePositionParamsDTO {
+            latitude: position.lat,
+            longitude: position.long,
+            recorded_at: position.recorded_at.unwrap_or(received_at),
+        })
+        .collect();
+
+    let (positions, events) = app_state
+        .db_client
+        .save_route_positions(&route, positions)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::RoutePositionsOutOfOrder))?;
+
+    Ok(HttpResponse::Created().json(RoutePositionsResponseDTO {
+        positions: FilterRoutePositionDTO::filter_route_positions(&positions),
+        geofence_events: FilterGeofenceEventDTO::filter_geofence_events(&events),
+    }))
+}
+
+/// Streams the status changes and new positions of routes as Server-Sent
+/// Events. A client reconnecting with `Last-Event-ID` first receives the
+/// events it missed.
+pub async fn stream_route_events(
+    query: web::Query<RouteEventQueryDTO>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    query.validate().map_err(HttpError::validation_error)?;
+
+    let filter = RouteEventFilter {
+        route_ids: query.route_ids(),
+        vehicle_ids: query.vehicle_ids(),
+    };
+
+    // Subscribed before reading the latest ID, so no event is lost between
+    // the two.
+    let receiver = app_state.route_events.subscribe();
+
+    let last_event_id = request
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .or(query.last_event_id);
+
+    let last_event_id = match last_event_id {
+        Some(last_event_id) => last_event_id,
+        None => app_state
+            .db_client
+            .latest_route_event_id()
+            .await
+            .map_err(HttpError::from)?,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(route_event_stream(
+            app_state.db_client.clone(),
+            receiver,
+            filter,
+            last_event_id,
+        )))
+}
+
+/// Estimates when a route reaches its final coordinates, from its latest
+/// position and the average speed over the positions before it.
+pub async fn get_route_eta(
+    id: web::Path<uuid::Uuid>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let route = app_state
+        .db_client
+        .get_route(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::RouteNotFound))?;
+
+    let origin = (
+        route.initial_lat.to_f64().unwrap_or_default(),
+        route.initial_long.to_f64().unwrap_or_default(),
+    );
+
+    let destination = match (
+        route.final_lat.as_ref().and_then(ToPrimitive::to_f64),
+        route.final_long.as_ref().and_then(ToPrimitive::to_f64),
+    ) {
+        (Some(latitude), Some(longitude)) => (latitude, longitude),
+        _ => {
+            return Err(HttpError::bad_request(
+                "The route has no final coordinates to estimate its arrival",
+            ))
+        }
+    };
+
+    if let Some(ended_at) = route.ended_at {
+        let progress = RouteProgress {
+            remaining_distance: 0.0,
+            percent_complete: 100.0,
+            average_speed: None,
+            remaining_seconds: Some(0),
+            estimated_arrival: Some(ended_at),
+        };
+
+        let position = (
+            route.final_lat.to_owned().unwrap_or_default(),
+            route.final_long.to_owned().unwrap_or_default(),
+        );
+
+        return Ok(HttpResponse::Ok().json(RouteEtaDTO::from_progress(
+            &route,
+            position,
+            Some(ended_at),
+            progress,
+        )));
+    }
+
+    let positions = app_state
+        .db_client
+        .list_recent_route_positions(route.id, SPEED_WINDOW_MINUTES, SPEED_WINDOW_POSITIONS)
+        .await
+        .map_err(HttpError::from)?;
+
+    let points: Vec<TimedPoint> = positions
+        .iter()
+        .map(|position| TimedPoint {
+            latitude: position.latitude.to_f64().unwrap_or_default(),
+            longitude: position.longitude.to_f64().unwrap_or_default(),
+            recorded_at: position.recorded_at,
+        })
+        .collect();
+
+    let progress = estimate_progress(origin, destination, &points);
+
+    let (position, recorded_at) = match positions.last() {
+        Some(latest) => (
+            (latest.latitude.to_owned(), latest.longitude.to_owned()),
+            Some(latest.recorded_at),
+        ),
+        None => (
+            (route.initial_lat.to_owned(), route.initial_long.to_owned()),
+            None,
+        ),
+    };
+
+    Ok(HttpResponse::Ok().json(RouteEtaDTO::from_progress(
+        &route,
+        position,
+        recorded_at,
+        progress,
+    )))
+}
+
+pub async fn list_geofence_events_from_route(
+    id: web::Path<uuid::Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let route = app_state
+        .db_client
+        .get_route(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::RouteNotFound))?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let events = app_state
+        .db_client
+        .list_geofence_events(Some(route.id), None, None, page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(GeofenceEventListResponseDTO {
+        events: FilterGeofenceEventDTO::filter_geofence_events(&events),
+        results: events.len(),
+    }))
+}
+
 pub async fn list_route_status(
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |status: &RouteStatus| status.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_route_status(page, limit, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "routes_status",
+            rows.map_ok(|status| FilterRouteStatusDTO::filter_route_status(&status)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let statuses = app_state
         .db_client
-        .list_route_status(page as u32, limit)
+        .list_route_status(page as u32, limit, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(RouteStatusListResponseDTO {
         status: FilterRouteStatusDTO::filter_route_statuses(&statuses),
@@ -243,8 +600,7 @@ pub async fn save_route_status(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterRouteStatusDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let result = app_state
         .db_client
@@ -261,10 +617,10 @@ pub async fn save_route_status(
                     ErrorMessage::RouteStatusExist,
                 ))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -276,7 +632,7 @@ pub async fn delete_route_status(
         .db_client
         .delete_route_status(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match status {
         Some(status) => {
@@ -288,17 +644,69 @@ pub async fn delete_route_status(
     }
 }
 
+/// Links a route saved without `initialAddressId` or `finalAddressId` to the
+/// closest known address within `route_address_radius_meters` of its start or
+/// end, when that setting isn't `0`.
+async fn link_nearest_addresses(
+    app_state: &AppState,
+    params: &mut SaveRouteParamsDTO<BigDecimal, String>,
+) -> Result<(), HttpError> {
+    let radius = app_state.env.route_address_radius_meters;
+
+    if radius == 0 {
+        return Ok(());
+    }
+
+    if params.initial_address_id.is_none() {
+        params.initial_address_id =
+            nearest_address_id(app_state, &params.initial_lat, &params.initial_long, radius)
+                .await?;
+    }
+
+    if let (None, Some(final_lat), Some(final_long)) = (
+        &params.final_address_id,
+        &params.final_lat,
+        &params.final_long,
+    ) {
+        params.final_address_id =
+            nearest_address_id(app_state, final_lat, final_long, radius).await?;
+    }
+
+    Ok(())
+}
+
+async fn nearest_address_id(
+    app_state: &AppState,
+    latitude: &BigDecimal,
+    longitude: &BigDecimal,
+    radius: u32,
+) -> Result<Option<String>, HttpError> {
+    let (Some(latitude), Some(longitude)) = (latitude.to_f64(), longitude.to_f64()) else {
+        return Ok(None);
+    };
+
+    let addresses = app_state
+        .db_client
+        .list_nearest_addresses(latitude, longitude, f64::from(radius), 1)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(addresses.first().map(|address| address.id.to_string()))
+}
+
 fn match_foreign_key_violation(db_err: &dyn DatabaseError) -> Result<HttpResponse, HttpError> {
     match db_err.constraint() {
         Some(constraint) => {
             if constraint == "fk_routes_initial_address_id"
                 || constraint == "fk_routes_final_address_id"
             {
-                Err(HttpError::bad_request(ErrorMessage::AddressNotFound))
+                Err(HttpError::invalid_reference(ErrorMessage::AddressNotFound))
             } else if constraint == "fk_routes_vehicle_id" {
-                Err(HttpError::bad_request(ErrorMessage::VehicleNotFound))
+                Err(HttpError::invalid_reference(ErrorMessage::VehicleNotFound))
             } else if constraint == "fk_routes_route_status" {
-                Err(HttpError::bad_request(ErrorMessage::RouteStatusNotFound))
+                Err(HttpError::invalid_reference(
+                    ErrorMessage::RouteStatusNotFound,
+                ))
             } else {
                 Err(HttpError::server_error(db_err.to_string()))
             }
@@ -311,8 +719,7 @@ pub async fn create_random_route(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterRandomRouteDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let request_dto = body.into_inner();
 
@@ -320,30 +727,44 @@ pub async fn create_random_route(
         .db_client
         .get_address_random()
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
     let final_address = app_state
         .db_client
         .get_address_random()
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     let route_status = app_state
         .db_client
         .get_route_status(None, Some("CREATED".to_owned()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
+
+    let (Some(initial_address), Some(final_address)) = (initial_address, final_address) else {
+        return Err(HttpError::from_error_message(ErrorMessage::AddressNotFound));
+    };
 
-    let initial_address_2 = initial_address.unwrap();
-    let final_address_2 = final_address.unwrap();
+    // Only addresses with coordinates are picked, so these are always set.
+    let (Some(initial_lat), Some(initial_long)) =
+        (initial_address.latitude, initial_address.longitude)
+    else {
+        return Err(HttpError::from_error_message(ErrorMessage::AddressNotFound));
+    };
+
+    let Some(route_status) = route_status else {
+        return Err(HttpError::from_error_message(
+            ErrorMessage::RouteStatusNotFound,
+        ));
+    };
 
     let dto = RegisterRouteDTO {
-        initial_lat: initial_address_2.latitude.unwrap(),
-        initial_long: initial_address_2.longitude.unwrap(),
-        final_lat: final_address_2.latitude,
-        final_long: final_address_2.longitude,
-        status_id: route_status.unwrap().id.to_string(),
-        initial_address_id: Some(initial_address_2.id.to_string()),
-        final_address_id: Some(final_address_2.id.to_string()),
+        initial_lat,
+        initial_long,
+        final_lat: final_address.latitude,
+        final_long: final_address.longitude,
+        status_id: route_status.id.to_string(),
+        initial_address_id: Some(initial_address.id.to_string()),
+        final_address_id: Some(final_address.id.to_string()),
         vehicle_id: request_dto.vehicle_id,
         driver_id: Some(request_dto.driver_id),
     };
@@ -362,6 +783,6 @@ pub async fn create_random_route(
                 Err(HttpError::server_error(db_err.to_string()))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
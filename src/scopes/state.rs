@@ -1,13 +1,17 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_web::{web, HttpRequest, HttpResponse, Scope};
+use futures_util::TryStreamExt;
 use validator::Validate;
 
 use crate::{
     db::state::StateExt,
     dtos::{
         request::RequestQueryDTO,
-        state::{FilterStateDTO, RegisterStateDTO, StateListResponseDTO},
+        state::{FilterStateDTO, RegisterStateDTO, StateListResponseDTO, StateQueryDTO},
     },
     error::{ErrorMessage, HttpError},
+    models::state::State,
+    scopes::city::list_cities_of_state,
+    utils::export::{export_format, export_response, paginate},
     AppState,
 };
 
@@ -15,6 +19,7 @@ pub fn state_scope() -> Scope {
     web::scope("/api/v1/states")
         .route("", web::get().to(list_states))
         .route("/{id}", web::get().to(get_state))
+        .route("/{id}/cities", web::get().to(list_state_cities))
         .route("", web::post().to(save_state))
         .route("/{id}", web::delete().to(delete_state))
 }
@@ -27,7 +32,7 @@ pub async fn get_state(
         .db_client
         .get_state(Some(id.into_inner()), None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match state {
         Some(state) => Ok(HttpResponse::Ok().json(FilterStateDTO::filter_state(&state))),
@@ -35,24 +40,74 @@ pub async fn get_state(
     }
 }
 
+pub async fn list_state_cities(
+    id: web::Path<uuid::Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let state = app_state
+        .db_client
+        .get_state(Some(id.into_inner()), None, None)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::StateNotFound))?;
+
+    list_cities_of_state(state.id, query.into_inner(), &app_state, &request).await
+}
+
+/// Lists states, optionally by `name` prefix and `country_id`.
 pub async fn list_states(
     query: web::Query<RequestQueryDTO>,
+    state_query: web::Query<StateQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    state_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let name = state_query.name.to_owned();
+    let country_id = state_query.country_id();
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |state: &State| (state.name.clone(), state.id),
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                let name = name.clone();
+                async move {
+                    db_client
+                        .list_states(page, limit, name.as_deref(), country_id, after)
+                        .await
+                }
+            },
+        );
+
+        return export_response(
+            format,
+            "states",
+            rows.map_ok(|state| FilterStateDTO::filter_state(&state)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let states = app_state
         .db_client
-        .list_states(page as u32, limit)
+        .list_states(page as u32, limit, name.as_deref(), country_id, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(StateListResponseDTO {
         states: FilterStateDTO::filter_states(&states),
@@ -64,8 +119,7 @@ pub async fn save_state(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterStateDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let result = app_state
         .db_client
@@ -80,12 +134,12 @@ pub async fn save_state(
                     ErrorMessage::StateExist,
                 ))
             } else if db_err.is_foreign_key_violation() {
-                Err(HttpError::bad_request(ErrorMessage::CountryNotFound))
+                Err(HttpError::invalid_reference(ErrorMessage::CountryNotFound))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -97,7 +151,7 @@ pub async fn delete_state(
         .db_client
         .delete_state(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match state {
         Some(state) => Ok(HttpResponse::Ok().json(FilterStateDTO::filter_state(&state))),
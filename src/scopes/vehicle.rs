@@ -1,9 +1,15 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Scope};
+use futures_util::TryStreamExt;
 use validator::Validate;
 
 use crate::{
-    db::vehicle::{VehicleDocumentExt, VehicleExt},
+    db::{
+        geofence::GeofenceExt,
+        vehicle::{VehicleDocumentExt, VehicleExt},
+    },
     dtos::{
+        geofence::{FilterGeofenceEventDTO, GeofenceEventListResponseDTO},
+        import::{ImportQueryDTO, ImportReportDTO},
         request::RequestQueryDTO,
         vehicle::{
             FilterVehicleDTO, FilterVehicleDocumentDTO, RegisterVehicleDTO,
@@ -11,12 +17,19 @@ use crate::{
         },
     },
     error::{ErrorMessage, HttpError},
+    models::vehicle::{Vehicle, VehicleDocument},
+    utils::{
+        etag::{check_if_match, is_not_modified, missing_or_modified, Versioned},
+        export::{export_format, export_response, paginate},
+        import::{import_response, parse_csv},
+    },
     AppState,
 };
 
 pub fn vehicle_scope() -> Scope {
     web::scope("/api/v1/vehicles")
         .route("", web::get().to(list_vehicles))
+        .route("/import", web::post().to(import_vehicles))
         .route("/documents", web::get().to(list_vehicles_documents))
         .route("/documents/{id}", web::get().to(get_vehicle_document))
         .route("/documents/{id}", web::put().to(update_vehicle_document))
@@ -41,20 +54,37 @@ pub fn vehicle_scope() -> Scope {
             "/{id}/documents",
             web::delete().to(delete_vehicle_document_from_vehicle),
         )
+        .route(
+            "/{id}/geofence-events",
+            web::get().to(list_geofence_events_from_vehicle),
+        )
 }
 
 pub async fn get_vehicle(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let vehicle = app_state
         .db_client
         .get_vehicle(Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match vehicle {
-        Some(vehicle) => Ok(HttpResponse::Ok().json(FilterVehicleDTO::filter_vehicle(&vehicle))),
+        Some(vehicle) => {
+            let etag = vehicle.entity_tag();
+
+            if is_not_modified(&request, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header(header::ETag(etag))
+                    .finish());
+            }
+
+            Ok(HttpResponse::Ok()
+                .insert_header(header::ETag(etag))
+                .json(FilterVehicleDTO::filter_vehicle(&vehicle)))
+        }
         None => Err(HttpError::from_error_message(ErrorMessage::VehicleNotFound)),
     }
 }
@@ -62,21 +92,41 @@ pub async fn get_vehicle(
 pub async fn list_vehicles(
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |vehicle: &Vehicle| vehicle.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_vehicles(page, limit, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "vehicles",
+            rows.map_ok(|vehicle| FilterVehicleDTO::filter_vehicle(&vehicle)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let vehicles = app_state
         .db_client
-        .list_vehicles(page as u32, limit)
+        .list_vehicles(page as u32, limit, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(VehicleListResponseDTO {
         vehicles: FilterVehicleDTO::filter_vehicles(&vehicles),
@@ -88,8 +138,7 @@ pub async fn save_vehicle(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterVehicleDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let mut actual_mileage = body.initial_mileage;
     if let Some(body_actual_mileage) = body.actual_mileage {
@@ -109,10 +158,59 @@ pub async fn save_vehicle(
                     ErrorMessage::VehicleExist,
                 ))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
+    }
+}
+
+pub async fn import_vehicles(
+    query: web::Query<ImportQueryDTO>,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let rows = parse_csv::<RegisterVehicleDTO>(&body)?;
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    let mut report = ImportReportDTO::new(dry_run, rows.len());
+    let mut vehicles = vec![];
+
+    for row in rows {
+        match row.record {
+            Ok(vehicle) => {
+                if report.validate(row.line, &vehicle) {
+                    vehicles.push(vehicle);
+                }
+            }
+            Err(e) => report.add_error(row.line, None, e),
+        }
+    }
+
+    report.valid_rows = vehicles.len();
+
+    if dry_run || report.has_errors() {
+        return Ok(import_response(report));
+    }
+
+    let result = app_state.db_client.save_vehicles(vehicles).await;
+
+    match result {
+        Ok(vehicles) => {
+            report.imported = vehicles.len();
+
+            Ok(import_response(report))
+        }
+        Err(sqlx::Error::Database(db_err)) => {
+            if db_err.is_unique_violation() {
+                Err(HttpError::unique_constraint_violation(
+                    ErrorMessage::VehicleExist,
+                ))
+            } else {
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
+            }
+        }
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -120,9 +218,9 @@ pub async fn update_vehicle(
     id: web::Path<uuid::Uuid>,
     body: web::Json<RegisterVehicleDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let vehicle_id = Some(id.into_inner());
 
@@ -130,10 +228,12 @@ pub async fn update_vehicle(
         .db_client
         .get_vehicle(vehicle_id)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match vehicle {
-        Some(_) => {
+        Some(vehicle) => {
+            let expected_updated_at = check_if_match(&request, &vehicle)?;
+
             let result = app_state
                 .db_client
                 .update_vehicle(
@@ -141,23 +241,28 @@ pub async fn update_vehicle(
                     &body.name,
                     Some(body.initial_mileage),
                     body.actual_mileage.unwrap(),
+                    expected_updated_at,
                 )
                 .await;
 
             match result {
-                Ok(vehicle) => {
-                    Ok(HttpResponse::Created().json(FilterVehicleDTO::filter_vehicle(&vehicle)))
-                }
+                Ok(Some(vehicle)) => Ok(HttpResponse::Created()
+                    .insert_header(header::ETag(vehicle.entity_tag()))
+                    .json(FilterVehicleDTO::filter_vehicle(&vehicle))),
+                Ok(None) => Err(missing_or_modified(
+                    expected_updated_at,
+                    ErrorMessage::VehicleNotFound,
+                )),
                 Err(sqlx::Error::Database(db_err)) => {
                     if db_err.is_unique_violation() {
                         Err(HttpError::unique_constraint_violation(
                             ErrorMessage::VehicleExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(ErrorMessage::VehicleNotFound)),
@@ -167,18 +272,34 @@ pub async fn update_vehicle(
 pub async fn delete_vehicle(
     id: web::Path<uuid::Uuid>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let vehicle_id = Some(id.into_inner());
+    let mut expected_updated_at = None;
+
+    if request.headers().contains_key(header::IF_MATCH) {
+        let vehicle = app_state
+            .db_client
+            .get_vehicle(vehicle_id)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::from_error_message(ErrorMessage::VehicleNotFound))?;
+
+        expected_updated_at = check_if_match(&request, &vehicle)?;
+    }
 
     let vehicle = app_state
         .db_client
-        .delete_vehicle(vehicle_id)
+        .delete_vehicle(vehicle_id, expected_updated_at)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match vehicle {
         Some(vehicle) => Ok(HttpResponse::Ok().json(FilterVehicleDTO::filter_vehicle(&vehicle))),
-        None => Err(HttpError::from_error_message(ErrorMessage::VehicleNotFound)),
+        None => Err(missing_or_modified(
+            expected_updated_at,
+            ErrorMessage::VehicleNotFound,
+        )),
     }
 }
 
@@ -190,7 +311,7 @@ pub async fn get_vehicle_document(
         .db_client
         .get_vehicle_document(Some(id.into_inner()), None, None, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match document {
         Some(document) => {
@@ -210,7 +331,7 @@ pub async fn get_vehicle_document_from_vehicle(
         .db_client
         .get_vehicle_document(None, Some(id.into_inner()), None, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match document {
         Some(document) => {
@@ -225,21 +346,41 @@ pub async fn get_vehicle_document_from_vehicle(
 pub async fn list_vehicles_documents(
     query: web::Query<RequestQueryDTO>,
     app_state: web::Data<AppState>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, HttpError> {
     let query_params: RequestQueryDTO = query.into_inner();
 
     query_params
         .validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        .map_err(HttpError::validation_error)?;
+
+    if let Some(format) = export_format(&request, query_params.format) {
+        let db_client = app_state.db_client.clone();
+        let rows = paginate(
+            &query_params,
+            |document: &VehicleDocument| document.id,
+            move |page, limit, after| {
+                let db_client = db_client.clone();
+                async move { db_client.list_vehicle_documents(page, limit, after).await }
+            },
+        );
+
+        return export_response(
+            format,
+            "vehicles_documents",
+            rows.map_ok(|document| FilterVehicleDocumentDTO::filter_document(&document)),
+        )
+        .await;
+    }
 
     let page = query_params.page.unwrap_or(1);
     let limit = query_params.limit.unwrap_or(50);
 
     let documents = app_state
         .db_client
-        .list_vehicle_documents(page as u32, limit)
+        .list_vehicle_documents(page as u32, limit, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     Ok(HttpResponse::Ok().json(VehicleDocumentListResponseDTO {
         documents: FilterVehicleDocumentDTO::filter_documents(&documents),
@@ -252,8 +393,7 @@ pub async fn save_vehicle_document_from_vehicle(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterVehicleDocumentDTO>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let mut dto = body.into_inner();
     dto.vehicle_id = Some(id.to_string()); // Uses the vehicle ID from path
@@ -273,12 +413,12 @@ pub async fn save_vehicle_document_from_vehicle(
                     ErrorMessage::VehicleDocumentExist,
                 ))
             } else if db_err.is_foreign_key_violation() {
-                Err(HttpError::bad_request(ErrorMessage::VehicleNotFound))
+                Err(HttpError::invalid_reference(ErrorMessage::VehicleNotFound))
             } else {
-                Err(HttpError::server_error(db_err.to_string()))
+                Err(HttpError::from(sqlx::Error::Database(db_err)))
             }
         }
-        Err(e) => Err(HttpError::server_error(e.to_string())),
+        Err(e) => Err(HttpError::from(e)),
     }
 }
 
@@ -287,8 +427,7 @@ pub async fn update_vehicle_document(
     body: web::Json<RegisterVehicleDocumentDTO>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let document_id = Some(id.into_inner());
 
@@ -298,7 +437,7 @@ pub async fn update_vehicle_document(
         .db_client
         .get_vehicle_document(document_id, None, None, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match document {
         Some(_) => {
@@ -320,10 +459,10 @@ pub async fn update_vehicle_document(
                             ErrorMessage::VehicleDocumentExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(
@@ -340,7 +479,7 @@ pub async fn delete_vehicle_document(
         .db_client
         .delete_vehicle_document(Some(id.into_inner()), None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match document {
         Some(document) => {
@@ -357,8 +496,7 @@ pub async fn update_vehicle_document_from_vehicle(
     body: web::Json<RegisterVehicleDocumentDTO>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
-    body.validate()
-        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    body.validate().map_err(HttpError::validation_error)?;
 
     let vehicle_id = Some(id.into_inner());
 
@@ -368,7 +506,7 @@ pub async fn update_vehicle_document_from_vehicle(
         .db_client
         .get_vehicle_document(None, vehicle_id, None, None, None)
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match document {
         Some(_) => {
@@ -390,10 +528,10 @@ pub async fn update_vehicle_document_from_vehicle(
                             ErrorMessage::VehicleDocumentExist,
                         ))
                     } else {
-                        Err(HttpError::server_error(db_err.to_string()))
+                        Err(HttpError::from(sqlx::Error::Database(db_err)))
                     }
                 }
-                Err(e) => Err(HttpError::server_error(e.to_string())),
+                Err(e) => Err(HttpError::from(e)),
             }
         }
         None => Err(HttpError::from_error_message(
@@ -410,7 +548,7 @@ pub async fn delete_vehicle_document_from_vehicle(
         .db_client
         .delete_vehicle_document(None, Some(id.into_inner()))
         .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .map_err(HttpError::from)?;
 
     match document {
         Some(document) => {
@@ -421,3 +559,36 @@ pub async fn delete_vehicle_document_from_vehicle(
         )),
     }
 }
+
+pub async fn list_geofence_events_from_vehicle(
+    id: web::Path<uuid::Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let vehicle = app_state
+        .db_client
+        .get_vehicle(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::VehicleNotFound))?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let events = app_state
+        .db_client
+        .list_geofence_events(None, Some(vehicle.id), None, page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(GeofenceEventListResponseDTO {
+        events: FilterGeofenceEventDTO::filter_geofence_events(&events),
+        results: events.len(),
+    }))
+}
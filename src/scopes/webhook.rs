@@ -0,0 +1,256 @@
+use actix_web::{web, HttpResponse, Scope};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    db::webhook::WebhookExt,
+    dtos::{
+        request::RequestQueryDTO,
+        webhook::{
+            FilterWebhookDeliveryDTO, FilterWebhookSubscriptionDTO, RegisterWebhookSubscriptionDTO,
+            SaveWebhookSubscriptionParamsDTO, WebhookDeliveryListResponseDTO,
+            WebhookDeliveryQueryDTO, WebhookSubscriptionListResponseDTO,
+        },
+    },
+    error::{ErrorMessage, HttpError},
+    models::webhook::WebhookSubscription,
+    utils::webhook::generate_secret,
+    AppState,
+};
+
+pub fn webhook_scope() -> Scope {
+    web::scope("/api/v1/webhooks")
+        .route("", web::get().to(list_webhook_subscriptions))
+        .route("", web::post().to(save_webhook_subscription))
+        .route("/{id}", web::get().to(get_webhook_subscription))
+        .route("/{id}", web::put().to(update_webhook_subscription))
+        .route("/{id}", web::delete().to(delete_webhook_subscription))
+        .route("/{id}/ping", web::post().to(ping_webhook_subscription))
+        .route("/{id}/deliveries", web::get().to(list_webhook_deliveries))
+        .route(
+            "/{id}/deliveries/{delivery_id}/retry",
+            web::post().to(retry_webhook_delivery),
+        )
+}
+
+pub async fn get_webhook_subscription(
+    id: web::Path<Uuid>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let subscription = find_webhook_subscription(&app_state, id.into_inner()).await?;
+
+    Ok(
+        HttpResponse::Ok().json(FilterWebhookSubscriptionDTO::filter_webhook_subscription(
+            &subscription,
+        )),
+    )
+}
+
+pub async fn list_webhook_subscriptions(
+    query: web::Query<RequestQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let subscriptions = app_state
+        .db_client
+        .list_webhook_subscriptions(page as u32, limit)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(WebhookSubscriptionListResponseDTO {
+        subscriptions: FilterWebhookSubscriptionDTO::filter_webhook_subscriptions(&subscriptions),
+        results: subscriptions.len(),
+    }))
+}
+
+/// Creates a subscription. Its secret is only returned here, so it must be
+/// kept by the caller to check the signatures.
+pub async fn save_webhook_subscription(
+    app_state: web::Data<AppState>,
+    body: web::Json<RegisterWebhookSubscriptionDTO>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate().map_err(HttpError::validation_error)?;
+
+    let body = body.into_inner();
+    let secret = body.secret.clone().unwrap_or_else(generate_secret);
+
+    let subscription = app_state
+        .db_client
+        .save_webhook_subscription(into_save_webhook_subscription_params(body, secret))
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Created().json(FilterWebhookSubscriptionDTO {
+        secret: Some(subscription.secret.to_owned()),
+        ..FilterWebhookSubscriptionDTO::filter_webhook_subscription(&subscription)
+    }))
+}
+
+pub async fn update_webhook_subscription(
+    id: web::Path<Uuid>,
+    body: web::Json<RegisterWebhookSubscriptionDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate().map_err(HttpError::validation_error)?;
+
+    let subscription_id = id.into_inner();
+    let subscription = find_webhook_subscription(&app_state, subscription_id).await?;
+
+    let body = body.into_inner();
+    let secret = body.secret.clone().unwrap_or(subscription.secret);
+
+    let subscription = app_state
+        .db_client
+        .update_webhook_subscription(
+            subscription_id,
+            into_save_webhook_subscription_params(body, secret),
+        )
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::WebhookSubscriptionNotFound))?;
+
+    Ok(
+        HttpResponse::Accepted().json(FilterWebhookSubscriptionDTO::filter_webhook_subscription(
+            &subscription,
+        )),
+    )
+}
+
+pub async fn delete_webhook_subscription(
+    id: web::Path<Uuid>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let subscription = app_state
+        .db_client
+        .delete_webhook_subscription(Some(id.into_inner()))
+        .await
+        .map_err(HttpError::from)?;
+
+    match subscription {
+        Some(subscription) => Ok(HttpResponse::Ok().json(
+            FilterWebhookSubscriptionDTO::filter_webhook_subscription(&subscription),
+        )),
+        None => Err(HttpError::from_error_message(
+            ErrorMessage::WebhookSubscriptionNotFound,
+        )),
+    }
+}
+
+/// Sends a `ping` event to the subscription, to check its endpoint and
+/// signature verification.
+pub async fn ping_webhook_subscription(
+    id: web::Path<Uuid>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let subscription = find_webhook_subscription(&app_state, id.into_inner()).await?;
+
+    let delivery = app_state
+        .db_client
+        .ping_webhook_subscription(subscription.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Accepted().json(FilterWebhookDeliveryDTO::filter_webhook_delivery(&delivery)))
+}
+
+pub async fn list_webhook_deliveries(
+    id: web::Path<Uuid>,
+    query: web::Query<RequestQueryDTO>,
+    delivery_query: web::Query<WebhookDeliveryQueryDTO>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let query_params: RequestQueryDTO = query.into_inner();
+
+    query_params
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    delivery_query
+        .validate()
+        .map_err(HttpError::validation_error)?;
+
+    let subscription = find_webhook_subscription(&app_state, id.into_inner()).await?;
+
+    let page = query_params.page.unwrap_or(1);
+    let limit = query_params.limit.unwrap_or(50);
+
+    let deliveries = app_state
+        .db_client
+        .list_webhook_deliveries(
+            subscription.id,
+            delivery_query.status.as_deref(),
+            page as u32,
+            limit,
+        )
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(WebhookDeliveryListResponseDTO {
+        deliveries: FilterWebhookDeliveryDTO::filter_webhook_deliveries(&deliveries),
+        results: deliveries.len(),
+    }))
+}
+
+/// Sends a delivery again, typically one that failed while the endpoint was
+/// down. It gets a full set of attempts.
+pub async fn retry_webhook_delivery(
+    path: web::Path<(Uuid, Uuid)>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let (subscription_id, delivery_id) = path.into_inner();
+
+    let subscription = find_webhook_subscription(&app_state, subscription_id).await?;
+
+    app_state
+        .db_client
+        .get_webhook_delivery(subscription.id, delivery_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::WebhookDeliveryNotFound))?;
+
+    let delivery = app_state
+        .db_client
+        .retry_webhook_delivery(subscription.id, delivery_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::WebhookDeliveryNotRetryable))?;
+
+    Ok(HttpResponse::Accepted().json(FilterWebhookDeliveryDTO::filter_webhook_delivery(&delivery)))
+}
+
+async fn find_webhook_subscription(
+    app_state: &AppState,
+    subscription_id: Uuid,
+) -> Result<WebhookSubscription, HttpError> {
+    app_state
+        .db_client
+        .get_webhook_subscription(Some(subscription_id))
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::from_error_message(ErrorMessage::WebhookSubscriptionNotFound))
+}
+
+/// Stores the event types sorted and without repeats.
+fn into_save_webhook_subscription_params(
+    dto: RegisterWebhookSubscriptionDTO,
+    secret: String,
+) -> SaveWebhookSubscriptionParamsDTO {
+    let mut event_types = dto.event_types;
+    event_types.sort();
+    event_types.dedup();
+
+    SaveWebhookSubscriptionParamsDTO {
+        url: dto.url,
+        event_types,
+        secret,
+        active: dto.active.unwrap_or(true),
+    }
+}
@@ -0,0 +1,56 @@
+use sqlx::PgConnection;
+use validator::ValidationError;
+
+use crate::middlewares::{actor::CURRENT_ACTOR, request_id::CURRENT_REQUEST_ID};
+
+/// The entity types recorded in the audit log, each one written by the
+/// `record_audit_log` trigger of its table.
+pub const AUDITED_ENTITIES: [&str; 13] = [
+    "country",
+    "state",
+    "city",
+    "address",
+    "collaborator",
+    "driver",
+    "cnh_type",
+    "vehicle",
+    "vehicle_document",
+    "route",
+    "route_status",
+    "geofence",
+    "webhook_subscription",
+];
+
+pub fn is_valid_audit_entity(entity: &str) -> Result<(), ValidationError> {
+    match AUDITED_ENTITIES.contains(&entity) {
+        true => Ok(()),
+        false => Err(ValidationError::new("Is not an audited entity")),
+    }
+}
+
+/// Tells the transaction who is making the changes of the current task, so
+/// the audit triggers can record it. Outside a request, such as in background
+/// jobs, both are cleared and the changes are recorded without them.
+///
+/// The settings are local to the transaction, so they end with it and never
+/// reach the next task using the connection. Only the transactions that
+/// change audited entities set them, see `DBClient::begin_audited`, so reads
+/// don't pay for the extra round trip.
+pub async fn set_audit_context(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    let actor = CURRENT_ACTOR
+        .try_with(|actor| actor.clone())
+        .unwrap_or_default();
+    let request_id = CURRENT_REQUEST_ID
+        .try_with(|id| id.clone())
+        .unwrap_or_default();
+
+    sqlx::query(
+        "SELECT set_config('audit.actor', $1, true), set_config('audit.request_id', $2, true)",
+    )
+    .bind(actor)
+    .bind(request_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
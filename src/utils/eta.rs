@@ -0,0 +1,236 @@
+use chrono::{Duration, NaiveDateTime};
+use geoutils::Location;
+
+/// How far back from the latest position the average speed is measured.
+pub const SPEED_WINDOW_MINUTES: i32 = 15;
+
+/// Positions read to measure the average speed, at most.
+pub const SPEED_WINDOW_POSITIONS: usize = 500;
+
+/// Below this average speed, in meters per second, the vehicle is taken as
+/// stopped and no arrival is estimated.
+const MIN_SPEED: f64 = 0.5;
+
+/// A point of a route at the time it was recorded.
+#[derive(Debug, Clone, Copy)]
+pub struct TimedPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub recorded_at: NaiveDateTime,
+}
+
+/// How far a route is from its destination and when it should get there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteProgress {
+    /// The distance left, in meters.
+    pub remaining_distance: f64,
+    pub percent_complete: f64,
+    /// The recent average speed, in meters per second.
+    pub average_speed: Option<f64>,
+    pub remaining_seconds: Option<i64>,
+    pub estimated_arrival: Option<NaiveDateTime>,
+}
+
+/// The great-circle distance in meters between two coordinates.
+pub fn haversine_meters(from: (f64, f64), to: (f64, f64)) -> f64 {
+    Location::new(from.0, from.1)
+        .haversine_distance_to(&Location::new(to.0, to.1))
+        .meters()
+}
+
+/// The average speed in meters per second along `points`, ordered by time.
+/// Returns `None` when they don't span any time.
+pub fn average_speed(points: &[TimedPoint]) -> Option<f64> {
+    let (first, last) = (points.first()?, points.last()?);
+    let seconds = (last.recorded_at - first.recorded_at).num_milliseconds() as f64 / 1000.0;
+
+    if seconds <= 0.0 {
+        return None;
+    }
+
+    let meters: f64 = points
+        .windows(2)
+        .map(|pair| {
+            haversine_meters(
+                (pair[0].latitude, pair[0].longitude),
+                (pair[1].latitude, pair[1].longitude),
+            )
+        })
+        .sum();
+
+    Some(meters / seconds)
+}
+
+/// Estimates the progress of a route from `origin` to `destination` given
+/// its recent `points`, ordered by time. Without points the route is taken
+/// as not started. Distances are straight lines, so the estimate is a lower
+/// bound of the distance driven.
+pub fn estimate_progress(
+    origin: (f64, f64),
+    destination: (f64, f64),
+    points: &[TimedPoint],
+) -> RouteProgress {
+    let current = points
+        .last()
+        .map_or(origin, |point| (point.latitude, point.longitude));
+
+    let total_distance = haversine_meters(origin, destination);
+    let remaining_distance = haversine_meters(current, destination);
+
+    let percent_complete = if total_distance > 0.0 {
+        ((1.0 - remaining_distance / total_distance) * 100.0).clamp(0.0, 100.0)
+    } else {
+        100.0
+    };
+
+    let average_speed = average_speed(points);
+
+    let remaining_seconds = average_speed
+        .filter(|speed| *speed >= MIN_SPEED)
+        .map(|speed| (remaining_distance / speed).round() as i64);
+
+    // The remaining distance was measured at the latest position, so the
+    // time left counts from there.
+    let estimated_arrival = match (remaining_seconds, points.last()) {
+        (Some(seconds), Some(point)) => Some(point.recorded_at + Duration::seconds(seconds)),
+        _ => None,
+    };
+
+    RouteProgress {
+        remaining_distance,
+        percent_complete,
+        average_speed,
+        remaining_seconds,
+        estimated_arrival,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    const ORIGIN: (f64, f64) = (-23.55, -46.63);
+    const DESTINATION: (f64, f64) = (-23.55, -46.53);
+
+    fn at(seconds: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            + Duration::seconds(seconds)
+    }
+
+    fn point((latitude, longitude): (f64, f64), seconds: i64) -> TimedPoint {
+        TimedPoint {
+            latitude,
+            longitude,
+            recorded_at: at(seconds),
+        }
+    }
+
+    /// The coordinate `fraction` of the way from the origin to the
+    /// destination, along the parallel.
+    fn along(fraction: f64) -> (f64, f64) {
+        (ORIGIN.0, ORIGIN.1 + (DESTINATION.1 - ORIGIN.1) * fraction)
+    }
+
+    #[test]
+    fn average_speed_over_points() {
+        let points = [
+            point(along(0.0), 0),
+            point(along(0.5), 300),
+            point(along(1.0), 600),
+        ];
+        let meters = haversine_meters(ORIGIN, DESTINATION);
+
+        let speed = average_speed(&points).unwrap();
+
+        assert!((speed - meters / 600.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn average_speed_needs_time_span() {
+        assert_eq!(average_speed(&[]), None);
+        assert_eq!(average_speed(&[point(ORIGIN, 0)]), None);
+        assert_eq!(
+            average_speed(&[point(ORIGIN, 0), point(along(0.5), 0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn progress_without_positions() {
+        let progress = estimate_progress(ORIGIN, DESTINATION, &[]);
+
+        assert_eq!(progress.percent_complete, 0.0);
+        assert_eq!(
+            progress.remaining_distance,
+            haversine_meters(ORIGIN, DESTINATION)
+        );
+        assert_eq!(progress.average_speed, None);
+        assert_eq!(progress.remaining_seconds, None);
+        assert_eq!(progress.estimated_arrival, None);
+    }
+
+    #[test]
+    fn progress_on_the_way() {
+        let points = [point(along(0.0), 0), point(along(0.5), 600)];
+
+        let progress = estimate_progress(ORIGIN, DESTINATION, &points);
+
+        assert!((progress.percent_complete - 50.0).abs() < 0.1);
+        assert_eq!(progress.remaining_seconds, Some(600));
+        assert_eq!(progress.estimated_arrival, Some(at(1200)));
+    }
+
+    #[test]
+    fn progress_with_zero_time_span() {
+        let points = [point(along(0.5), 600), point(along(0.5), 600)];
+
+        let progress = estimate_progress(ORIGIN, DESTINATION, &points);
+
+        assert!((progress.percent_complete - 50.0).abs() < 0.1);
+        assert_eq!(progress.average_speed, None);
+        assert_eq!(progress.remaining_seconds, None);
+        assert_eq!(progress.estimated_arrival, None);
+    }
+
+    #[test]
+    fn progress_while_stopped() {
+        // About 1 meter in 10 minutes, well below `MIN_SPEED`.
+        let points = [
+            point(along(0.5), 0),
+            point((along(0.5).0 + 0.00001, along(0.5).1), 600),
+        ];
+
+        let progress = estimate_progress(ORIGIN, DESTINATION, &points);
+
+        assert!(progress.average_speed.unwrap() < MIN_SPEED);
+        assert_eq!(progress.remaining_seconds, None);
+        assert_eq!(progress.estimated_arrival, None);
+    }
+
+    #[test]
+    fn progress_moving_away() {
+        let behind = (ORIGIN.0, ORIGIN.1 - 0.05);
+        let points = [point(ORIGIN, 0), point(behind, 600)];
+
+        let progress = estimate_progress(ORIGIN, DESTINATION, &points);
+
+        // Farther than at the start: no progress, but the arrival is still
+        // estimated from the speed.
+        assert_eq!(progress.percent_complete, 0.0);
+        assert!(progress.remaining_distance > haversine_meters(ORIGIN, DESTINATION));
+        assert!(progress.remaining_seconds.unwrap() > 600);
+    }
+
+    #[test]
+    fn progress_at_the_destination() {
+        let progress = estimate_progress(DESTINATION, DESTINATION, &[]);
+
+        assert_eq!(progress.percent_complete, 100.0);
+        assert_eq!(progress.remaining_distance, 0.0);
+    }
+}
@@ -0,0 +1,130 @@
+use actix_web::{
+    http::header::{self, EntityTag, Header, IfMatch, IfNoneMatch},
+    HttpRequest,
+};
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::{
+    error::{ErrorMessage, HttpError},
+    models::{
+        collaborator::Collaborator, driver::Driver, geofence::Geofence, route::Route,
+        vehicle::Vehicle,
+    },
+};
+
+/// Implemented by resources whose `updated_at` column is maintained by the
+/// `update_updated_on_table` trigger, so it can be used as a version marker.
+pub trait Versioned {
+    fn entity_tag(&self) -> EntityTag;
+
+    fn updated_at(&self) -> NaiveDateTime;
+}
+
+impl Versioned for Vehicle {
+    fn entity_tag(&self) -> EntityTag {
+        entity_tag(&self.id, &self.updated_at)
+    }
+
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+impl Versioned for Route {
+    fn entity_tag(&self) -> EntityTag {
+        entity_tag(&self.id, &self.updated_at)
+    }
+
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+impl Versioned for Geofence {
+    fn entity_tag(&self) -> EntityTag {
+        entity_tag(&self.id, &self.updated_at)
+    }
+
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+impl Versioned for Driver {
+    fn entity_tag(&self) -> EntityTag {
+        entity_tag(&self.id, &self.updated_at)
+    }
+
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+impl Versioned for Collaborator {
+    fn entity_tag(&self) -> EntityTag {
+        entity_tag(&self.id, &self.updated_at)
+    }
+
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+pub fn entity_tag(id: &Uuid, updated_at: &NaiveDateTime) -> EntityTag {
+    EntityTag::new_strong(format!(
+        "{}-{}",
+        id.simple(),
+        updated_at.and_utc().timestamp_micros()
+    ))
+}
+
+/// Fails with `412 Precondition Failed` when the request carries an `If-Match`
+/// header that does not match the current entity tag. Requests without the
+/// header are always allowed.
+///
+/// On success, returns the `updated_at` the entity must still have when it is
+/// written, so the write can be made conditional on it: another client may
+/// change the entity between this check and the write. It is `None` when the
+/// request doesn't ask for a version.
+pub fn check_if_match<T: Versioned>(
+    request: &HttpRequest,
+    current: &T,
+) -> Result<Option<NaiveDateTime>, HttpError> {
+    if !request.headers().contains_key(header::IF_MATCH) {
+        return Ok(None);
+    }
+
+    match IfMatch::parse(request) {
+        Ok(IfMatch::Any) => Ok(None),
+        Ok(IfMatch::Items(tags)) if tags.iter().any(|tag| tag.strong_eq(&current.entity_tag())) => {
+            Ok(Some(current.updated_at()))
+        }
+        Ok(_) => Err(HttpError::from_error_message(
+            ErrorMessage::PreconditionFailed,
+        )),
+        Err(_) => Err(HttpError::bad_request("The If-Match header is malformed")),
+    }
+}
+
+/// The error for a conditional write that found no row: the entity changed
+/// since `check_if_match` when a version was expected, or it doesn't exist.
+pub fn missing_or_modified(
+    expected_updated_at: Option<NaiveDateTime>,
+    not_found: ErrorMessage,
+) -> HttpError {
+    match expected_updated_at {
+        Some(_) => HttpError::from_error_message(ErrorMessage::PreconditionFailed),
+        None => HttpError::from_error_message(not_found),
+    }
+}
+
+/// Returns `true` when the `If-None-Match` header matches the current entity
+/// tag, meaning the client copy is still fresh and a `304` can be returned.
+pub fn is_not_modified(request: &HttpRequest, current: &EntityTag) -> bool {
+    match IfNoneMatch::parse(request) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(current)),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    cell::Cell,
+    future::Future,
+    io::{Read, Seek},
+    rc::Rc,
+};
+
+use actix_web::{
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType},
+    web::Bytes,
+    HttpRequest, HttpResponse,
+};
+use futures_util::{future, stream, Stream, StreamExt, TryStreamExt};
+use rust_xlsxwriter::Workbook;
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
+};
+
+use crate::{dtos::request::RequestQueryDTO, error::HttpError};
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv";
+pub const XLSX_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// Rows fetched per query while exporting a whole table.
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Bytes read per chunk while streaming an XLSX file back.
+const XLSX_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => CSV_CONTENT_TYPE,
+            ExportFormat::Xlsx => XLSX_CONTENT_TYPE,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// Returns the spreadsheet format requested through `?format=` or, when the
+/// query parameter is absent, through the `Accept` header. `None` means the
+/// regular JSON response should be sent.
+pub fn export_format(request: &HttpRequest, format: Option<ExportFormat>) -> Option<ExportFormat> {
+    if format.is_some() {
+        return format;
+    }
+
+    let accept = request.headers().get(header::ACCEPT)?.to_str().ok()?;
+
+    accept
+        .split(',')
+        .map(|media_type| media_type.split(';').next().unwrap_or_default().trim())
+        .find_map(|media_type| match media_type {
+            CSV_CONTENT_TYPE => Some(ExportFormat::Csv),
+            XLSX_CONTENT_TYPE => Some(ExportFormat::Xlsx),
+            _ => None,
+        })
+}
+
+/// Turns a `list_*` query into a stream of rows.
+///
+/// When the request asks for a specific `page`, only that page is exported.
+/// Otherwise the whole table is walked in chunks of `EXPORT_PAGE_SIZE`, each
+/// one fetched after the `key` of the last row of the previous one, in the
+/// order of the query. Unlike an `OFFSET`, the key doesn't make the database
+/// skip the rows already exported, so the last chunks are as fast as the
+/// first, and large exports never hold more than one chunk in memory.
+pub fn paginate<T, K, F, Fut>(
+    query: &RequestQueryDTO,
+    key: fn(&T) -> K,
+    mut fetch: F,
+) -> impl Stream<Item = Result<T, sqlx::Error>>
+where
+    F: FnMut(u32, usize, Option<K>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+{
+    let single_page = query.page.is_some();
+    let page = query.page.unwrap_or(1) as u32;
+    let limit = match single_page {
+        true => query.limit.unwrap_or(50),
+        false => EXPORT_PAGE_SIZE,
+    };
+
+    stream::try_unfold(Some(None), move |after| {
+        let rows = after.map(|after| fetch(page, limit, after));
+
+        async move {
+            match rows {
+                Some(rows) => {
+                    let rows = rows.await?;
+                    let next_after = match rows.last() {
+                        Some(last) if !single_page && rows.len() == limit => Some(Some(key(last))),
+                        _ => None,
+                    };
+
+                    Ok::<_, sqlx::Error>(Some((stream::iter(rows.into_iter().map(Ok)), next_after)))
+                }
+                None => Ok(None),
+            }
+        }
+    })
+    .try_flatten()
+}
+
+/// Builds the response for an export. Column headers are the serialized
+/// (camelCase) field names of `T`, written even when there are no rows.
+pub async fn export_response<T, S>(
+    format: ExportFormat,
+    file_name: &str,
+    rows: S,
+) -> Result<HttpResponse, HttpError>
+where
+    T: Serialize + DeserializeOwned + 'static,
+    S: Stream<Item = Result<T, sqlx::Error>> + 'static,
+{
+    let mut response = HttpResponse::Ok();
+
+    response
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "{file_name}.{}",
+                format.extension()
+            ))],
+        });
+
+    match format {
+        ExportFormat::Csv => Ok(response.streaming(csv_stream(rows))),
+        ExportFormat::Xlsx => Ok(response.streaming(xlsx_stream(rows).await?)),
+    }
+}
+
+fn csv_stream<T, S>(rows: S) -> impl Stream<Item = Result<Bytes, HttpError>>
+where
+    T: Serialize + DeserializeOwned,
+    S: Stream<Item = Result<T, sqlx::Error>>,
+{
+    // The header is written with the first row, or alone after the last one
+    // when there are none.
+    let has_headers = Rc::new(Cell::new(true));
+    let missing_headers = Rc::clone(&has_headers);
+
+    let rows = rows.map(move |row| {
+        let row = row.map_err(HttpError::from)?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(has_headers.replace(false))
+            .from_writer(vec![]);
+
+        writer
+            .serialize(row)
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        writer
+            .into_inner()
+            .map(Bytes::from)
+            .map_err(|e| HttpError::server_error(e.to_string()))
+    });
+
+    let headers = stream::once(future::lazy(move |_| missing_headers.get()))
+        .filter_map(|missing| future::ready(missing.then(csv_headers::<T>)));
+
+    rows.chain(headers)
+}
+
+fn csv_headers<T: DeserializeOwned>() -> Result<Bytes, HttpError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    writer
+        .write_record(field_names::<T>())
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    writer
+        .into_inner()
+        .map(Bytes::from)
+        .map_err(|e| HttpError::server_error(e.to_string()))
+}
+
+/// The serialized field names of the struct `T`, read from its `Deserialize`
+/// implementation, which lists them without needing a value.
+fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields = None;
+    let _ = T::deserialize(FieldNames(&mut fields));
+
+    fields.unwrap_or_default()
+}
+
+/// A deserializer that records the fields of the struct asked for and fails.
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+
+        Err(de::Error::custom("field names recorded"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// XLSX files are zip archives and can only be sent once complete. The
+/// worksheet is written in constant memory mode, which flushes every row to a
+/// temporary file, and the workbook is then saved to another temporary file
+/// and streamed back in chunks, so neither the table nor the archive is held
+/// in memory.
+async fn xlsx_stream<T, S>(
+    rows: S,
+) -> Result<impl Stream<Item = Result<Bytes, HttpError>>, HttpError>
+where
+    T: Serialize + DeserializeOwned,
+    S: Stream<Item = Result<T, sqlx::Error>>,
+{
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+
+    worksheet
+        .deserialize_headers::<T>(0, 0)
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let mut rows = Box::pin(rows);
+
+    while let Some(row) = rows.next().await {
+        let row = row.map_err(HttpError::from)?;
+
+        worksheet
+            .serialize(&row)
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
+    // The file has no name, so it is removed once closed, after the last
+    // chunk or when the client disconnects.
+    let mut file = tempfile::tempfile().map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    workbook
+        .save_to_writer(&mut file)
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    file.rewind()
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(stream::try_unfold(file, |mut file| async move {
+        let mut chunk = vec![0; XLSX_CHUNK_SIZE];
+        let read = file
+            .read(&mut chunk)
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        chunk.truncate(read);
+
+        Ok((read > 0).then(|| (Bytes::from(chunk), file)))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// Pages through `table`, recording the arguments of every query.
+    async fn export(
+        page: Option<usize>,
+        limit: Option<usize>,
+        table: &[u32],
+    ) -> (Vec<u32>, Vec<(u32, usize, Option<u32>)>) {
+        let query = RequestQueryDTO {
+            page,
+            limit,
+            format: Some(ExportFormat::Csv),
+        };
+        let calls = RefCell::new(vec![]);
+
+        let rows = paginate(
+            &query,
+            |row: &u32| *row,
+            |page, limit, after| {
+                calls.borrow_mut().push((page, limit, after));
+
+                let offset = (page as usize - 1) * limit;
+                let rows = table
+                    .iter()
+                    .copied()
+                    .filter(|row| match after {
+                        Some(after) => *row > after,
+                        None => true,
+                    })
+                    .skip(offset)
+                    .take(limit)
+                    .collect();
+
+                future::ready(Ok(rows))
+            },
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+        (rows, calls.into_inner())
+    }
+
+    #[actix_web::test]
+    async fn paginate_walks_the_table_after_the_last_key() {
+        let table: Vec<u32> = (1..=1200).collect();
+
+        let (rows, calls) = export(None, None, &table).await;
+
+        assert_eq!(rows, table);
+        assert_eq!(
+            calls,
+            [(1, 500, None), (1, 500, Some(500)), (1, 500, Some(1000))]
+        );
+    }
+
+    #[actix_web::test]
+    async fn paginate_stops_after_a_full_last_chunk() {
+        let table: Vec<u32> = (1..=1000).collect();
+
+        let (rows, calls) = export(None, None, &table).await;
+
+        assert_eq!(rows, table);
+        assert_eq!(calls.last(), Some(&(1, 500, Some(1000))));
+    }
+
+    #[actix_web::test]
+    async fn paginate_exports_only_the_requested_page() {
+        let table: Vec<u32> = (1..=1200).collect();
+
+        let (rows, calls) = export(Some(3), Some(10), &table).await;
+
+        assert_eq!(rows, (21..=30).collect::<Vec<_>>());
+        assert_eq!(calls, [(3, 10, None)]);
+    }
+}
@@ -1,13 +1,45 @@
 use std::collections::HashMap;
 
-use serde::Deserialize;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-use crate::db::country::CountryExt;
+use crate::config::{Config, CountrySourceKind};
+use crate::db::{client::DBClient, country::CountryExt};
+use crate::dtos::country::{CountrySyncReportDTO, RegisterCountryDTO};
 use crate::error::HttpError;
-use crate::models::country::Country;
-use crate::AppState;
 
-#[derive(Debug, Deserialize)]
+/// ISO 3166-1 countries shipped with the binary, generated from the Debian
+/// `iso-codes` package. Columns: name, alpha2, alpha3, numeric3.
+const BUNDLED_COUNTRIES: &str = include_str!("../../data/countries.csv");
+
+/// Where the list of countries comes from.
+#[async_trait]
+pub trait CountrySource: Send + Sync {
+    /// A short name of the source, reported after a sync.
+    fn name(&self) -> &'static str;
+
+    async fn fetch(&self) -> Result<Vec<RegisterCountryDTO>, HttpError>;
+}
+
+/// Reads the ISO 3166-1 dataset bundled with the binary. Needs no network.
+pub struct BundledCountrySource;
+
+#[async_trait]
+impl CountrySource for BundledCountrySource {
+    fn name(&self) -> &'static str {
+        "bundled"
+    }
+
+    async fn fetch(&self) -> Result<Vec<RegisterCountryDTO>, HttpError> {
+        csv::Reader::from_reader(BUNDLED_COUNTRIES.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|e| HttpError::server_error(format!("Invalid bundled countries: {e}")))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ApiCountry {
     name: String,
@@ -16,66 +48,89 @@ struct ApiCountry {
     numeric_code: String,
 }
 
-pub async fn fetch_countries(app_state: &AppState) -> Result<(), HttpError> {
-    let url = "https://countryapi.io/api/all?apikey=".to_owned()
-        + &*std::env::var("COUNTRYAPI_KEY").expect("COUNTRYAPI_KEY must be set");
+/// Fetches the countries from countryapi.io.
+pub struct CountryApiSource {
+    pub api_key: String,
+}
 
-    let response: HashMap<String, ApiCountry> = reqwest::get(&url)
-        .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?
-        .json()
-        .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-    let countries: Vec<Country> = response
-        .into_values()
-        .map(|api_country| Country {
-            id: uuid::Uuid::new_v4(),
-            name: api_country.name,
-            alpha_2: api_country.alpha2_code,
-            alpha_3: api_country.alpha3_code,
-            numeric_3: api_country.numeric_code,
-        })
-        .collect();
-
-    for api_country in countries {
-        let country = app_state
-            .db_client
-            .get_country(
-                None,
-                None,
-                Some(&api_country.alpha_2),
-                Some(&api_country.alpha_3),
-                Some(&api_country.numeric_3),
-            )
+#[async_trait]
+impl CountrySource for CountryApiSource {
+    fn name(&self) -> &'static str {
+        "countryapi"
+    }
+
+    async fn fetch(&self) -> Result<Vec<RegisterCountryDTO>, HttpError> {
+        let url = "https://countryapi.io/api/all?apikey=".to_owned() + &self.api_key;
+
+        // The API key travels in the query string, so the URL is dropped from
+        // the errors before they are logged.
+        let response: HashMap<String, ApiCountry> = reqwest::get(&url)
+            .await
+            .map_err(|e| HttpError::server_error(e.without_url().to_string()))?
+            .json()
             .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-        if let Some(found_country) = country {
-            app_state
-                .db_client
-                .update_country(
-                    found_country.id,
-                    &api_country.name,
-                    &api_country.alpha_2,
-                    &api_country.alpha_3,
-                    &api_country.numeric_3,
-                )
-                .await
-                .map_err(|e| HttpError::server_error(e.to_string()))?;
-        } else {
-            app_state
-                .db_client
-                .save_country(
-                    api_country.name,
-                    api_country.alpha_2,
-                    api_country.alpha_3,
-                    api_country.numeric_3,
-                )
-                .await
-                .map_err(|e| HttpError::server_error(e.to_string()))?;
-        }
+            .map_err(|e| HttpError::server_error(e.without_url().to_string()))?;
+
+        let countries = response
+            .into_values()
+            .map(|api_country| RegisterCountryDTO {
+                name: api_country.name,
+                alpha_2: api_country.alpha2_code,
+                alpha_3: api_country.alpha3_code,
+                numeric_3: api_country.numeric_code,
+            })
+            .collect();
+
+        Ok(countries)
     }
+}
+
+/// Builds the source selected by `country_source` in the configuration.
+pub fn country_source(config: &Config) -> Result<Box<dyn CountrySource>, HttpError> {
+    match config.country_source {
+        CountrySourceKind::Bundled => Ok(Box::new(BundledCountrySource)),
+        CountrySourceKind::CountryApi => match &config.country_api_key {
+            Some(api_key) => Ok(Box::new(CountryApiSource {
+                api_key: api_key.to_owned(),
+            })),
+            None => Err(HttpError::server_error("COUNTRYAPI_KEY is not set")),
+        },
+    }
+}
+
+/// Fetches every country from `source` and upserts them by alpha-2 code in a
+/// single statement.
+///
+/// # Errors
+///
+/// Returns an `HttpError` if the source can't be read, one of its countries
+/// is invalid, or the upsert fails. Nothing is saved in those cases.
+pub async fn fetch_countries(
+    db_client: &DBClient,
+    source: &dyn CountrySource,
+) -> Result<CountrySyncReportDTO, HttpError> {
+    let countries = source.fetch().await?;
+
+    for country in &countries {
+        country.validate().map_err(|e| {
+            HttpError::server_error(format!("Invalid country {}: {e}", country.alpha_2))
+        })?;
+    }
+
+    let total = countries.len();
+
+    let changed = db_client
+        .upsert_countries(countries)
+        .await
+        .map_err(HttpError::from)?;
+
+    let inserted = changed.iter().filter(|inserted| **inserted).count();
 
-    Ok(())
+    Ok(CountrySyncReportDTO {
+        source: source.name().to_string(),
+        total,
+        inserted,
+        updated: changed.len() - inserted,
+        unchanged: total - changed.len(),
+    })
 }
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{city::CityExt, client::DBClient, street::StreetExt},
+    dtos::address::SaveAddressParamsDTO,
+    error::HttpError,
+    utils::string::normalize_street_name,
+};
+
+/// How precise the coordinates of an address are, stored with the address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeocodingAccuracy {
+    /// Sent by the client.
+    Provided,
+    /// The point of the street in the street index.
+    Street,
+    /// The centroid of the city.
+    City,
+}
+
+impl fmt::Display for GeocodingAccuracy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            GeocodingAccuracy::Provided => "provided",
+            GeocodingAccuracy::Street => "street",
+            GeocodingAccuracy::City => "city",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeocodeResult {
+    pub latitude: BigDecimal,
+    pub longitude: BigDecimal,
+    pub accuracy: GeocodingAccuracy,
+}
+
+/// Turns a street of a city into coordinates.
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    /// Returns `None` when neither the street nor the city can be located.
+    async fn geocode(
+        &self,
+        db_client: &DBClient,
+        street: &str,
+        city_id: Uuid,
+    ) -> Result<Option<GeocodeResult>, HttpError>;
+}
+
+/// Works without network access: the street is looked up in the local street
+/// index, falling back to the centroid of the city.
+pub struct OfflineGeocoder;
+
+#[async_trait]
+impl Geocoder for OfflineGeocoder {
+    async fn geocode(
+        &self,
+        db_client: &DBClient,
+        street: &str,
+        city_id: Uuid,
+    ) -> Result<Option<GeocodeResult>, HttpError> {
+        let street = db_client
+            .get_street(city_id, &normalize_street_name(street))
+            .await
+            .map_err(HttpError::from)?;
+
+        if let Some(street) = street {
+            return Ok(Some(GeocodeResult {
+                latitude: street.latitude,
+                longitude: street.longitude,
+                accuracy: GeocodingAccuracy::Street,
+            }));
+        }
+
+        let city = db_client
+            .get_city(Some(city_id), None)
+            .await
+            .map_err(HttpError::from)?;
+
+        Ok(city.and_then(|city| match (city.latitude, city.longitude) {
+            (Some(latitude), Some(longitude)) => Some(GeocodeResult {
+                latitude,
+                longitude,
+                accuracy: GeocodingAccuracy::City,
+            }),
+            _ => None,
+        }))
+    }
+}
+
+/// Returns the geocoder, or `None` when `features.geocoding` is off.
+pub fn geocoder(config: &Config) -> Option<Box<dyn Geocoder>> {
+    match config.features.geocoding {
+        true => Some(Box::new(OfflineGeocoder)),
+        false => None,
+    }
+}
+
+/// Fills in the coordinates of an address that is about to be saved without
+/// them. Addresses that can't be located are saved without coordinates.
+pub async fn geocode_address(
+    db_client: &DBClient,
+    config: &Config,
+    params: &mut SaveAddressParamsDTO<String, BigDecimal>,
+) -> Result<(), HttpError> {
+    if params.latitude.is_some() && params.longitude.is_some() {
+        return Ok(());
+    }
+
+    let Some(geocoder) = geocoder(config) else {
+        return Ok(());
+    };
+
+    let Ok(city_id) = Uuid::parse_str(&params.city_id) else {
+        return Ok(());
+    };
+
+    if let Some(result) = geocoder
+        .geocode(db_client, &params.address, city_id)
+        .await?
+    {
+        params.latitude = Some(result.latitude);
+        params.longitude = Some(result.longitude);
+        params.geocoding_accuracy = Some(result.accuracy.to_string());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use std::{collections::HashSet, fmt};
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use geoutils::Location;
+use uuid::Uuid;
+use validator::ValidationError;
+
+use crate::{
+    models::geofence::{Geofence, GeofenceVertex},
+    utils::spatial::degree_deltas,
+};
+
+/// What a geofence stands for, stored with the geofence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeofenceKind {
+    Depot,
+    CustomerSite,
+    RestrictedZone,
+}
+
+impl GeofenceKind {
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "depot" => Some(GeofenceKind::Depot),
+            "customer_site" => Some(GeofenceKind::CustomerSite),
+            "restricted_zone" => Some(GeofenceKind::RestrictedZone),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GeofenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            GeofenceKind::Depot => "depot",
+            GeofenceKind::CustomerSite => "customer_site",
+            GeofenceKind::RestrictedZone => "restricted_zone",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeofenceShape {
+    /// A center and a radius in meters.
+    Circle,
+    /// A closed ring of vertices. The last vertex connects back to the first.
+    Polygon,
+}
+
+impl fmt::Display for GeofenceShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            GeofenceShape::Circle => "circle",
+            GeofenceShape::Polygon => "polygon",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeofenceEventType {
+    Enter,
+    Exit,
+}
+
+impl fmt::Display for GeofenceEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            GeofenceEventType::Enter => "enter",
+            GeofenceEventType::Exit => "exit",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+/// A geofence entered or left at one of the evaluated positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeofenceTransition {
+    pub geofence_id: Uuid,
+    pub event_type: GeofenceEventType,
+    /// The index of the position where it happened.
+    pub position: usize,
+}
+
+pub fn is_valid_geofence_kind(kind: &str) -> Result<(), ValidationError> {
+    match GeofenceKind::from_code(kind) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("Is not a valid geofence kind")),
+    }
+}
+
+/// The `[min_lat, max_lat, min_long, max_long]` box around a circle.
+pub fn circle_bounds(latitude: f64, longitude: f64, radius_meters: f64) -> [f64; 4] {
+    let (latitude_delta, longitude_delta) = degree_deltas(latitude, radius_meters);
+
+    [
+        latitude - latitude_delta,
+        latitude + latitude_delta,
+        longitude - longitude_delta,
+        longitude + longitude_delta,
+    ]
+}
+
+/// The `[min_lat, max_lat, min_long, max_long]` box around a polygon.
+pub fn polygon_bounds(vertices: &[GeofenceVertex]) -> [f64; 4] {
+    vertices.iter().fold(
+        [f64::MAX, f64::MIN, f64::MAX, f64::MIN],
+        |[min_lat, max_lat, min_long, max_long], vertex| {
+            [
+                min_lat.min(vertex.lat),
+                max_lat.max(vertex.lat),
+                min_long.min(vertex.long),
+                max_long.max(vertex.long),
+            ]
+        },
+    )
+}
+
+/// Whether a point lies inside the geofence. Circles use the haversine
+/// distance, polygons are evaluated on the plane of their coordinates, which
+/// is close enough at the size of a site.
+pub fn contains(geofence: &Geofence, latitude: f64, longitude: f64) -> bool {
+    if let Some(vertices) = &geofence.vertices {
+        return polygon_contains(vertices, latitude, longitude);
+    }
+
+    let (Some(center_lat), Some(center_long), Some(radius_meters)) = (
+        geofence.center_lat.as_ref().and_then(BigDecimal::to_f64),
+        geofence.center_long.as_ref().and_then(BigDecimal::to_f64),
+        geofence.radius_meters.as_ref().and_then(BigDecimal::to_f64),
+    ) else {
+        return false;
+    };
+
+    let center = Location::new(center_lat, center_long);
+    let point = Location::new(latitude, longitude);
+
+    point.haversine_distance_to(&center).meters() <= radius_meters
+}
+
+/// Counts the edges crossed by a ray cast from the point towards increasing
+/// longitudes: the point is inside when the count is odd. Points on an edge
+/// or a vertex are inside, like those on the edge of a circle.
+fn polygon_contains(vertices: &[GeofenceVertex], latitude: f64, longitude: f64) -> bool {
+    let mut inside = false;
+    let mut previous = match vertices.last() {
+        Some(vertex) => vertex,
+        None => return false,
+    };
+
+    for vertex in vertices {
+        if on_edge(previous, vertex, latitude, longitude) {
+            return true;
+        }
+
+        if (vertex.lat > latitude) != (previous.lat > latitude) {
+            let crossing = vertex.long
+                + (latitude - vertex.lat) * (previous.long - vertex.long)
+                    / (previous.lat - vertex.lat);
+
+            if longitude < crossing {
+                inside = !inside;
+            }
+        }
+
+        previous = vertex;
+    }
+
+    inside
+}
+
+/// Whether the point lies on the segment between `start` and `end`.
+fn on_edge(start: &GeofenceVertex, end: &GeofenceVertex, latitude: f64, longitude: f64) -> bool {
+    let cross = (end.lat - start.lat) * (longitude - start.long)
+        - (end.long - start.long) * (latitude - start.lat);
+
+    cross.abs() <= 1e-12
+        && latitude >= start.lat.min(end.lat)
+        && latitude <= start.lat.max(end.lat)
+        && longitude >= start.long.min(end.long)
+        && longitude <= start.long.max(end.long)
+}
+
+/// Walks `positions` in order and reports every time one of `geofences` is
+/// entered or left. `inside` holds the geofences the route was in before the
+/// first position, which are left as soon as a position falls outside them,
+/// even when they aren't in `geofences`.
+pub fn detect_transitions(
+    geofences: &[Geofence],
+    mut inside: HashSet<Uuid>,
+    positions: &[(f64, f64)],
+) -> Vec<GeofenceTransition> {
+    let mut transitions = vec![];
+
+    for (index, &(latitude, longitude)) in positions.iter().enumerate() {
+        let current: HashSet<Uuid> = geofences
+            .iter()
+            .filter(|geofence| contains(geofence, latitude, longitude))
+            .map(|geofence| geofence.id)
+            .collect();
+
+        for &geofence_id in current.difference(&inside) {
+            transitions.push(GeofenceTransition {
+                geofence_id,
+                event_type: GeofenceEventType::Enter,
+                position: index,
+            });
+        }
+
+        for &geofence_id in inside.difference(&current) {
+            transitions.push(GeofenceTransition {
+                geofence_id,
+                event_type: GeofenceEventType::Exit,
+                position: index,
+            });
+        }
+
+        inside = current;
+    }
+
+    transitions
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::FromPrimitive;
+    use chrono::NaiveDateTime;
+    use sqlx::types::Json;
+
+    use super::*;
+
+    fn vertices(points: &[(f64, f64)]) -> Vec<GeofenceVertex> {
+        points
+            .iter()
+            .map(|&(lat, long)| GeofenceVertex { lat, long })
+            .collect()
+    }
+
+    fn polygon(points: &[(f64, f64)]) -> Geofence {
+        Geofence {
+            id: Uuid::new_v4(),
+            name: "polygon".to_string(),
+            kind: GeofenceKind::Depot.to_string(),
+            shape: GeofenceShape::Polygon.to_string(),
+            center_lat: None,
+            center_long: None,
+            radius_meters: None,
+            vertices: Some(Json(vertices(points))),
+            address_id: None,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn circle(latitude: f64, longitude: f64, radius_meters: f64) -> Geofence {
+        Geofence {
+            shape: GeofenceShape::Circle.to_string(),
+            center_lat: BigDecimal::from_f64(latitude),
+            center_long: BigDecimal::from_f64(longitude),
+            radius_meters: BigDecimal::from_f64(radius_meters),
+            vertices: None,
+            ..polygon(&[])
+        }
+    }
+
+    /// A U open towards the north: the notch between longitudes 3 and 7,
+    /// above latitude 4, is outside.
+    const U: [(f64, f64); 8] = [
+        (0.0, 0.0),
+        (0.0, 10.0),
+        (10.0, 10.0),
+        (10.0, 7.0),
+        (4.0, 7.0),
+        (4.0, 3.0),
+        (10.0, 3.0),
+        (10.0, 0.0),
+    ];
+
+    const DIAMOND: [(f64, f64); 4] = [(0.0, 5.0), (5.0, 10.0), (10.0, 5.0), (5.0, 0.0)];
+
+    #[test]
+    fn polygon_contains_concave_polygon() {
+        let u = vertices(&U);
+
+        assert!(polygon_contains(&u, 2.0, 5.0));
+        assert!(polygon_contains(&u, 7.0, 1.0));
+        assert!(polygon_contains(&u, 7.0, 9.0));
+        assert!(!polygon_contains(&u, 7.0, 5.0));
+        assert!(!polygon_contains(&u, 11.0, 5.0));
+        assert!(!polygon_contains(&u, 5.0, -1.0));
+    }
+
+    #[test]
+    fn polygon_contains_boundary() {
+        let diamond = vertices(&DIAMOND);
+
+        for &(latitude, longitude) in &DIAMOND {
+            assert!(polygon_contains(&diamond, latitude, longitude));
+        }
+
+        assert!(polygon_contains(&diamond, 2.5, 7.5));
+        assert!(polygon_contains(&vertices(&U), 4.0, 5.0));
+    }
+
+    #[test]
+    fn polygon_contains_ray_through_vertex() {
+        let diamond = vertices(&DIAMOND);
+
+        // The ray cast from these points passes through the vertices at
+        // latitude 5, which must be counted once.
+        assert!(polygon_contains(&diamond, 5.0, 2.0));
+        assert!(!polygon_contains(&diamond, 5.0, -1.0));
+        assert!(!polygon_contains(&diamond, 5.0, 11.0));
+    }
+
+    #[test]
+    fn polygon_contains_needs_vertices() {
+        assert!(!polygon_contains(&[], 0.0, 0.0));
+    }
+
+    #[test]
+    fn circle_contains_by_distance() {
+        let geofence = circle(-23.55, -46.63, 100.0);
+
+        assert!(contains(&geofence, -23.55, -46.63));
+        assert!(contains(&geofence, -23.5505, -46.63));
+        assert!(!contains(&geofence, -23.552, -46.63));
+    }
+
+    #[test]
+    fn detect_transitions_enter_and_exit() {
+        let geofence = polygon(&U);
+        let positions = [(-1.0, 5.0), (2.0, 5.0), (2.0, 6.0), (7.0, 5.0)];
+
+        assert_eq!(
+            detect_transitions(std::slice::from_ref(&geofence), HashSet::new(), &positions),
+            vec![
+                GeofenceTransition {
+                    geofence_id: geofence.id,
+                    event_type: GeofenceEventType::Enter,
+                    position: 1,
+                },
+                GeofenceTransition {
+                    geofence_id: geofence.id,
+                    event_type: GeofenceEventType::Exit,
+                    position: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_transitions_from_previous_state() {
+        let geofence = polygon(&U);
+
+        // Already inside: staying there isn't an entry.
+        assert!(detect_transitions(
+            std::slice::from_ref(&geofence),
+            HashSet::from([geofence.id]),
+            &[(2.0, 5.0), (1.0, 5.0)],
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn detect_transitions_exit_outside_batch() {
+        let geofence_id = Uuid::new_v4();
+
+        // The geofence isn't loaded, as it doesn't overlap the batch, but the
+        // route was inside it: the first position leaves it.
+        assert_eq!(
+            detect_transitions(
+                &[],
+                HashSet::from([geofence_id]),
+                &[(50.0, 50.0), (51.0, 51.0)]
+            ),
+            vec![GeofenceTransition {
+                geofence_id,
+                event_type: GeofenceEventType::Exit,
+                position: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn detect_transitions_without_positions() {
+        let geofence = polygon(&U);
+
+        assert!(detect_transitions(
+            std::slice::from_ref(&geofence),
+            HashSet::from([geofence.id]),
+            &[]
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,469 @@
+use actix_web::{http::header, HttpRequest};
+
+use crate::error::ErrorMessage;
+
+tokio::task_local! {
+    /// The language negotiated for the request being handled. Set by the
+    /// `Locale` middleware around every request.
+    pub static LANGUAGE: Language;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    En,
+    PtBr,
+}
+
+impl Language {
+    /// Parses a BCP 47 language tag such as `pt-BR`, `pt` or `en-US`. Only the
+    /// primary subtag is considered.
+    pub fn from_tag(tag: &str) -> Option<Language> {
+        let primary = tag.trim().split(['-', '_']).next().unwrap_or_default();
+
+        match primary.to_ascii_lowercase().as_str() {
+            "en" => Some(Language::En),
+            "pt" => Some(Language::PtBr),
+            _ => None,
+        }
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::PtBr => "pt-BR",
+        }
+    }
+
+    /// Picks the supported language with the highest quality value in the
+    /// `Accept-Language` header, or `default` when none is supported.
+    pub fn from_request(request: &HttpRequest, default: Language) -> Language {
+        let Some(accept_language) = request
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return default;
+        };
+
+        let mut languages: Vec<(Language, f32)> = accept_language
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|quality| quality.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+
+                let language = match tag {
+                    "*" => default,
+                    tag => Language::from_tag(tag)?,
+                };
+
+                (quality > 0.0).then_some((language, quality))
+            })
+            .collect();
+
+        // A stable sort keeps the header order for equal quality values.
+        languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        languages
+            .first()
+            .map(|(language, _)| *language)
+            .unwrap_or(default)
+    }
+
+    /// The language of the current request, or English outside of one.
+    pub fn current() -> Language {
+        LANGUAGE
+            .try_with(|language| *language)
+            .unwrap_or(Language::En)
+    }
+}
+
+/// Translates a free-text message into the language of the current request.
+///
+/// These are the messages written in place across the code base, such as
+/// validation messages, and their English text is the key of the `PT_BR`
+/// table. Messages without a translation are returned unchanged.
+pub fn translate(message: &str) -> String {
+    match Language::current() {
+        Language::En => message.to_string(),
+        Language::PtBr => PT_BR
+            .iter()
+            .find(|(english, _)| *english == message)
+            .map_or(message, |(_, translation)| translation)
+            .to_string(),
+    }
+}
+
+/// The message and hint of `error_message` in the language of the current
+/// request.
+pub fn translate_error(error_message: ErrorMessage) -> (&'static str, &'static str) {
+    match Language::current() {
+        Language::En => (error_message.to_str(), error_message.hint()),
+        Language::PtBr => pt_br_error(error_message),
+    }
+}
+
+/// A message shown to the user, translated into the language of the current
+/// request.
+pub trait Translate {
+    fn translate(&self) -> String;
+}
+
+impl Translate for ErrorMessage {
+    fn translate(&self) -> String {
+        translate_error(*self).0.to_string()
+    }
+}
+
+impl Translate for str {
+    fn translate(&self) -> String {
+        translate(self)
+    }
+}
+
+impl Translate for String {
+    fn translate(&self) -> String {
+        translate(self)
+    }
+}
+
+impl<T: Translate + ?Sized> Translate for &T {
+    fn translate(&self) -> String {
+        (**self).translate()
+    }
+}
+
+fn pt_br_error(error_message: ErrorMessage) -> (&'static str, &'static str) {
+    match error_message {
+        ErrorMessage::ServerError => (
+            "Ocorreu um erro no servidor. Tente novamente mais tarde",
+            "Verifique os logs do servidor para mais detalhes e confirme que o servidor está funcionando corretamente",
+        ),
+        ErrorMessage::CountryExist => (
+            "Já existe um país com os dados informados",
+            "Verifique se os dados do país que você está tentando adicionar são únicos e ainda não existem",
+        ),
+        ErrorMessage::CountryNotFound => (
+            "O país com o ID, alpha2, alpha3 ou numeric3 informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o countryId, alpha2, alpha3 ou numeric3 está correto e existe no banco de dados. Use 'GET /api/v1/countries' para consultar os IDs e códigos ISO 3166 disponíveis",
+        ),
+        ErrorMessage::StateExist => (
+            "Já existe um estado com o código e countryId informados",
+            "Verifique se o código do estado e o countryId são únicos e ainda não existem",
+        ),
+        ErrorMessage::StateNotFound => (
+            "O estado com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o stateId está correto e existe no banco de dados. Use 'GET /api/v1/states' para consultar os IDs de estados disponíveis",
+        ),
+        ErrorMessage::CityExist => (
+            "Já existe uma cidade com o código informado",
+            "Verifique se o código da cidade é único e ainda não existe",
+        ),
+        ErrorMessage::CityNotFound => (
+            "A cidade com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o cityId está correto e existe no banco de dados. Use 'GET /api/v1/cities' para consultar os IDs de cidades disponíveis",
+        ),
+        ErrorMessage::AddressExist => (
+            "Já existe um endereço com os dados informados (address, number, zipCode)",
+            "Verifique se os dados do endereço (address, number, zipCode) são únicos e ainda não existem",
+        ),
+        ErrorMessage::AddressNotFound => (
+            "O endereço com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o addressId está correto e existe no banco de dados. Use 'GET /api/v1/addresses' para consultar os IDs de endereços disponíveis",
+        ),
+        ErrorMessage::ZipCodeNotFound => (
+            "O CEP informado não foi encontrado. Verifique e tente novamente",
+            "Confirme que o CEP está correto. Ele deve ter 8 dígitos, com ou sem o hífen",
+        ),
+        ErrorMessage::CollaboratorExist => (
+            "Já existe um colaborador com o email ou CPF informado",
+            "Verifique se os dados do colaborador (email, cpf) são únicos e ainda não existem",
+        ),
+        ErrorMessage::CollaboratorNotFound => (
+            "O colaborador com o ID, email ou CPF informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o collaboratorId, email ou CPF está correto e existe no banco de dados. Use 'GET /api/v1/collaborators' para consultar os IDs de colaboradores disponíveis",
+        ),
+        ErrorMessage::VehicleExist => (
+            "Já existe um veículo com os dados informados",
+            "Confirme que as informações do veículo são únicas e ainda não existem",
+        ),
+        ErrorMessage::VehicleNotFound => (
+            "O veículo com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o vehicleId está correto e existe no banco de dados. Use 'GET /api/v1/vehicles' para consultar os IDs de veículos disponíveis",
+        ),
+        ErrorMessage::VehicleDocumentExist => (
+            "Já existe um documento de veículo com o chassisNumber, registrationNumber ou plate informado",
+            "Verifique se os dados do documento do veículo (chassisNumber, registrationNumber, plate) são únicos e ainda não existem",
+        ),
+        ErrorMessage::VehicleDocumentNotFound => (
+            "O documento do veículo com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o vehicleId, chassisNumber, registrationNumber ou plate está correto e existe no banco de dados. Use 'GET /api/v1/vehicles' e 'GET /api/v1/vehicles/{vehicleId}/documents' para consultar os IDs e documentos de veículos disponíveis",
+        ),
+        ErrorMessage::RouteNotFound => (
+            "A rota com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o routeId está correto e existe no banco de dados. Use 'GET /api/v1/routes' para consultar os IDs de rotas disponíveis",
+        ),
+        ErrorMessage::RoutePositionsOutOfOrder => (
+            "As posições são anteriores à última posição registrada para a rota",
+            "Envie as posições na ordem em que foram registradas. Use 'GET /api/v1/routes/{routeId}/positions' para consultar a última",
+        ),
+        ErrorMessage::GeofenceNotFound => (
+            "A cerca virtual com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o geofenceId está correto e existe no banco de dados. Use 'GET /api/v1/geofences' para consultar os IDs de cercas virtuais disponíveis",
+        ),
+        ErrorMessage::WebhookSubscriptionNotFound => (
+            "A assinatura de webhook com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o subscriptionId está correto e existe no banco de dados. Use 'GET /api/v1/webhooks' para consultar os IDs de assinaturas disponíveis",
+        ),
+        ErrorMessage::WebhookDeliveryNotFound => (
+            "A entrega de webhook com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o deliveryId está correto e pertence à assinatura. Use 'GET /api/v1/webhooks/{subscriptionId}/deliveries' para consultar as suas entregas",
+        ),
+        ErrorMessage::WebhookDeliveryNotRetryable => (
+            "A entrega de webhook está pendente e pode estar sendo enviada",
+            "Apenas entregas com falha, ou pendentes após o seu 'nextAttemptAt', podem ser reenviadas. Tente novamente depois dele",
+        ),
+        ErrorMessage::RouteStatusExist => (
+            "Já existe um status com os dados informados para esta rota",
+            "Verifique se o código do status da rota é único e ainda não existe",
+        ),
+        ErrorMessage::RouteStatusNotFound => (
+            "O status da rota com o ID informado não existe em nossos registros. Verifique e tente novamente",
+            "Confirme que o routeId está correto e existe no banco de dados. Use 'GET /api/v1/routes' para consultar os IDs de rotas disponíveis",
+        ),
+        ErrorMessage::DriverNotFound => (
+            "O motorista com o ID informado não existe em nossos registros",
+            "Confirme que o driverId, cnhNumber ou collaboratorId está correto e existe no banco de dados. Use 'GET /api/v1/collaborators' para consultar os IDs de colaboradores e 'GET /api/v1/collaborators/drivers' para consultar os IDs de motoristas disponíveis",
+        ),
+        ErrorMessage::DriverExist => (
+            "Já existe um motorista com os dados informados",
+            "Confirme que o cnhNumber e o collaboratorId são únicos e ainda não existem",
+        ),
+        ErrorMessage::CnhTypeNotFound => (
+            "O tipo de CNH com o ID ou código informado não existe em nossos registros",
+            "Confirme que o cnhTypeId ou código está correto e existe no banco de dados. Use 'GET /api/v1/collaborators/drivers/cnh' para consultar os tipos de CNH disponíveis",
+        ),
+        ErrorMessage::PreconditionFailed => (
+            "O recurso foi modificado desde a última vez em que foi consultado",
+            "Consulte o recurso novamente para obter o ETag atual e repita a requisição com o cabeçalho If-Match atualizado",
+        ),
+        ErrorMessage::IdempotencyKeyInvalid => (
+            "O cabeçalho Idempotency-Key deve ter entre 1 e 255 caracteres",
+            "Envie um valor único, como um UUID, no cabeçalho Idempotency-Key",
+        ),
+        ErrorMessage::IdempotencyKeyInProgress => (
+            "Uma requisição com o Idempotency-Key informado ainda está sendo processada",
+            "Aguarde a requisição original terminar e tente novamente com o mesmo Idempotency-Key",
+        ),
+        ErrorMessage::IdempotencyKeyMismatch => (
+            "O Idempotency-Key informado já foi usado com um corpo de requisição diferente",
+            "Use um novo Idempotency-Key para uma requisição diferente ou reenvie o corpo original",
+        ),
+        ErrorMessage::IdempotencyPayloadTooLarge => (
+            "Requisições com Idempotency-Key devem ter um corpo de até 2 MiB",
+            "Divida o corpo em requisições menores, cada uma com seu próprio Idempotency-Key",
+        ),
+    }
+}
+
+const PT_BR: &[(&str, &str)] = &[
+    // Error responses
+    ("One or more fields are invalid", "Um ou mais campos são inválidos"),
+    ("The If-Match header is malformed", "O cabeçalho If-Match está malformado"),
+    ("Check the request parameters and try again", "Verifique os parâmetros da requisição e tente novamente"),
+    ("Check the fields listed in the response and try again", "Verifique os campos listados na resposta e tente novamente"),
+    ("No endpoint found in request path", "Nenhum endpoint encontrado no caminho da requisição"),
+
+    // Validation messages
+    ("Address must have a maximum of 100 characters", "O endereço deve ter no máximo 100 caracteres"),
+    ("Alpha 2 code must be 2 characters long", "O código alpha 2 deve ter 2 caracteres"),
+    ("Alpha 3 code must be 3 characters long", "O código alpha 3 deve ter 3 caracteres"),
+    ("CNH Type ID must be a valid UUID", "O ID do tipo de CNH deve ser um UUID válido"),
+    ("CNH number must be 11 characters long", "O número da CNH deve ter 11 caracteres"),
+    ("CPF must have a maximum of 11 characters", "O CPF deve ter no máximo 11 caracteres"),
+    ("Chassis number must have between 10 and 17 characters", "O número do chassi deve ter entre 10 e 17 caracteres"),
+    ("City ID must be a valid UUID", "O ID da cidade deve ser um UUID válido"),
+    ("Code must be 2 characters long", "O código deve ter 2 caracteres"),
+    ("Code must be 7 characters long", "O código deve ter 7 caracteres"),
+    ("Collaborator ID must be a valid UUID", "O ID do colaborador deve ser um UUID válido"),
+    ("Color must have between 1 and 60 characters", "A cor deve ter entre 1 e 60 caracteres"),
+    ("Complement must have a maximum of 60 characters", "O complemento deve ter no máximo 60 caracteres"),
+    ("Country ID must be a valid UUID", "O ID do país deve ser um UUID válido"),
+    ("Driver ID must be a valid UUID", "O ID do motorista deve ser um UUID válido"),
+    ("Email must be a valid email address", "O email deve ser um endereço de email válido"),
+    ("Email must have a maximum of 150 characters", "O email deve ter no máximo 150 caracteres"),
+    ("Final address ID must be a valid UUID", "O ID do endereço final deve ser um UUID válido"),
+    ("Initial address ID must be a valid UUID", "O ID do endereço inicial deve ser um UUID válido"),
+    ("Limit must be between 1 and 50", "O limite deve estar entre 1 e 50"),
+    ("Make must have between 1 and 60 characters", "A marca deve ter entre 1 e 60 caracteres"),
+    ("Model must have between 1 and 60 characters", "O modelo deve ter entre 1 e 60 caracteres"),
+    ("Name must have a maximum of 100 characters", "O nome deve ter no máximo 100 caracteres"),
+    ("Name must have a maximum of 50 characters", "O nome deve ter no máximo 50 caracteres"),
+    ("Neighbourhood must have a maximum of 60 characters", "O bairro deve ter no máximo 60 caracteres"),
+    ("Number must have a maximum of 10 characters", "O número deve ter no máximo 10 caracteres"),
+    ("Numeric 3 code must be 3 characters long", "O código numeric 3 deve ter 3 caracteres"),
+    ("Page must be greater than or equal to 1", "A página deve ser maior ou igual a 1"),
+    ("Password must have between 8 and 128 characters", "A senha deve ter entre 8 e 128 caracteres"),
+    ("Plate must have between 5 and 7 characters", "A placa deve ter entre 5 e 7 caracteres"),
+    ("RG must have a maximum of 9 characters", "O RG deve ter no máximo 9 caracteres"),
+    ("Reference must have a maximum of 60 characters", "A referência deve ter no máximo 60 caracteres"),
+    ("Registration number must have between 10 and 20 characters", "O número do registro deve ter entre 10 e 20 caracteres"),
+    ("State ID must be a valid UUID", "O ID do estado deve ser um UUID válido"),
+    ("Status ID must be a valid UUID", "O ID do status deve ser um UUID válido"),
+    ("Vehicle ID must be a valid UUID", "O ID do veículo deve ser um UUID válido"),
+    ("Zip code must have 8 digits", "O CEP deve ter 8 dígitos"),
+
+    // Import messages
+    ("Duplicate CPF in the file", "CPF duplicado no arquivo"),
+    ("Duplicate email in the file", "Email duplicado no arquivo"),
+    ("A collaborator with this CPF already exists", "Já existe um colaborador com este CPF"),
+    ("A collaborator with this email already exists", "Já existe um colaborador com este email"),
+    ("Unknown CNH type code", "Código de tipo de CNH desconhecido"),
+    ("Duplicate collaborator CPF in the file", "CPF de colaborador duplicado no arquivo"),
+    ("The collaborator with this CPF is already a driver", "O colaborador com este CPF já é motorista"),
+    ("No collaborator with this CPF exists", "Não existe colaborador com este CPF"),
+    ("Duplicate CNH number in the file", "Número de CNH duplicado no arquivo"),
+    ("A driver with this CNH number already exists", "Já existe um motorista com este número de CNH"),
+    ("Unknown city code", "Código de cidade desconhecido"),
+    ("Duplicate address (address, number, zipCode) in the file", "Endereço (address, number, zipCode) duplicado no arquivo"),
+    ("Unknown state code", "Código de estado desconhecido"),
+    ("Duplicate code in the file", "Código duplicado no arquivo"),
+    ("State code must be 2 characters long", "O código do estado deve ter 2 caracteres"),
+    ("State name must have a maximum of 100 characters", "O nome do estado deve ter no máximo 100 caracteres"),
+    ("State name differs from a previous row", "O nome do estado difere de uma linha anterior"),
+    ("Duplicate street in the file", "Rua duplicada no arquivo"),
+    ("Latitude must be between -90 and 90", "A latitude deve estar entre -90 e 90"),
+    ("Longitude must be between -180 and 180", "A longitude deve estar entre -180 e 180"),
+    ("Radius must be between 1 and 50000 meters", "O raio deve estar entre 1 e 50000 metros"),
+    ("Bounding box must be minLong,minLat,maxLong,maxLat", "A área deve ser informada como minLong,minLat,maxLong,maxLat"),
+    ("lat, long and radius must be given together", "lat, long e radius devem ser informados juntos"),
+    ("Filter either by radius or by bbox, not both", "Filtre por radius ou por bbox, não pelos dois"),
+    ("Name must have between 1 and 100 characters", "O nome deve ter entre 1 e 100 caracteres"),
+    ("Kind must be one of depot, customer_site, restricted_zone", "O tipo deve ser depot, customer_site ou restricted_zone"),
+    ("A polygon must have between 3 and 1000 vertices", "Um polígono deve ter entre 3 e 1000 vértices"),
+    ("Address ID must be a valid UUID", "O ID do endereço deve ser um UUID válido"),
+    ("A geofence has either a radiusMeters or vertices, not both", "Uma cerca virtual tem radiusMeters ou vertices, não os dois"),
+    ("A geofence needs a radiusMeters for a circle or vertices for a polygon", "Uma cerca virtual precisa de radiusMeters para um círculo ou vertices para um polígono"),
+    ("A polygon is described by its vertices, without centerLat and centerLong", "Um polígono é descrito pelos seus vértices, sem centerLat e centerLong"),
+    ("centerLat and centerLong must be given together", "centerLat e centerLong devem ser informados juntos"),
+    ("A circle needs centerLat and centerLong, or an addressId with coordinates", "Um círculo precisa de centerLat e centerLong, ou de um addressId com coordenadas"),
+    ("Positions must have between 1 and 500 items", "As posições devem ter entre 1 e 500 itens"),
+    ("Search terms must have between 1 and 200 characters", "Os termos da busca devem ter entre 1 e 200 caracteres"),
+    ("Search terms must not be blank", "Os termos da busca não podem estar em branco"),
+    ("The route has no final coordinates to estimate its arrival", "A rota não tem coordenadas finais para estimar a sua chegada"),
+    ("Route IDs must be up to 100 UUIDs separated by commas", "Os IDs de rota devem ser até 100 UUIDs separados por vírgulas"),
+    ("Vehicle IDs must be up to 100 UUIDs separated by commas", "Os IDs de veículo devem ser até 100 UUIDs separados por vírgulas"),
+    ("URL must have up to 2048 characters", "A URL deve ter até 2048 caracteres"),
+    ("URL must be an absolute http or https URL", "A URL deve ser uma URL http ou https absoluta"),
+    ("Event types must have between 1 and 10 items", "Os tipos de evento devem ter entre 1 e 10 itens"),
+    ("Event types must be among route.created, route.started, route.finished, driver.cnh_expired", "Os tipos de evento devem estar entre route.created, route.started, route.finished, driver.cnh_expired"),
+    ("Secret must have between 16 and 128 characters", "O segredo deve ter entre 16 e 128 caracteres"),
+    ("Status must be one of pending, delivered, failed", "O status deve ser pending, delivered ou failed"),
+    ("Entity must be one of country, state, city, address, collaborator, driver, cnh_type, vehicle, vehicle_document, route, route_status, geofence, webhook_subscription", "A entidade deve ser country, state, city, address, collaborator, driver, cnh_type, vehicle, vehicle_document, route, route_status, geofence ou webhook_subscription"),
+    ("ID must be a valid UUID", "O ID deve ser um UUID válido"),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use regex::Regex;
+
+    use super::*;
+
+    /// The string literals given as user-facing messages in the sources: the
+    /// `message` of the validators, and the messages passed to
+    /// `HttpError::bad_request` and `ImportReportDTO::add_error`.
+    fn source_messages(dir: &Path, messages: &mut HashSet<String>) {
+        let patterns = [
+            Regex::new(r#"message = "([^"]*)""#).unwrap(),
+            Regex::new(r#"bad_request\(\s*"([^"]*)""#).unwrap(),
+            Regex::new(r#"add_error\([^;]*?,\s*"([^"]*)",?\s*\);"#).unwrap(),
+        ];
+
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.is_dir() {
+                source_messages(&path, messages);
+            } else if path.extension().is_some_and(|extension| extension == "rs")
+                && !path.ends_with("utils/i18n.rs")
+            {
+                let source = fs::read_to_string(&path).unwrap();
+
+                for pattern in &patterns {
+                    messages.extend(
+                        pattern
+                            .captures_iter(&source)
+                            .map(|captures| captures[1].to_string()),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn free_text_messages_are_translated() {
+        let mut messages = HashSet::new();
+        source_messages(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut messages,
+        );
+
+        let translated: HashSet<&str> = PT_BR.iter().map(|(english, _)| *english).collect();
+        let mut missing: Vec<_> = messages
+            .iter()
+            .filter(|message| !translated.contains(message.as_str()))
+            .collect();
+        missing.sort();
+
+        assert!(
+            messages.len() > 50,
+            "found only {} messages",
+            messages.len()
+        );
+        assert!(
+            missing.is_empty(),
+            "missing pt-BR translations: {missing:#?}"
+        );
+    }
+
+    #[test]
+    fn free_text_messages_are_unique() {
+        let mut seen = HashSet::new();
+
+        for (english, _) in PT_BR {
+            assert!(seen.insert(english), "duplicate message: {english}");
+        }
+    }
+
+    #[test]
+    fn translates_by_language() {
+        let error_message = ErrorMessage::CountryNotFound;
+
+        assert_eq!(
+            translate_error(error_message),
+            (error_message.to_str(), error_message.hint())
+        );
+        assert_eq!(translate("Unknown city code"), "Unknown city code");
+
+        LANGUAGE.sync_scope(Language::PtBr, || {
+            assert_eq!(translate_error(error_message), pt_br_error(error_message));
+            assert_eq!(
+                translate("Unknown city code"),
+                "Código de cidade desconhecido"
+            );
+            assert_eq!(translate("Not in the table"), "Not in the table");
+        });
+    }
+
+    #[test]
+    fn parses_accept_language() {
+        assert_eq!(Language::from_tag("pt-BR"), Some(Language::PtBr));
+        assert_eq!(Language::from_tag("en_US"), Some(Language::En));
+        assert_eq!(Language::from_tag("fr"), None);
+    }
+}
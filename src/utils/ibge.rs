@@ -0,0 +1,78 @@
+use serde::Deserialize;
+
+use crate::{dtos::city::ImportIbgeCityRowDTO, error::HttpError, utils::import::CsvRow};
+
+/// A municipality as returned by the IBGE localities API
+/// (`/api/v1/localidades/municipios`). Only the fields needed to find its
+/// state are read.
+#[derive(Debug, Deserialize)]
+struct IbgeMunicipality {
+    id: u32,
+    nome: String,
+    microrregiao: Option<IbgeMicroregion>,
+    #[serde(rename = "regiao-imediata")]
+    regiao_imediata: Option<IbgeImmediateRegion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbgeMicroregion {
+    mesorregiao: IbgeRegion,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbgeImmediateRegion {
+    #[serde(rename = "regiao-intermediaria")]
+    regiao_intermediaria: IbgeRegion,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbgeRegion {
+    #[serde(rename = "UF")]
+    uf: IbgeState,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbgeState {
+    sigla: String,
+    nome: String,
+}
+
+impl IbgeMunicipality {
+    /// Municipalities created after 2017 have no microregion, so the state is
+    /// read from the immediate region first.
+    fn into_row(self) -> Result<ImportIbgeCityRowDTO, String> {
+        let state = self
+            .regiao_imediata
+            .map(|region| region.regiao_intermediaria.uf)
+            .or(self.microrregiao.map(|region| region.mesorregiao.uf))
+            .ok_or_else(|| format!("municipality {} has no state", self.id))?;
+
+        Ok(ImportIbgeCityRowDTO {
+            code: self.id.to_string(),
+            name: self.nome,
+            state_code: state.sigla,
+            state_name: state.nome,
+        })
+    }
+}
+
+/// Parses the JSON array returned by the IBGE localities API. The `line` of
+/// each row is its position in the array, starting at 1, and elements that
+/// fail to deserialize are kept so they can be reported.
+pub fn parse_ibge_json(body: &[u8]) -> Result<Vec<CsvRow<ImportIbgeCityRowDTO>>, HttpError> {
+    let elements: Vec<serde_json::Value> = serde_json::from_slice(body)
+        .map_err(|e| HttpError::bad_request(format!("Failed to read the IBGE JSON: {e}")))?;
+
+    let rows = elements
+        .into_iter()
+        .enumerate()
+        .map(|(index, element)| CsvRow {
+            line: index as u64 + 1,
+            record: serde_json::from_value::<IbgeMunicipality>(element)
+                .map_err(|e| e.to_string())
+                .and_then(IbgeMunicipality::into_row),
+        })
+        .collect();
+
+    Ok(rows)
+}
@@ -0,0 +1,60 @@
+use actix_web::HttpResponse;
+use serde::de::DeserializeOwned;
+
+use crate::{dtos::import::ImportReportDTO, error::HttpError};
+
+pub struct CsvRow<T> {
+    /// The line of the row in the uploaded file, counting the header line.
+    pub line: u64,
+    pub record: Result<T, String>,
+}
+
+/// Parses a CSV document whose header line matches the camelCase field names
+/// of `T`. Rows that fail to deserialize are kept so they can be reported.
+pub fn parse_csv<T: DeserializeOwned>(body: &[u8]) -> Result<Vec<CsvRow<T>>, HttpError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| HttpError::bad_request(format!("Failed to read the CSV header: {e}")))?
+        .clone();
+
+    let mut rows = vec![];
+
+    for (index, record) in reader.records().enumerate() {
+        let row = match record {
+            Ok(record) => CsvRow {
+                line: record
+                    .position()
+                    .map(|position| position.line())
+                    .unwrap_or(index as u64 + 2),
+                record: record
+                    .deserialize::<T>(Some(&headers))
+                    .map_err(|e| e.to_string()),
+            },
+            Err(e) => CsvRow {
+                line: e
+                    .position()
+                    .map(|position| position.line())
+                    .unwrap_or(index as u64 + 2),
+                record: Err(e.to_string()),
+            },
+        };
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+pub fn import_response(report: ImportReportDTO) -> HttpResponse {
+    if report.dry_run {
+        HttpResponse::Ok().json(report)
+    } else if report.has_errors() {
+        HttpResponse::UnprocessableEntity().json(report)
+    } else {
+        HttpResponse::Created().json(report)
+    }
+}
@@ -0,0 +1,153 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use sqlx::{Pool, Postgres};
+
+/// Label used for requests that did not match any registered route, so that
+/// unknown paths do not create a new time series each.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Collectors exposed in the Prometheus text format at `/metrics`.
+///
+/// Request metrics are recorded by the `RequestMetrics` middleware, while pool
+/// and domain gauges are refreshed on every scrape.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests_total: IntCounterVec,
+    http_request_duration_seconds: HistogramVec,
+    db_pool_connections: IntGauge,
+    db_pool_idle_connections: IntGauge,
+    db_pool_max_connections: IntGauge,
+    active_routes: IntGauge,
+    drivers_with_expired_cnh: IntGauge,
+}
+
+impl Metrics {
+    /// Creates the collectors and registers them in a dedicated registry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a collector can't be registered, which only happens when two
+    /// collectors share the same name.
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("rusty_roads".to_string()), None)
+            .expect("metrics registry prefix must be valid");
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "Total number of HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .expect("http_requests_total must be valid");
+
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route"],
+        )
+        .expect("http_request_duration_seconds must be valid");
+
+        let db_pool_connections = IntGauge::new(
+            "db_pool_connections",
+            "Connections currently open in the database pool",
+        )
+        .expect("db_pool_connections must be valid");
+
+        let db_pool_idle_connections = IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle connections in the database pool",
+        )
+        .expect("db_pool_idle_connections must be valid");
+
+        let db_pool_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Maximum number of connections in the database pool",
+        )
+        .expect("db_pool_max_connections must be valid");
+
+        let active_routes = IntGauge::new("active_routes", "Routes that have not ended yet")
+            .expect("active_routes must be valid");
+
+        let drivers_with_expired_cnh = IntGauge::new(
+            "drivers_with_expired_cnh",
+            "Drivers whose CNH expiration date has passed",
+        )
+        .expect("drivers_with_expired_cnh must be valid");
+
+        let metrics = Metrics {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_pool_connections,
+            db_pool_idle_connections,
+            db_pool_max_connections,
+            active_routes,
+            drivers_with_expired_cnh,
+        };
+
+        metrics.register();
+
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(self.http_requests_total.clone()),
+            Box::new(self.http_request_duration_seconds.clone()),
+            Box::new(self.db_pool_connections.clone()),
+            Box::new(self.db_pool_idle_connections.clone()),
+            Box::new(self.db_pool_max_connections.clone()),
+            Box::new(self.active_routes.clone()),
+            Box::new(self.drivers_with_expired_cnh.clone()),
+        ];
+
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("metric names must be unique");
+        }
+    }
+
+    /// Records a finished request.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method of the request.
+    /// * `route` - The matched route pattern, e.g. `/api/v1/routes/{id}`.
+    /// * `status` - The response status code.
+    /// * `seconds` - How long the request took to be handled.
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        self.http_requests_total
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+
+        self.http_request_duration_seconds
+            .with_label_values(&[method, route])
+            .observe(seconds);
+    }
+
+    /// Updates the pool gauges from the current state of `pool`.
+    pub fn set_pool(&self, pool: &Pool<Postgres>) {
+        self.db_pool_connections.set(i64::from(pool.size()));
+        self.db_pool_idle_connections.set(pool.num_idle() as i64);
+        self.db_pool_max_connections
+            .set(i64::from(pool.options().get_max_connections()));
+    }
+
+    /// Updates the domain gauges.
+    pub fn set_domain(&self, active_routes: i64, drivers_with_expired_cnh: i64) {
+        self.active_routes.set(active_routes);
+        self.drivers_with_expired_cnh.set(drivers_with_expired_cnh);
+    }
+
+    /// Encodes every registered metric in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
@@ -1,2 +1,18 @@
+pub mod audit;
+pub mod eta;
+pub mod etag;
+pub mod export;
 pub mod fetch;
+pub mod geocoding;
+pub mod geofence;
+pub mod i18n;
+pub mod ibge;
+pub mod import;
+pub mod logging;
+pub mod metrics;
+pub mod password;
+pub mod spatial;
 pub mod string;
+pub mod tracking;
+pub mod webhook;
+pub mod zip_code;
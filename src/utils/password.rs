@@ -0,0 +1,38 @@
+use argon2::{
+    password_hash::{self, PasswordHasher, SaltString},
+    Argon2,
+};
+use uuid::Uuid;
+
+/// Hashes `password` with Argon2id and a random salt, returning the PHC
+/// string to store.
+pub fn hash_password(password: &str) -> Result<String, password_hash::Error> {
+    // A v4 UUID holds 122 random bits from the OS, enough for a salt.
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes())?;
+
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+
+    Ok(hash.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::{PasswordHash, PasswordVerifier};
+
+    use super::*;
+
+    #[test]
+    fn hashes_verifiable_salted_passwords() {
+        let hash = hash_password("correct horse").unwrap();
+        let parsed = PasswordHash::new(&hash).unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(Argon2::default()
+            .verify_password(b"correct horse", &parsed)
+            .is_ok());
+        assert!(Argon2::default()
+            .verify_password(b"battery staple", &parsed)
+            .is_err());
+        assert_ne!(hash, hash_password("correct horse").unwrap());
+    }
+}
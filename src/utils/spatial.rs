@@ -0,0 +1,112 @@
+use validator::ValidationError;
+
+/// Meters in a degree of latitude, used to bound proximity searches.
+pub const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// An area used to filter listings by coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpatialFilter {
+    /// Points within `meters` of a coordinate.
+    Radius {
+        latitude: f64,
+        longitude: f64,
+        meters: f64,
+    },
+    /// Points inside a rectangle of coordinates. Boxes crossing the
+    /// antimeridian aren't supported.
+    BoundingBox {
+        min_longitude: f64,
+        min_latitude: f64,
+        max_longitude: f64,
+        max_latitude: f64,
+    },
+}
+
+/// The latitude and longitude deltas, in degrees, of a circle of `meters`
+/// around a point. Longitudes shrink towards the poles, so the longitude
+/// delta grows with the latitude.
+pub fn degree_deltas(latitude: f64, meters: f64) -> (f64, f64) {
+    let latitude_delta = meters / METERS_PER_DEGREE;
+    let longitude_delta = meters / (METERS_PER_DEGREE * latitude.to_radians().cos().max(0.01));
+
+    (latitude_delta, longitude_delta)
+}
+
+/// Parses a bounding box written as `minLong,minLat,maxLong,maxLat`, the
+/// order used by GeoJSON. Returns `None` when the corners are out of range or
+/// swapped.
+pub fn parse_bbox(bbox: &str) -> Option<SpatialFilter> {
+    let values = bbox
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+
+    let [min_longitude, min_latitude, max_longitude, max_latitude] = values[..] else {
+        return None;
+    };
+
+    let longitudes = -180.0..=180.0;
+    let latitudes = -90.0..=90.0;
+
+    let valid = longitudes.contains(&min_longitude)
+        && longitudes.contains(&max_longitude)
+        && latitudes.contains(&min_latitude)
+        && latitudes.contains(&max_latitude)
+        && min_longitude <= max_longitude
+        && min_latitude <= max_latitude;
+
+    valid.then_some(SpatialFilter::BoundingBox {
+        min_longitude,
+        min_latitude,
+        max_longitude,
+        max_latitude,
+    })
+}
+
+pub fn is_valid_bbox(bbox: &str) -> Result<(), ValidationError> {
+    match parse_bbox(bbox) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("Is not a valid bounding box")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bbox_corners() {
+        assert_eq!(
+            parse_bbox("-46.7,-23.6,-46.5,-23.5"),
+            Some(SpatialFilter::BoundingBox {
+                min_longitude: -46.7,
+                min_latitude: -23.6,
+                max_longitude: -46.5,
+                max_latitude: -23.5,
+            })
+        );
+        assert_eq!(
+            parse_bbox(" -180 , -90 , 180 , 90 "),
+            Some(SpatialFilter::BoundingBox {
+                min_longitude: -180.0,
+                min_latitude: -90.0,
+                max_longitude: 180.0,
+                max_latitude: 90.0,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_bbox_rejects_invalid_boxes() {
+        // Swapped corners.
+        assert_eq!(parse_bbox("-46.5,-23.6,-46.7,-23.5"), None);
+        assert_eq!(parse_bbox("-46.7,-23.5,-46.5,-23.6"), None);
+        // Out of range, given as minLat,minLong,maxLat,maxLong.
+        assert_eq!(parse_bbox("-23.6,-146.7,-23.5,-146.5"), None);
+        assert_eq!(parse_bbox("NaN,-23.6,-46.5,-23.5"), None);
+        assert_eq!(parse_bbox("-46.7,-23.6,-46.5"), None);
+        assert_eq!(parse_bbox("-46.7,-23.6,-46.5,-23.5,0"), None);
+        assert_eq!(parse_bbox("-46.7,-23.6,east,-23.5"), None);
+        assert_eq!(parse_bbox(""), None);
+    }
+}
@@ -1,5 +1,6 @@
 use actix_web::HttpRequest;
 use regex::Regex;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use uuid::Uuid;
 use validator::ValidationError;
 
@@ -12,6 +13,93 @@ pub fn is_valid_uuid(city_id: &str) -> Result<(), ValidationError> {
     }
 }
 
+/// Parses a comma-separated list of UUIDs, as sent in query strings. Returns
+/// `None` when any of them is invalid.
+pub fn parse_uuid_list(ids: &str) -> Option<Vec<Uuid>> {
+    ids.split(',')
+        .map(|id| Uuid::parse_str(id.trim()).ok())
+        .collect()
+}
+
+/// Accepts up to 100 comma-separated UUIDs.
+pub fn is_valid_uuid_list(ids: &str) -> Result<(), ValidationError> {
+    match parse_uuid_list(ids) {
+        Some(ids) if ids.len() <= 100 => Ok(()),
+        _ => Err(ValidationError::new("Is not a valid list of UUIDs")),
+    }
+}
+
+/// A CEP is stored as its 8 digits, without the hyphen.
+pub fn is_valid_zip_code(zip_code: &str) -> Result<(), ValidationError> {
+    match zip_code.len() == 8 && zip_code.chars().all(|c| c.is_ascii_digit()) {
+        true => Ok(()),
+        false => Err(ValidationError::new("Is not a valid zip code")),
+    }
+}
+
+/// Strips the punctuation of a CEP typed as `01001-000` or `01.001-000`.
+/// Returns `None` unless 8 digits remain.
+pub fn normalize_zip_code(zip_code: &str) -> Option<String> {
+    let digits: String = zip_code
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '-' | '.'))
+        .collect();
+
+    is_valid_zip_code(&digits).ok().map(|_| digits)
+}
+
+/// Abbreviations of street types expanded by `normalize_street_name`.
+const STREET_TYPES: &[(&str, &str)] = &[
+    ("al", "alameda"),
+    ("av", "avenida"),
+    ("est", "estrada"),
+    ("estr", "estrada"),
+    ("pc", "praca"),
+    ("pca", "praca"),
+    ("r", "rua"),
+    ("rod", "rodovia"),
+    ("trav", "travessa"),
+    ("tv", "travessa"),
+];
+
+/// Folds a street name so that `Av. São João` and `avenida sao joao` compare
+/// equal: accents and punctuation are removed, the case is lowered, and the
+/// street type is expanded.
+pub fn normalize_street_name(name: &str) -> String {
+    let folded: String = name
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    let mut words: Vec<&str> = folded.split_whitespace().collect();
+
+    if let Some(first) = words.first_mut() {
+        if let Some((_, street_type)) = STREET_TYPES.iter().find(|(short, _)| short == first) {
+            *first = street_type;
+        }
+    }
+
+    words.join(" ")
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, so `value` is matched as typed.
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
 pub fn extract_endpoint_from_path(regex: &str, request: &HttpRequest) -> Result<String, HttpError> {
     let path = request.path();
     let re = Regex::new(regex)
@@ -28,3 +116,75 @@ pub fn extract_endpoint_from_path(regex: &str, request: &HttpRequest) -> Result<
         )),
     }
 }
+
+pub fn to_camel_case(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut uppercase_next = false;
+
+    for c in value.chars() {
+        if c == '_' {
+            uppercase_next = true;
+        } else if uppercase_next {
+            result.extend(c.to_uppercase());
+            uppercase_next = false;
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_like_wildcards() {
+        assert_eq!(escape_like("sao paulo"), "sao paulo");
+        assert_eq!(escape_like("100%"), "100\\%");
+        assert_eq!(escape_like("rua_1"), "rua\\_1");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+        assert_eq!(escape_like(""), "");
+    }
+
+    #[test]
+    fn parse_uuid_list_values() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+
+        assert_eq!(parse_uuid_list(&first.to_string()), Some(vec![first]));
+        assert_eq!(
+            parse_uuid_list(&format!("{first}, {second}")),
+            Some(vec![first, second])
+        );
+        assert_eq!(parse_uuid_list(&format!("{first},not-a-uuid")), None);
+        assert_eq!(parse_uuid_list(&format!("{first},")), None);
+        assert_eq!(parse_uuid_list(""), None);
+    }
+
+    #[test]
+    fn uuid_list_limit() {
+        let ids = vec![Uuid::new_v4().to_string(); 101];
+
+        assert!(is_valid_uuid_list(&ids[..100].join(",")).is_ok());
+        assert!(is_valid_uuid_list(&ids.join(",")).is_err());
+    }
+
+    #[test]
+    fn normalize_zip_code_punctuation() {
+        assert_eq!(normalize_zip_code("01001000"), Some("01001000".to_string()));
+        assert_eq!(
+            normalize_zip_code("01001-000"),
+            Some("01001000".to_string())
+        );
+        assert_eq!(
+            normalize_zip_code(" 01.001-000 "),
+            Some("01001000".to_string())
+        );
+        assert_eq!(normalize_zip_code("0100-000"), None);
+        assert_eq!(normalize_zip_code("01001-0000"), None);
+        assert_eq!(normalize_zip_code("0100a-000"), None);
+        assert_eq!(normalize_zip_code("01001 000"), None);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use actix_web::web::Bytes;
+use futures_util::{stream, Stream};
+use sqlx::postgres::PgListener;
+use tokio::sync::{
+    broadcast::{self, error::RecvError, Receiver, Sender},
+    Notify,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{client::DBClient, route::RouteEventExt},
+    dtos::route::FilterRouteEventDTO,
+    models::route::RouteEvent,
+};
+
+/// The channel notified by the `route_events` table on every insert.
+pub const ROUTE_EVENTS_CHANNEL: &str = "route_events";
+
+/// The channel notified by the `route_events_pending` table when events wait
+/// to be sequenced.
+pub const PENDING_ROUTE_EVENTS_CHANNEL: &str = "route_events_pending";
+
+/// Events kept for a stream that falls behind. Past that, the stream catches
+/// up from the table instead.
+const BROADCAST_CAPACITY: usize = 1024;
+
+/// Events read at once when a stream catches up from the table.
+const REPLAY_PAGE_SIZE: usize = 500;
+
+/// Comments are sent this often on idle streams, so proxies keep them open.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Wait before listening again after an error.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// The pending events are sequenced at least this often.
+const SEQUENCE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Events moved from `route_events_pending` at once.
+const SEQUENCE_BATCH_SIZE: usize = 1000;
+
+/// Events older than this can no longer be resumed from.
+const RETENTION_HOURS: i64 = 24;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What the hub hands to the open streams.
+#[derive(Debug, Clone)]
+pub enum RouteEventMessage {
+    Event(Arc<RouteEvent>),
+    /// Events may have been missed, the streams catch up from the table.
+    Resync,
+}
+
+/// Hands the route events received by this instance to its open streams.
+#[derive(Debug, Clone)]
+pub struct RouteEventHub {
+    sender: Sender<RouteEventMessage>,
+}
+
+impl RouteEventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+
+        RouteEventHub { sender }
+    }
+
+    pub fn subscribe(&self) -> Receiver<RouteEventMessage> {
+        self.sender.subscribe()
+    }
+
+    /// Makes the open streams catch up from the table.
+    pub fn resync(&self) {
+        _ = self.sender.send(RouteEventMessage::Resync);
+    }
+}
+
+/// The routes a stream follows: those in `route_ids` or driven by the
+/// vehicles in `vehicle_ids`. Every route is followed when both are empty.
+#[derive(Debug, Clone, Default)]
+pub struct RouteEventFilter {
+    pub route_ids: Vec<Uuid>,
+    pub vehicle_ids: Vec<Uuid>,
+}
+
+impl RouteEventFilter {
+    pub fn matches(&self, event: &RouteEvent) -> bool {
+        (self.route_ids.is_empty() && self.vehicle_ids.is_empty())
+            || self.route_ids.contains(&event.route_id)
+            || self.vehicle_ids.contains(&event.vehicle_id)
+    }
+}
+
+/// Listens for the route events committed by any instance and publishes
+/// them to `hub`, until the server stops. The pending events are sequenced
+/// meanwhile, see `sequence_route_events`.
+pub async fn listen_route_events(db_client: DBClient, hub: RouteEventHub) {
+    let pending = Arc::new(Notify::new());
+
+    actix_web::rt::spawn(sequence_route_events(
+        db_client.clone(),
+        Arc::clone(&pending),
+    ));
+
+    loop {
+        let mut listener = match PgListener::connect_with(&db_client.pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Error connecting the route events listener: {e}");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+
+        if let Err(e) = listener
+            .listen_all([ROUTE_EVENTS_CHANNEL, PENDING_ROUTE_EVENTS_CHANNEL])
+            .await
+        {
+            error!("Error listening for route events: {e}");
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            continue;
+        }
+
+        info!("Listening for route events.");
+
+        // Notifications sent while not listening are lost, so the events
+        // pending meanwhile are sequenced and the streams read the table
+        // again.
+        pending.notify_one();
+        hub.resync();
+
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(notification))
+                    if notification.channel() == PENDING_ROUTE_EVENTS_CHANNEL =>
+                {
+                    pending.notify_one();
+                }
+                Ok(Some(notification)) => {
+                    match serde_json::from_str::<RouteEvent>(notification.payload()) {
+                        // Sending only fails when no stream is open.
+                        Ok(event) => _ = hub.sender.send(RouteEventMessage::Event(Arc::new(event))),
+                        Err(e) => warn!("Error parsing a route event: {e}"),
+                    }
+                }
+                Ok(None) => {
+                    warn!("Lost the route events connection, listening again.");
+                    break;
+                }
+                Err(e) => {
+                    error!("Error receiving route events: {e}");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Moves the committed events from `route_events_pending` to
+/// `route_events` when `pending` is notified, or every
+/// `SEQUENCE_INTERVAL` in case a notification was missed.
+async fn sequence_route_events(db_client: DBClient, pending: Arc<Notify>) {
+    loop {
+        _ = tokio::time::timeout(SEQUENCE_INTERVAL, pending.notified()).await;
+
+        loop {
+            match db_client.sequence_route_events(SEQUENCE_BATCH_SIZE).await {
+                Ok(moved) if moved == SEQUENCE_BATCH_SIZE as u64 => {}
+                Ok(_) => break,
+                Err(e) => {
+                    warn!("Error sequencing route events: {e}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Deletes the route events past their retention every hour.
+pub async fn prune_route_events(db_client: DBClient) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let before = chrono::Utc::now().naive_utc() - chrono::Duration::hours(RETENTION_HOURS);
+
+        match db_client.delete_route_events(before).await {
+            Ok(deleted) if deleted > 0 => info!(deleted, "Old route events deleted."),
+            Ok(_) => {}
+            Err(e) => warn!("Error deleting old route events: {e}"),
+        }
+    }
+}
+
+enum Phase {
+    /// Reading the events after `last_id` from the table.
+    Replay,
+    /// Forwarding the events published to the hub.
+    Live,
+}
+
+struct StreamState {
+    db_client: DBClient,
+    receiver: Receiver<RouteEventMessage>,
+    filter: RouteEventFilter,
+    phase: Phase,
+    pending: VecDeque<RouteEvent>,
+    /// The latest event sent, or the one the client resumes after.
+    last_id: i64,
+}
+
+/// Streams the events matching `filter` after `last_id` as Server-Sent
+/// Events. The missed events are read from the table first, then the live
+/// ones are forwarded from `receiver`, which must be subscribed before the
+/// call so no event falls in between.
+pub fn route_event_stream(
+    db_client: DBClient,
+    receiver: Receiver<RouteEventMessage>,
+    filter: RouteEventFilter,
+    last_id: i64,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let state = StreamState {
+        db_client,
+        receiver,
+        filter,
+        phase: Phase::Replay,
+        pending: VecDeque::new(),
+        last_id,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                state.last_id = event.id;
+                return Some((Ok(to_message(&event)), state));
+            }
+
+            match state.phase {
+                Phase::Replay => {
+                    let events = state
+                        .db_client
+                        .list_route_events(
+                            state.last_id,
+                            &state.filter.route_ids,
+                            &state.filter.vehicle_ids,
+                            REPLAY_PAGE_SIZE,
+                        )
+                        .await;
+
+                    match events {
+                        Ok(events) if events.is_empty() => state.phase = Phase::Live,
+                        Ok(events) => state.pending.extend(events),
+                        Err(e) => {
+                            warn!("Error replaying route events: {e}");
+                            return None;
+                        }
+                    }
+                }
+                Phase::Live => {
+                    match tokio::time::timeout(KEEPALIVE_INTERVAL, state.receiver.recv()).await {
+                        Err(_) => {
+                            return Some((Ok(Bytes::from_static(b": keepalive\n\n")), state));
+                        }
+                        // Events up to `last_id` were already sent from the
+                        // table.
+                        Ok(Ok(RouteEventMessage::Event(event))) => {
+                            if event.id > state.last_id && state.filter.matches(&event) {
+                                state.last_id = event.id;
+                                return Some((Ok(to_message(&event)), state));
+                            }
+                        }
+                        Ok(Ok(RouteEventMessage::Resync)) | Ok(Err(RecvError::Lagged(_))) => {
+                            state.phase = Phase::Replay
+                        }
+                        Ok(Err(RecvError::Closed)) => return None,
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Formats an event as a Server-Sent Events message, named after its type.
+fn to_message(event: &RouteEvent) -> Bytes {
+    let data =
+        serde_json::to_string(&FilterRouteEventDTO::filter_route_event(event)).unwrap_or_default();
+
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {data}\n\n",
+        event.id, event.event_type
+    ))
+}
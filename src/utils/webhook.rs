@@ -0,0 +1,367 @@
+use std::{fmt, time::Duration};
+
+use chrono::NaiveDateTime;
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::{header::CONTENT_TYPE, redirect::Policy, Client, Url};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use validator::ValidationError;
+
+use crate::{
+    db::{client::DBClient, webhook::WebhookExt},
+    models::webhook::PendingWebhookDelivery,
+};
+
+/// The ID of the event, the same on every attempt so receivers can ignore
+/// the deliveries they already processed.
+pub const WEBHOOK_ID_HEADER: &str = "X-Webhook-Id";
+pub const WEBHOOK_EVENT_HEADER: &str = "X-Webhook-Event";
+/// The Unix time the request was signed at, part of the signed content.
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// `sha256=` followed by the hex HMAC-SHA256 of `{timestamp}.{body}`, keyed
+/// with the secret of the subscription.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// A delivery is given up on after this many failed attempts.
+pub const MAX_ATTEMPTS: i32 = 10;
+
+/// The wait after the first failed attempt, doubled after each one.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How long an endpoint may take to answer before the attempt fails.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a claimed delivery is reserved for the instance sending it. Past
+/// that, it is sent again, so deliveries interrupted by a crash aren't lost.
+const LEASE: Duration = Duration::from_secs(60);
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const BATCH_SIZE: i64 = 20;
+const CNH_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The longest error kept in the delivery log.
+const MAX_ERROR_LENGTH: usize = 500;
+
+/// Finished deliveries are kept in the log this long.
+const RETENTION_DAYS: i64 = 30;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEventType {
+    RouteCreated,
+    RouteStarted,
+    RouteFinished,
+    DriverCnhExpired,
+    /// Sent on demand to check a subscription. It can't be subscribed to.
+    Ping,
+}
+
+impl WebhookEventType {
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "route.created" => Some(WebhookEventType::RouteCreated),
+            "route.started" => Some(WebhookEventType::RouteStarted),
+            "route.finished" => Some(WebhookEventType::RouteFinished),
+            "driver.cnh_expired" => Some(WebhookEventType::DriverCnhExpired),
+            "ping" => Some(WebhookEventType::Ping),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WebhookEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            WebhookEventType::RouteCreated => "route.created",
+            WebhookEventType::RouteStarted => "route.started",
+            WebhookEventType::RouteFinished => "route.finished",
+            WebhookEventType::DriverCnhExpired => "driver.cnh_expired",
+            WebhookEventType::Ping => "ping",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookDeliveryStatus {
+    /// Waiting for its first attempt or a retry.
+    Pending,
+    Delivered,
+    /// Given up on after `MAX_ATTEMPTS`.
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "pending" => Some(WebhookDeliveryStatus::Pending),
+            "delivered" => Some(WebhookDeliveryStatus::Delivered),
+            "failed" => Some(WebhookDeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WebhookDeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+pub fn is_valid_webhook_event_types(event_types: &[String]) -> Result<(), ValidationError> {
+    let valid = event_types.iter().all(|event_type| {
+        !matches!(
+            WebhookEventType::from_code(event_type),
+            None | Some(WebhookEventType::Ping)
+        )
+    });
+
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("Is not a valid list of event types")),
+    }
+}
+
+pub fn is_valid_webhook_delivery_status(status: &str) -> Result<(), ValidationError> {
+    match WebhookDeliveryStatus::from_code(status) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("Is not a valid delivery status")),
+    }
+}
+
+/// Accepts absolute `http` and `https` URLs. Plain `http` is allowed so a
+/// local endpoint can be used while testing.
+pub fn is_valid_webhook_url(url: &str) -> Result<(), ValidationError> {
+    match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => Err(ValidationError::new("Is not a valid webhook URL")),
+    }
+}
+
+/// A random secret for the subscriptions created without one.
+pub fn generate_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// The hex HMAC-SHA256 of `{timestamp}.{body}` keyed with `secret`, sent in
+/// `WEBHOOK_SIGNATURE_HEADER`.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// How long to wait before attempting a delivery again after `attempts`
+/// failed attempts.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.pow(exponent))
+        .min(RETRY_MAX_DELAY)
+}
+
+/// The body sent to the subscriptions.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookPayload<'a> {
+    id: Uuid,
+    #[serde(rename = "type")]
+    event_type: &'a str,
+    created_at: NaiveDateTime,
+    data: &'a serde_json::Value,
+}
+
+/// Sends the pending deliveries and records the expired CNHs, until the
+/// server stops.
+///
+/// Deliveries are claimed with a lease, so several instances can run this
+/// together without sending a delivery twice, unless one of them stops while
+/// sending it.
+pub async fn dispatch_webhooks(db_client: DBClient) {
+    let client = match Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .redirect(Policy::none())
+        .user_agent(concat!("rusty_roads/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Error building the webhook client, webhooks won't be sent: {e}");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    let mut next_cnh_check = tokio::time::Instant::now();
+
+    info!("Dispatching webhooks.");
+
+    loop {
+        interval.tick().await;
+
+        if tokio::time::Instant::now() >= next_cnh_check {
+            match db_client.enqueue_expired_cnh_events().await {
+                Ok(enqueued) if enqueued > 0 => info!(enqueued, "Expired CNHs recorded."),
+                Ok(_) => {}
+                Err(e) => warn!("Error recording expired CNHs: {e}"),
+            }
+
+            next_cnh_check = tokio::time::Instant::now() + CNH_CHECK_INTERVAL;
+        }
+
+        loop {
+            let deliveries = match db_client
+                .claim_webhook_deliveries(BATCH_SIZE, LEASE.as_secs_f64())
+                .await
+            {
+                Ok(deliveries) => deliveries,
+                Err(e) => {
+                    warn!("Error claiming webhook deliveries: {e}");
+                    break;
+                }
+            };
+
+            join_all(
+                deliveries
+                    .iter()
+                    .map(|delivery| deliver(&client, &db_client, delivery)),
+            )
+            .await;
+
+            if (deliveries.len() as i64) < BATCH_SIZE {
+                break;
+            }
+        }
+    }
+}
+
+/// Deletes the deliveries and events past their retention every hour.
+pub async fn prune_webhook_events(db_client: DBClient) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let before = chrono::Utc::now().naive_utc() - chrono::Duration::days(RETENTION_DAYS);
+
+        match db_client.delete_webhook_events(before).await {
+            Ok((deliveries, events)) if deliveries + events > 0 => {
+                info!(deliveries, events, "Old webhook deliveries deleted.")
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Error deleting old webhook deliveries: {e}"),
+        }
+    }
+}
+
+async fn deliver(client: &Client, db_client: &DBClient, delivery: &PendingWebhookDelivery) {
+    let body = serde_json::to_string(&WebhookPayload {
+        id: delivery.event_id,
+        event_type: &delivery.event_type,
+        created_at: delivery.event_created_at,
+        data: &delivery.data,
+    })
+    .unwrap_or_default();
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign_payload(&delivery.secret, timestamp, &body);
+
+    let response = client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(WEBHOOK_ID_HEADER, delivery.event_id.to_string())
+        .header(WEBHOOK_EVENT_HEADER, &delivery.event_type)
+        .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+        .header(WEBHOOK_SIGNATURE_HEADER, format!("sha256={signature}"))
+        .body(body)
+        .send()
+        .await;
+
+    let (response_status, error) = match response {
+        Ok(response) if response.status().is_success() => {
+            let status = response.status().as_u16() as i32;
+
+            if let Err(e) = db_client
+                .complete_webhook_delivery(delivery.id, status)
+                .await
+            {
+                warn!(delivery_id = %delivery.id, "Error recording a webhook delivery: {e}");
+            }
+
+            return;
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            format!("The endpoint answered {}", response.status()),
+        ),
+        Err(e) => (None, e.to_string()),
+    };
+
+    let retry_in =
+        (delivery.attempts < MAX_ATTEMPTS).then(|| retry_delay(delivery.attempts).as_secs_f64());
+
+    warn!(
+        delivery_id = %delivery.id,
+        attempts = delivery.attempts,
+        retrying = retry_in.is_some(),
+        "Error delivering a webhook: {error}"
+    );
+
+    let error: String = error.chars().take(MAX_ERROR_LENGTH).collect();
+
+    if let Err(e) = db_client
+        .fail_webhook_delivery(delivery.id, response_status, &error, retry_in)
+        .await
+    {
+        warn!(delivery_id = %delivery.id, "Error recording a webhook delivery: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_the_timestamp_and_the_body() {
+        assert_eq!(
+            sign_payload("whsec_test", 1700000000, r#"{"id":1}"#),
+            "2f441ba4b3b2d50d28a9ab9d9fd8880376ecd1eb5d0435401553f5d8d0a5dcf8"
+        );
+        assert_ne!(
+            sign_payload("whsec_test", 1700000001, r#"{"id":1}"#),
+            sign_payload("whsec_test", 1700000000, r#"{"id":1}"#)
+        );
+    }
+
+    #[test]
+    fn doubles_the_retry_delay_up_to_six_hours() {
+        assert_eq!(retry_delay(0), Duration::from_secs(30));
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(10), Duration::from_secs(30 * 512));
+        assert_eq!(retry_delay(11), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(i32::MAX), RETRY_MAX_DELAY);
+    }
+}
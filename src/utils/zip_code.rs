@@ -0,0 +1,135 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    config::{Config, ZipCodeProviderKind},
+    db::{city::CityExt, client::DBClient, zip_code::ZipCodeExt},
+    error::HttpError,
+    models::zip_code::ZipCode,
+};
+
+const VIACEP_URL: &str = "https://viacep.com.br/ws";
+
+/// How long a request to an HTTP provider may take before the lookup fails.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolves a CEP into the street, neighbourhood and city it covers.
+#[async_trait]
+pub trait ZipCodeProvider: Debug + Send + Sync {
+    /// A short name of the provider, returned with every lookup.
+    fn name(&self) -> &'static str;
+
+    /// Looks up `zip_code`, given as its 8 digits. Returns `None` when the
+    /// CEP, or the city it belongs to, is unknown.
+    async fn lookup(
+        &self,
+        db_client: &DBClient,
+        zip_code: &str,
+    ) -> Result<Option<ZipCode>, HttpError>;
+}
+
+/// Reads the `zip_codes` table, filled by `import zip-codes`.
+#[derive(Debug)]
+pub struct LocalZipCodeProvider;
+
+#[async_trait]
+impl ZipCodeProvider for LocalZipCodeProvider {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn lookup(
+        &self,
+        db_client: &DBClient,
+        zip_code: &str,
+    ) -> Result<Option<ZipCode>, HttpError> {
+        db_client
+            .get_zip_code(zip_code)
+            .await
+            .map_err(HttpError::from)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ViaCepResponse {
+    logradouro: Option<String>,
+    bairro: Option<String>,
+    ibge: Option<String>,
+    /// Set instead of the other fields when the CEP doesn't exist.
+    erro: Option<serde_json::Value>,
+}
+
+/// Asks the ViaCEP API, and matches the IBGE code it returns with a city.
+#[derive(Debug)]
+pub struct ViaCepProvider {
+    client: reqwest::Client,
+}
+
+impl ViaCepProvider {
+    /// Builds the HTTP client shared by every lookup.
+    pub fn new() -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?;
+
+        Ok(ViaCepProvider { client })
+    }
+}
+
+#[async_trait]
+impl ZipCodeProvider for ViaCepProvider {
+    fn name(&self) -> &'static str {
+        "viacep"
+    }
+
+    async fn lookup(
+        &self,
+        db_client: &DBClient,
+        zip_code: &str,
+    ) -> Result<Option<ZipCode>, HttpError> {
+        let response: ViaCepResponse = self
+            .client
+            .get(format!("{VIACEP_URL}/{zip_code}/json/"))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| HttpError::server_error(format!("ViaCEP request failed: {e}")))?
+            .json()
+            .await
+            .map_err(|e| HttpError::server_error(format!("Invalid ViaCEP response: {e}")))?;
+
+        if response.erro.is_some() {
+            return Ok(None);
+        }
+
+        let Some(city_code) = response.ibge else {
+            return Ok(None);
+        };
+
+        let Some(city) = db_client
+            .get_city(None, Some(&city_code))
+            .await
+            .map_err(HttpError::from)?
+        else {
+            warn!("ViaCEP returned the unknown city code {city_code}");
+            return Ok(None);
+        };
+
+        Ok(Some(ZipCode {
+            zip_code: zip_code.to_string(),
+            address: response.logradouro.filter(|value| !value.is_empty()),
+            neighbourhood: response.bairro.filter(|value| !value.is_empty()),
+            city_id: city.id,
+        }))
+    }
+}
+
+/// Builds the provider selected by `zip_code_provider` in the configuration,
+/// once at startup.
+pub fn zip_code_provider(config: &Config) -> Result<Arc<dyn ZipCodeProvider>, reqwest::Error> {
+    Ok(match config.zip_code_provider {
+        ZipCodeProviderKind::Local => Arc::new(LocalZipCodeProvider),
+        ZipCodeProviderKind::ViaCep => Arc::new(ViaCepProvider::new()?),
+    })
+}